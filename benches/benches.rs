@@ -2,6 +2,17 @@ use criterion::{black_box, criterion_group, criterion_main, Benchmark, Criterion
 use walrus::Module;
 
 fn criterion_benchmark(c: &mut Criterion) {
+    c.bench(
+        "parse",
+        Benchmark::new("dodrio-todomvc.wasm", |b| {
+            let input_wasm = include_bytes!("./fixtures/dodrio-todomvc.wasm");
+            b.iter(|| {
+                let input_wasm = black_box(input_wasm);
+                let module = Module::from_buffer(input_wasm).unwrap();
+                black_box(module);
+            });
+        }),
+    );
     c.bench(
         "round-trip-with-gc",
         Benchmark::new("dodrio-todomvc.wasm", |b| {
@@ -8,9 +8,9 @@ use std::cmp;
 use std::fmt;
 use std::fs;
 use std::marker::PhantomData;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time;
-use walrus_tests_utils::wasm_interp;
+use walrus_tests_utils::{wasm_interp, wasm_validate};
 
 /// `Ok(T)` or a `Err(anyhow::Error)`
 pub type Result<T> = std::result::Result<T, anyhow::Error>;
@@ -37,8 +37,10 @@ where
 {
     _generator: PhantomData<G>,
     rng: R,
+    seed: Option<u64>,
     fuel: usize,
     timeout: u64,
+    dir: PathBuf,
     scratch: tempfile::NamedTempFile,
 }
 
@@ -70,17 +72,28 @@ where
             .join("walrus-fuzz");
         fs::create_dir_all(&dir).expect(&format!("should create directory: {:?}", dir));
 
-        let scratch = tempfile::NamedTempFile::new_in(dir).expect("should create temp file OK");
+        let scratch = tempfile::NamedTempFile::new_in(&dir).expect("should create temp file OK");
 
         Config {
             _generator: PhantomData,
             rng,
+            seed: None,
             fuel,
             timeout,
+            dir,
             scratch,
         }
     }
 
+    /// Record the seed that the RNG was created from.
+    ///
+    /// This doesn't affect the RNG itself; it is only included in failing test
+    /// cases, so that they can be reproduced.
+    pub fn set_seed(mut self, seed: u64) -> Config<G, R> {
+        self.seed = Some(seed);
+        self
+    }
+
     /// Set the fuel level.
     ///
     /// `fuel` must be greater than zero.
@@ -103,6 +116,11 @@ where
         wasm_interp(self.scratch.path())
     }
 
+    fn validate(&self, wasm: &[u8]) -> Result<()> {
+        fs::write(self.scratch.path(), &wasm).context("failed to write to scratch file")?;
+        wasm_validate(self.scratch.path())
+    }
+
     fn round_trip_through_walrus(&self, wasm: &[u8]) -> Result<Vec<u8>> {
         let mut module =
             walrus::Module::from_buffer(&wasm).context("walrus failed to parse the wasm buffer")?;
@@ -116,23 +134,101 @@ where
         let expected = self.interp(&wasm)?;
 
         let walrus_wasm = self.round_trip_through_walrus(&wasm)?;
-        let actual = self.interp(&walrus_wasm)?;
 
-        if expected == actual {
-            return Ok(());
-        }
+        // Check that the output is well-formed on its own, since a broken
+        // encoding can go unnoticed by the comparison below if the interpreter
+        // never gets as far as running the module.
+        let failure = match self.validate(&walrus_wasm) {
+            Err(e) => Failure::InvalidOutput {
+                error: format!("{:?}", e),
+            },
+            Ok(()) => {
+                let actual = self.interp(&walrus_wasm)?;
+                if run_result(&expected) != run_result(&actual) {
+                    Failure::ReturnValueDiffers {
+                        expected: run_result(&expected).unwrap_or("<missing>").to_string(),
+                        actual: run_result(&actual).unwrap_or("<missing>").to_string(),
+                    }
+                } else if expected == actual {
+                    return Ok(());
+                } else {
+                    Failure::ExecutionDiffers { expected, actual }
+                }
+            }
+        };
 
         Err(FailingTestCase {
             generator: G::NAME,
+            seed: self.seed,
+            fuel: self.fuel,
             wat: wat.to_string(),
-            expected,
-            actual,
+            failure,
         }
         .into())
     }
 
-    /// Generate a single wasm file and then compare its output in the reference
-    /// interpreter before and after round tripping it through `walrus`.
+    /// Shrink a failing test case by repeatedly deleting chunks of lines from
+    /// its WAT, keeping any deletion after which round tripping still fails.
+    fn shrink(&self, mut case: FailingTestCase) -> FailingTestCase {
+        let mut chunk = cmp::max(case.wat.lines().count() / 2, 1);
+        loop {
+            let mut i = 0;
+            loop {
+                let lines: Vec<&str> = case.wat.lines().collect();
+                if i >= lines.len() {
+                    break;
+                }
+                let end = cmp::min(i + chunk, lines.len());
+                let candidate = lines[..i]
+                    .iter()
+                    .chain(&lines[end..])
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                match self.test_wat(&candidate) {
+                    Err(e) => match e.downcast::<FailingTestCase>() {
+                        // Still failing, so keep the smaller test case and try
+                        // deleting the lines that are now at `i`.
+                        Ok(smaller) => case = smaller,
+                        Err(_) => i = end,
+                    },
+                    Ok(()) => i = end,
+                }
+            }
+            if chunk == 1 {
+                return case;
+            }
+            chunk /= 2;
+        }
+    }
+
+    /// Reduce a failing test case, and write it out as a standalone test to a
+    /// file in `target/walrus-fuzz/`.
+    fn reduce(&self, e: anyhow::Error) -> anyhow::Error {
+        let case = match e.downcast::<FailingTestCase>() {
+            Ok(case) => case,
+            Err(e) => return e,
+        };
+        let case = self.shrink(case);
+
+        let timestamp = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = self
+            .dir
+            .join(format!("{}-{}.rs", G::NAME.to_lowercase(), timestamp));
+        match fs::write(&path, case.standalone_test()) {
+            Ok(()) => eprintln!("Wrote reduced test case to {}", path.display()),
+            Err(e) => eprintln!("Warning: failed to write {}: {}", path.display(), e),
+        }
+
+        case.into()
+    }
+
+    /// Generate a single wasm file, round trip it through `walrus`, and check
+    /// that the result validates and that its output in the reference
+    /// interpreter is the same as before.
     ///
     /// Does not attempt to reduce any failing test cases.
     pub fn run_one(&mut self) -> Result<()> {
@@ -145,7 +241,8 @@ where
     /// Generate and test as many wasm files as we can within the configured
     /// timeout budget.
     ///
-    /// Returns the reduced failing test case, if any.
+    /// Returns the reduced failing test case, if any, after also writing it out
+    /// as a standalone test to a file in `target/walrus-fuzz/`.
     pub fn run(&mut self) -> Result<()> {
         let start = time::Instant::now();
         let timeout = time::Duration::from_secs(self.timeout);
@@ -160,8 +257,8 @@ where
                 Ok(()) => {
                     // We reduced fuel as far as we could, so return the last
                     // failing test case.
-                    if failing.is_err() {
-                        return failing;
+                    if let Err(e) = failing {
+                        return Err(self.reduce(e));
                     }
 
                     // This did not produce a failing test case, so generate a
@@ -179,7 +276,7 @@ where
                     if self.fuel > 1 {
                         self.fuel -= (self.fuel / 10).max(1);
                     } else {
-                        return failing;
+                        return failing.map_err(|e| self.reduce(e));
                     }
                 }
             }
@@ -187,34 +284,108 @@ where
     }
 }
 
-/// A failing wasm test case where round tripping the wasm through walrus
-/// produces an observably different execution in the reference interpreter.
+/// A failing wasm test case, where round tripping the wasm through walrus
+/// produces invalid wasm or an observably different execution in the
+/// reference interpreter.
 #[derive(Clone, Debug)]
 pub struct FailingTestCase {
     /// The WAT disassembly of the wasm test case.
     pub wat: String,
 
-    /// The reference interpeter's output while interpreting the wasm *before* it
-    /// has been round tripped through `walrus`.
-    pub expected: String,
-
-    /// The reference interpeter's output while interpreting the wasm *after* it
-    /// has been round tripped through `walrus`.
-    pub actual: String,
+    /// How round tripping the test case failed.
+    pub failure: Failure,
 
     /// The test case generator that created this failing test case.
     pub generator: &'static str,
+
+    /// The seed of the RNG used to generate this test case, if known.
+    pub seed: Option<u64>,
+
+    /// The fuel used to generate this test case.
+    pub fuel: usize,
 }
 
-impl fmt::Display for FailingTestCase {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(
-            f,
-            "\
-Found a failing test case!
+/// The ways in which round tripping a test case through walrus can fail.
+#[derive(Clone, Debug)]
+pub enum Failure {
+    /// The wasm emitted by walrus doesn't pass `wasm-validate`.
+    InvalidOutput {
+        /// The validation error.
+        error: String,
+    },
+
+    /// The exported `run` function returns something different from what it
+    /// did in the original wasm.
+    ReturnValueDiffers {
+        /// What `run` returned *before* round tripping through `walrus`, as
+        /// printed by the reference interpreter.
+        expected: String,
+
+        /// What `run` returned *after* round tripping through `walrus`, as
+        /// printed by the reference interpreter.
+        actual: String,
+    },
+
+    /// The wasm emitted by walrus behaves differently from the original.
+    ExecutionDiffers {
+        /// The reference interpeter's output while interpreting the wasm
+        /// *before* it has been round tripped through `walrus`.
+        expected: String,
+
+        /// The reference interpeter's output while interpreting the wasm
+        /// *after* it has been round tripped through `walrus`.
+        actual: String,
+    },
+}
 
-{wat}
+impl FailingTestCase {
+    /// Get a standalone Rust test that reproduces this failing test case.
+    pub fn standalone_test(&self) -> String {
+        let seed = match self.seed {
+            Some(seed) => seed.to_string(),
+            None => "unknown".to_string(),
+        };
+        format!(
+            "\
+#[test]
+fn test_name() {{
+    // Found by the {generator} generator with seed {seed} and fuel {fuel}.
+    walrus_fuzz::assert_round_trip_execution_is_same(\"\\
+{wat}\");
+}}
+",
+            generator = self.generator,
+            seed = seed,
+            fuel = self.fuel,
+            wat = self.wat,
+        )
+    }
+}
 
+impl fmt::Display for FailingTestCase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Found a failing test case!\n\n{}\n", self.wat)?;
+        match &self.failure {
+            Failure::InvalidOutput { error } => writeln!(
+                f,
+                "\
+The wasm emitted by walrus does not validate:
+
+{error}
+",
+                error = error,
+            )?,
+            Failure::ReturnValueDiffers { expected, actual } => writeln!(
+                f,
+                "\
+`run` returned {before} BEFORE round tripping through walrus, but {after} AFTER.
+",
+                before = expected,
+                after = actual,
+            )?,
+            Failure::ExecutionDiffers { expected, actual } => writeln!(
+                f,
+                "\
 BEFORE round tripping through walrus:
 
 {before}
@@ -222,48 +393,77 @@ BEFORE round tripping through walrus:
 AFTER round tripping through walrus:
 
 {after}
-
+",
+                before = expected,
+                after = actual,
+            )?,
+        }
+        writeln!(
+            f,
+            "\
 Here is a standalone test case:
 
 ----------------8<----------------8<----------------8<----------------
-#[test]
-fn test_name() {{
-    walrus_fuzz::assert_round_trip_execution_is_same(\"\\
-{wat}\");
-}}
-----------------8<----------------8<----------------8<----------------
+{test}----------------8<----------------8<----------------8<----------------
 ",
-            wat = self.wat,
-            before = self.expected,
-            after = self.actual,
+            test = self.standalone_test(),
         )
     }
 }
 
 impl std::error::Error for FailingTestCase {}
 
-/// Assert that the given WAT has the same execution trace before and after
-/// round tripping it through walrus.
+/// Find the result of calling the exported `run` function in the reference
+/// interpreter's output, if the test case has one.
+///
+/// Generators that can should export a `run` function, taking no parameters
+/// and returning an `i32`, that drives the rest of the test case. Its return
+/// value is compared before and after round tripping, which is a sharper check
+/// than comparing the interpreter's whole output.
+fn run_result(output: &str) -> Option<&str> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("run() => "))
+        .map(|result| result.trim())
+}
+
+/// Assert that round tripping the given WAT through walrus produces valid
+/// wasm, with the same execution trace as before.
 pub fn assert_round_trip_execution_is_same(wat: &str) {
     let config = Config::<WasmOptTtf, SmallRng>::new(SmallRng::seed_from_u64(0));
     if let Err(e) = config.test_wat(wat) {
         print_err(&e);
-        panic!("round trip execution is not the same!");
+        panic!("round trip produced invalid wasm or a different execution!");
     }
 }
 
 /// A simple WAT generator.
-pub struct WatGen<R: Rng> {
+///
+/// This is `WatGenWith` with `unreachable` code generated for about one in
+/// every twenty instructions that don't take any operands.
+pub type WatGen<R> = WatGenWith<R, 20>;
+
+/// A simple WAT generator, which generates `unreachable` code for about one in
+/// every `UNREACHABLE_ONE_IN` instructions that don't take any operands, or
+/// never if it is zero.
+///
+/// Each `unreachable` is followed by some dead instructions, to exercise the
+/// handling of the polymorphic stack after it. It is put inside an `if` whose
+/// condition is always false, so that it never actually traps and the
+/// execution of the rest of the test case can still be compared.
+pub struct WatGenWith<R: Rng, const UNREACHABLE_ONE_IN: u32> {
     rng: R,
     wat: String,
 }
 
-impl<R: Rng> TestCaseGenerator for WatGen<R> {
+impl<R: Rng, const UNREACHABLE_ONE_IN: u32> TestCaseGenerator
+    for WatGenWith<R, UNREACHABLE_ONE_IN>
+{
     const NAME: &'static str = "WatGen";
 
     fn generate(rng: &mut impl Rng, fuel: usize) -> String {
         let wat = String::new();
-        let mut g = WatGen { rng, wat };
+        let mut g = WatGenWith::<_, UNREACHABLE_ONE_IN> { rng, wat };
         g.prefix();
         g.gen_instructions(fuel);
         g.suffix();
@@ -271,13 +471,13 @@ impl<R: Rng> TestCaseGenerator for WatGen<R> {
     }
 }
 
-impl<R: Rng> WatGen<R> {
+impl<R: Rng, const UNREACHABLE_ONE_IN: u32> WatGenWith<R, UNREACHABLE_ONE_IN> {
     fn prefix(&mut self) {
         self.wat.push_str(
             "\
 (module
   (import \"host\" \"print\" (func (param i32) (result i32)))
-  (func (export \"$f\")
+  (func (export \"run\") (result i32)
 ",
         );
     }
@@ -298,9 +498,12 @@ impl<R: Rng> WatGen<R> {
             }
         }
 
-        for _ in stack {
-            self.instr("call 0");
-            self.instr("drop");
+        // Return the sum of everything left on the stack.
+        if stack.is_empty() {
+            self.instr_imm("i32.const", Some("0"));
+        }
+        for _ in 1..stack.len() {
+            self.instr("i32.add");
         }
     }
 
@@ -335,6 +538,10 @@ impl<R: Rng> WatGen<R> {
     }
 
     fn op_0(&mut self, stack: &mut Vec<ValType>) {
+        if UNREACHABLE_ONE_IN > 0 && self.rng.gen_range(0, UNREACHABLE_ONE_IN) == 0 {
+            self.dead_code();
+            return;
+        }
         match self.rng.gen_range(0, 2) {
             0 => {
                 let value = self.rng.gen::<i32>().to_string();
@@ -348,6 +555,46 @@ impl<R: Rng> WatGen<R> {
         }
     }
 
+    /// Generate an `unreachable` followed by some dead instructions, inside an
+    /// `if` that is never taken.
+    fn dead_code(&mut self) {
+        self.instr_imm("i32.const", Some("0"));
+        self.instr("if");
+        self.instr("unreachable");
+
+        // The number of values pushed since the `unreachable`. Instructions
+        // can pop more values than that, since the stack is polymorphic, but
+        // whatever they push must be dropped before the `end`.
+        let mut pushed = 0;
+        for _ in 0..self.rng.gen_range(0, 5) {
+            match self.rng.gen_range(0, 4) {
+                0 => {
+                    let value = self.rng.gen::<i32>().to_string();
+                    self.instr_imm("i32.const", Some(value));
+                    pushed += 1;
+                }
+                1 => {
+                    self.instr("drop");
+                    pushed = cmp::max(pushed, 1) - 1;
+                }
+                2 => {
+                    self.instr("i32.popcnt");
+                    pushed = cmp::max(pushed, 1);
+                }
+                3 => {
+                    self.instr("i32.add");
+                    pushed = cmp::max(pushed, 2) - 1;
+                }
+                _ => unreachable!(),
+            }
+        }
+        for _ in 0..pushed {
+            self.instr("drop");
+        }
+
+        self.instr("end");
+    }
+
     fn op_1(&mut self, _operand: ValType, stack: &mut Vec<ValType>) {
         match self.rng.gen_range(0, 2) {
             0 => {
@@ -432,6 +679,35 @@ impl TestCaseGenerator for WasmOptTtf {
     }
 }
 
+/// Mix the test cases of two generators in one fuzzing session.
+///
+/// Each test case comes from `A` with probability `A_WEIGHT / (A_WEIGHT +
+/// B_WEIGHT)`, and from `B` otherwise, chosen with the same RNG that the
+/// generators then use, so a seed still determines the test case. Nest
+/// `Weighted`s to mix in more than two generators.
+pub struct Weighted<A, B, const A_WEIGHT: u32, const B_WEIGHT: u32>(PhantomData<(A, B)>);
+
+impl<A, B, const A_WEIGHT: u32, const B_WEIGHT: u32> TestCaseGenerator
+    for Weighted<A, B, A_WEIGHT, B_WEIGHT>
+where
+    A: TestCaseGenerator,
+    B: TestCaseGenerator,
+{
+    const NAME: &'static str = "Weighted";
+
+    fn generate(rng: &mut impl Rng, fuel: usize) -> String {
+        assert!(
+            A_WEIGHT + B_WEIGHT > 0,
+            "at least one generator must have a non-zero weight"
+        );
+        if rng.gen_range(0, A_WEIGHT + B_WEIGHT) < A_WEIGHT {
+            A::generate(rng, fuel)
+        } else {
+            B::generate(rng, fuel)
+        }
+    }
+}
+
 /// Print a `anyhow::Error` with its chain.
 pub fn print_err(e: &anyhow::Error) {
     eprintln!("Error: {:?}", e);
@@ -451,9 +727,9 @@ mod tests {
 
     #[test]
     fn watgen_fuzz() {
-        let mut config = Config::<WatGen<SmallRng>, SmallRng>::new(SmallRng::seed_from_u64(
-            rand::thread_rng().gen(),
-        ));
+        let seed = rand::thread_rng().gen();
+        let mut config =
+            Config::<WatGen<SmallRng>, SmallRng>::new(SmallRng::seed_from_u64(seed)).set_seed(seed);
         if let Some(t) = get_timeout() {
             config.timeout = t;
         }
@@ -463,10 +739,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn watgen_dead_code_validates() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut saw_unreachable = false;
+        for _ in 0..100 {
+            let wat = WatGenWith::<SmallRng, 2>::generate(&mut rng, 20);
+            saw_unreachable |= wat.contains("unreachable");
+            let wasm = wat::parse_str(&wat).unwrap();
+            wasmparser::validate(&wasm).unwrap();
+            let mut module = walrus::Module::from_buffer(&wasm).unwrap();
+            wasmparser::validate(&module.emit_wasm()).unwrap();
+        }
+        assert!(saw_unreachable);
+
+        for _ in 0..100 {
+            let wat = WatGenWith::<SmallRng, 0>::generate(&mut rng, 20);
+            assert!(!wat.contains("unreachable"));
+        }
+    }
+
+    #[test]
+    fn run_result() {
+        let output = "called host host.print(i32:1) => i32:0\nrun() => i32:42\n";
+        assert_eq!(super::run_result(output), Some("i32:42"));
+        assert_eq!(super::run_result("f() =>\n"), None);
+    }
+
     #[test]
     fn wasm_opt_ttf_fuzz() {
+        let seed = rand::thread_rng().gen();
         let mut config =
-            Config::<WasmOptTtf, SmallRng>::new(SmallRng::seed_from_u64(rand::thread_rng().gen()));
+            Config::<WasmOptTtf, SmallRng>::new(SmallRng::seed_from_u64(seed)).set_seed(seed);
         if let Some(t) = get_timeout() {
             config.timeout = t;
         }
@@ -476,6 +780,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn weighted_picks_by_weight() {
+        struct Left;
+        impl TestCaseGenerator for Left {
+            const NAME: &'static str = "Left";
+            fn generate(_: &mut impl Rng, _: usize) -> String {
+                "left".to_string()
+            }
+        }
+
+        struct Right;
+        impl TestCaseGenerator for Right {
+            const NAME: &'static str = "Right";
+            fn generate(_: &mut impl Rng, _: usize) -> String {
+                "right".to_string()
+            }
+        }
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let cases: Vec<_> = (0..100)
+            .map(|_| Weighted::<Left, Right, 1, 1>::generate(&mut rng, 1))
+            .collect();
+        assert!(cases.iter().any(|c| c == "left"));
+        assert!(cases.iter().any(|c| c == "right"));
+
+        for _ in 0..100 {
+            assert_eq!(
+                Weighted::<Left, Right, 0, 3>::generate(&mut rng, 1),
+                "right"
+            );
+        }
+    }
+
     #[test]
     fn fuzz0() {
         super::assert_round_trip_execution_is_same(
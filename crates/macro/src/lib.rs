@@ -597,6 +597,21 @@ fn create_visit(variants: &[WalrusVariant]) -> impl quote::ToTokens {
                 // ...
             }
 
+            /// Called before visiting an `Instr`, to decide whether the
+            /// traversal should descend into the instruction sequences nested
+            /// inside it.
+            ///
+            /// This is a chance to inspect or replace the instruction before
+            /// any of its children are visited, for example to wrap it in a new
+            /// block. Returning `VisitAction::SkipChildren` still visits the
+            /// instruction itself, but none of its nested sequences.
+            ///
+            /// Only called by `walrus::ir::dfs_pre_order_mut`.
+            #[inline]
+            fn enter_instr_mut(&mut self, instr: &mut Instr, instr_loc: &mut InstrLocId) -> VisitAction {
+                VisitAction::Continue
+            }
+
             /// Visit `Instr`.
             #[inline]
             fn visit_instr_mut(&mut self, instr: &mut Instr, instr_loc: &mut InstrLocId) {
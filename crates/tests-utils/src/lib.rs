@@ -47,6 +47,31 @@ pub fn wasm_interp(path: &Path) -> Result<String> {
     Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
+fn require_wasm_validate() {
+    require_tool("wasm-validate", "https://github.com/WebAssembly/wabt");
+}
+
+/// Run `wasm-validate` on the given wasm file, returning an error with its
+/// output if the file doesn't validate.
+pub fn wasm_validate(path: &Path) -> Result<()> {
+    static CHECK: Once = Once::new();
+    CHECK.call_once(require_wasm_validate);
+
+    let mut cmd = Command::new("wasm-validate");
+    cmd.arg(path);
+    cmd.arg("--enable-all");
+    println!("running: {:?}", cmd);
+    let output = cmd.output().context("could not run wasm-validate")?;
+    if !output.status.success() {
+        bail!(
+            "wasm-validate exited with status {:?}\n\nstderr = '''\n{}\n'''",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
 fn require_wasm_opt() {
     require_tool("wasm-opt", "https://github.com/WebAssembly/binaryen");
 }
@@ -88,6 +113,48 @@ where
     Ok(buf)
 }
 
+/// Check that applying `transform` to the module `wasm` keeps its behavior:
+/// the transformed module must validate, and its output in the reference
+/// interpreter, running all of its exports, must be the same as before.
+pub fn transform_preserves_behavior(
+    wasm: &[u8],
+    transform: impl FnOnce(&mut walrus::Module),
+) -> Result<()> {
+    let tmp = tempfile::NamedTempFile::new()?;
+    fs::write(tmp.path(), wasm)?;
+    let expected = wasm_interp(tmp.path()).context("failed to run the original module")?;
+
+    let mut module =
+        walrus::Module::from_buffer(wasm).context("walrus failed to parse the wasm buffer")?;
+    transform(&mut module);
+    fs::write(tmp.path(), module.emit_wasm())?;
+    wasm_validate(tmp.path()).context("the transformed module is invalid")?;
+    let actual = wasm_interp(tmp.path()).context("failed to run the transformed module")?;
+
+    if expected != actual {
+        bail!(
+            "the transformed module behaves differently\n\n\
+             expected = '''\n{}\n'''\n\n\
+             actual = '''\n{}\n'''",
+            expected,
+            actual
+        );
+    }
+    Ok(())
+}
+
+/// Assert that applying `transform` to the module `wasm` keeps its behavior.
+///
+/// See `transform_preserves_behavior` for details.
+pub fn assert_transform_preserves_behavior(
+    wasm: &[u8],
+    transform: impl FnOnce(&mut walrus::Module),
+) {
+    if let Err(e) = transform_preserves_behavior(wasm, transform) {
+        panic!("transform changed the module's behavior: {:?}", e);
+    }
+}
+
 pub fn handle<T: TestResult>(result: T) {
     result.handle();
 }
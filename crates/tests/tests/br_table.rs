@@ -0,0 +1,38 @@
+use walrus::ir::BrTable;
+use walrus::{FunctionBuilder, Module};
+
+#[test]
+fn targets_include_default() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let a = builder.dangling_instr_seq(None).id();
+    let b = builder.dangling_instr_seq(None).id();
+    let c = builder.dangling_instr_seq(None).id();
+
+    let mut br_table = BrTable {
+        blocks: vec![a, b, a].into_boxed_slice(),
+        default: c,
+    };
+    assert_eq!(br_table.targets().collect::<Vec<_>>(), [a, b, a, c]);
+
+    let empty = BrTable {
+        blocks: Box::new([]),
+        default: b,
+    };
+    assert_eq!(empty.targets().collect::<Vec<_>>(), [b]);
+
+    let mut seen = Vec::new();
+    br_table.remap_targets(|id| {
+        seen.push(id);
+        if id == a {
+            c
+        } else if id == c {
+            b
+        } else {
+            id
+        }
+    });
+    assert_eq!(seen, [a, b, a, c]);
+    assert_eq!(&br_table.blocks[..], [c, b, c]);
+    assert_eq!(br_table.default, b);
+}
@@ -0,0 +1,91 @@
+use walrus::passes::{CallGraph, IndirectCalls};
+use walrus::{FunctionId, Module};
+
+fn func(module: &Module, name: &str) -> FunctionId {
+    module.funcs.by_name(name).unwrap()
+}
+
+fn sorted_sccs(module: &Module, graph: &CallGraph) -> Vec<Vec<String>> {
+    let mut sccs: Vec<Vec<String>> = graph
+        .sccs()
+        .into_iter()
+        .map(|scc| {
+            let mut names: Vec<_> = scc
+                .into_iter()
+                .map(|f| module.funcs.get(f).name.clone().unwrap())
+                .collect();
+            names.sort();
+            names
+        })
+        .collect();
+    sccs.sort();
+    sccs
+}
+
+#[test]
+fn recursion_groups() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (import "env" "imported" (func $imported))
+                (func $even (param i32) (result i32)
+                    (call $odd (local.get 0)))
+                (func $odd (param i32) (result i32)
+                    (call $even (local.get 0)))
+                (func $self (param i32) (result i32)
+                    (call $self (local.get 0)))
+                (func $main
+                    (drop (call $even (i32.const 1)))
+                    (drop (call $self (i32.const 2)))
+                    call $imported
+                    (call_indirect (type 0) (i32.const 0)))
+                (func $indirect
+                    call $main)
+                (table 1 funcref)
+                (elem (i32.const 0) $indirect))
+        "#,
+    )?;
+    let module = Module::from_buffer(&wasm)?;
+
+    let graph = CallGraph::new(&module, IndirectCalls::Ignore);
+    assert_eq!(
+        sorted_sccs(&module, &graph),
+        [
+            vec!["even", "odd"],
+            vec!["imported"],
+            vec!["indirect"],
+            vec!["main"],
+            vec!["self"],
+        ]
+    );
+    let main = func(&module, "main");
+    assert_eq!(
+        graph.callees(main),
+        [
+            func(&module, "even"),
+            func(&module, "self"),
+            func(&module, "imported"),
+        ]
+    );
+    let self_ = func(&module, "self");
+    assert_eq!(graph.callees(self_), [self_]);
+
+    // Components come in reverse topological order.
+    let sccs = graph.sccs();
+    let position = |f| sccs.iter().position(|scc| scc.contains(&f)).unwrap();
+    assert!(position(func(&module, "even")) < position(main));
+    assert!(position(self_) < position(main));
+
+    // `main` may call `indirect` through the table, and vice versa.
+    let graph = CallGraph::new(&module, IndirectCalls::TypeCompatible);
+    assert_eq!(
+        sorted_sccs(&module, &graph),
+        [
+            vec!["even", "odd"],
+            vec!["imported"],
+            vec!["indirect", "main"],
+            vec!["self"],
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use std::borrow::Cow;
+use walrus::{CustomSection, ExportItem, FunctionBuilder, IdsToIndices, Module};
+use walrus::{RawCustomSection, ValType};
+
+#[derive(Debug)]
+struct UncloneableSection;
+
+impl CustomSection for UncloneableSection {
+    fn name(&self) -> &str {
+        "uncloneable"
+    }
+
+    fn data(&self, _: &IdsToIndices) -> Cow<[u8]> {
+        Cow::Borrowed(b"data")
+    }
+}
+
+fn module() -> Module {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (global $g (mut i32) (i32.const 0))
+              (memory 1)
+              (data (i32.const 0) "hello")
+              (func $f (export "f") (result i32)
+                (global.set $g (i32.const 1))
+                (global.get $g)))
+        "#,
+    )
+    .unwrap();
+    Module::from_buffer(&wasm).unwrap()
+}
+
+#[test]
+fn clone_is_independent() {
+    let mut original = module();
+    let f = match original.exports.iter().next().unwrap().item {
+        ExportItem::Function(f) => f,
+        _ => unreachable!(),
+    };
+    let before = original.emit_wasm();
+
+    let mut clone = original.clone();
+
+    // Ids from the original are valid in the clone.
+    assert_eq!(clone.funcs.get(f).ty(), original.funcs.get(f).ty(),);
+
+    // Mutate the clone in a few ways.
+    let body = clone.funcs.get_mut(f).kind.unwrap_local_mut();
+    let entry = body.entry_block();
+    body.block_mut(entry).instrs.clear();
+    body.builder_mut().func_body().i32_const(42);
+    let data = clone.data.iter().next().unwrap().id();
+    clone.data.get_mut(data).value = b"bye".to_vec();
+    let mut builder = FunctionBuilder::new(&mut clone.types, &[], &[ValType::I32]);
+    builder.func_body().i32_const(7);
+    let g = builder.finish(vec![], &mut clone.funcs);
+    clone.exports.add("g", g);
+
+    // The original is unaffected.
+    assert_eq!(original.emit_wasm(), before);
+    assert_eq!(original.funcs.iter().count(), 1);
+    assert_eq!(original.exports.iter().count(), 1);
+    assert_eq!(original.data.get(data).value, b"hello");
+
+    // Both copies still emit valid wasm.
+    let clone_wasm = clone.emit_wasm();
+    assert_ne!(clone_wasm, before);
+    let reparsed = Module::from_buffer(&clone_wasm).unwrap();
+    assert_eq!(reparsed.funcs.iter().count(), 2);
+    Module::from_buffer(&before).unwrap();
+}
+
+#[test]
+fn clone_custom_sections() {
+    let mut original = module();
+    let raw = original.customs.add(RawCustomSection {
+        name: "raw".to_string(),
+        data: vec![1, 2, 3],
+    });
+    let uncloneable = original.customs.add(UncloneableSection);
+
+    let mut clone = original.clone();
+    assert_eq!(clone.customs.get(raw).unwrap().data, [1, 2, 3]);
+    assert!(clone.customs.get(uncloneable).is_none());
+    assert!(original.customs.get(uncloneable).is_some());
+
+    clone.customs.get_mut(raw).unwrap().data.push(4);
+    assert_eq!(original.customs.get(raw).unwrap().data, [1, 2, 3]);
+}
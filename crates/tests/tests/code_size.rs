@@ -0,0 +1,72 @@
+use walrus::Module;
+
+/// The size of the contents of the code section in `wasm`.
+fn code_section_size(wasm: &[u8]) -> usize {
+    fn leb128(wasm: &[u8], pos: &mut usize) -> usize {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = wasm[*pos];
+            *pos += 1;
+            result |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return result;
+            }
+            shift += 7;
+        }
+    }
+
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let id = wasm[pos];
+        pos += 1;
+        let size = leb128(wasm, &mut pos);
+        if id == 10 {
+            return size;
+        }
+        pos += size;
+    }
+    panic!("no code section")
+}
+
+#[test]
+fn code_size_report_is_exact() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (import "env" "f" (func $imported (param i32)))
+                (global $g (mut i32) (i32.const 0))
+                (func $small
+                    i32.const 0
+                    call $imported)
+                (func $large (param i32) (result i32)
+                    (local i64 f32)
+                    local.get 0
+                    i32.const 1000000
+                    i32.add
+                    global.get $g
+                    i32.mul
+                    local.tee 0
+                    global.set $g
+                    local.get 0)
+                (func $empty))
+        "#,
+    )?;
+    let mut module = Module::from_buffer(&wasm)?;
+    let report = module.code_size_report();
+
+    let small = module.funcs.by_name("small").unwrap();
+    let large = module.funcs.by_name("large").unwrap();
+    let empty = module.funcs.by_name("empty").unwrap();
+    assert_eq!(report.funcs.len(), 3);
+    assert_eq!(
+        report.largest(2),
+        [(large, report.funcs[&large]), (small, report.funcs[&small])]
+    );
+    // An empty function is just its (empty) locals and an `end`.
+    assert_eq!(report.funcs[&empty], 2);
+
+    let wasm = module.emit_wasm();
+    assert_eq!(report.total, code_section_size(&wasm));
+    Ok(())
+}
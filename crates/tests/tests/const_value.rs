@@ -0,0 +1,32 @@
+use walrus::ir::{ConstValue, Instr};
+use walrus::ValType;
+
+#[test]
+fn const_round_trips_through_instr() {
+    let values = [
+        ConstValue::I32(-1),
+        ConstValue::I64(1 << 40),
+        ConstValue::F32(1.5f32.to_bits()),
+        ConstValue::F64(2.5f64.to_bits()),
+        ConstValue::V128(0x0102_0304),
+    ];
+    for value in values.iter() {
+        let instr = Instr::const_(*value);
+        assert!(instr.is_const());
+        assert_eq!(instr.as_const(), Some(*value));
+    }
+    assert_eq!(ConstValue::F64(0).ty(), ValType::F64);
+}
+
+#[test]
+fn const_keeps_nan_payloads() {
+    let nan = 0x7fa0_0001;
+    let instr = Instr::const_(ConstValue::F32(nan));
+    assert_eq!(instr.as_const(), Some(ConstValue::F32(nan)));
+}
+
+#[test]
+fn non_const_instrs_have_no_const_value() {
+    let instr = Instr::Drop(walrus::ir::Drop {});
+    assert_eq!(instr.as_const(), None);
+}
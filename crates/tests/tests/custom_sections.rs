@@ -1,7 +1,10 @@
 //! Tests for working with custom sections that `walrus` doesn't know about.
 
 use std::borrow::Cow;
-use walrus::{CodeTransform, CustomSection, IdsToIndices, Module, ModuleConfig, ValType};
+use walrus::{BranchHint, BranchHintSection, CodeTransform, CustomSection, IdsToIndices};
+use walrus::{
+    LinkingSection, Module, ModuleConfig, RawCustomSection, SymbolInfo, SymbolKind, ValType,
+};
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 struct HelloCustomSection(String);
@@ -126,3 +129,322 @@ fn smoke_test_code_transform() {
 
     assert_eq!(APPLIED_CODE_TRANSFORM.load(Ordering::SeqCst), 1);
 }
+
+// Parse a `metadata.code.branch_hint` section, insert a `(drop (i32.const 0))`
+// at the start of the function, and assert that the hinted `br_if` is pushed
+// down by 3 in the emitted section.
+#[test]
+fn branch_hints_follow_code_transform() {
+    let mut wasm = wat::parse_str(
+        r#"
+            (module
+              (func (export "f") (param i32)
+                (block
+                  (br_if 0 (local.get 0)))))
+        "#,
+    )
+    .unwrap();
+
+    // Hint that the `br_if`, at offset 5 in the function's body, is likely.
+    let name = BranchHintSection::NAME;
+    let data = [1, 0, 1, 5, 1, 1];
+    wasm.push(0);
+    wasm.push((1 + name.len() + data.len()) as u8);
+    wasm.push(name.len() as u8);
+    wasm.extend_from_slice(name.as_bytes());
+    wasm.extend_from_slice(&data);
+
+    let mut config = ModuleConfig::new();
+    config.generate_producers_section(false);
+    config.preserve_code_transform(true);
+    config.on_parse(|module, indices| {
+        let raw = module.customs.remove_raw(BranchHintSection::NAME).unwrap();
+        module
+            .customs
+            .add(BranchHintSection::parse(&raw.data, indices)?);
+        Ok(())
+    });
+    let mut module = config.parse(&wasm).unwrap();
+
+    let hints = module.customs.get_typed::<BranchHintSection>().unwrap();
+    assert_eq!(
+        hints.iter().map(|h| h.value).collect::<Vec<_>>(),
+        [BranchHint::Likely]
+    );
+
+    for (_id, f) in module.funcs.iter_local_mut() {
+        let builder = f.builder_mut();
+        builder.func_body().const_at(0, walrus::ir::Value::I32(0));
+        builder.func_body().drop_at(1);
+    }
+    let wasm = module.emit_wasm();
+
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    let raw = module.customs.remove_raw(BranchHintSection::NAME).unwrap();
+    assert_eq!(raw.data, [1, 0, 1, 8, 1, 1]);
+}
+
+#[test]
+fn strip_debug_removes_debug_sections_and_names() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(r#"(module (func $foo (export "foo")))"#)?;
+    let mut config = ModuleConfig::new();
+    config.generate_dwarf(true);
+    let mut module = config.parse(&wasm)?;
+    module.customs.add(RawCustomSection {
+        name: ".debug_info".to_string(),
+        data: vec![1, 2, 3],
+    });
+    module.customs.add(HelloCustomSection("World".into()));
+
+    walrus::passes::strip_debug(&mut module, false);
+
+    let wasm = module.emit_wasm();
+    let mut module = config.parse(&wasm)?;
+    assert!(module.customs.remove_raw(".debug_info").is_none());
+    assert!(module.customs.remove_raw("hello").is_some());
+    assert!(module.funcs.iter().all(|f| f.name.is_none()));
+    Ok(())
+}
+
+#[test]
+fn linking_section_symbols() -> anyhow::Result<()> {
+    let mut data = vec![2];
+    // Symbol table: a defined function `foo` and an undefined data `bar`.
+    data.extend_from_slice(&[8, 14, 2]);
+    data.extend_from_slice(&[0, 0, 0, 3, b'f', b'o', b'o']);
+    data.extend_from_slice(&[1, 0x10, 3, b'b', b'a', b'r']);
+    // Segment info for `.data`.
+    data.extend_from_slice(&[5, 9, 1, 5, b'.', b'd', b'a', b't', b'a', 2, 0]);
+    // A COMDAT info subsection, which isn't understood and is skipped.
+    data.extend_from_slice(&[7, 1, 0]);
+
+    let linking = LinkingSection::parse(&data)?;
+    assert_eq!(linking.version(), 2);
+    assert_eq!(
+        linking.symbols(),
+        &[
+            SymbolInfo {
+                kind: SymbolKind::Function {
+                    index: 0,
+                    name: Some("foo".to_string()),
+                },
+                flags: 0,
+            },
+            SymbolInfo {
+                kind: SymbolKind::Data {
+                    name: "bar".to_string(),
+                    definition: None,
+                },
+                flags: SymbolInfo::UNDEFINED,
+            },
+        ]
+    );
+    assert_eq!(linking.segments().len(), 1);
+    assert_eq!(linking.segments()[0].name, ".data");
+    assert_eq!(linking.segments()[0].alignment, 2);
+    assert!(linking.init_funcs().is_empty());
+
+    let mut module = Module::default();
+    module.customs.add(linking);
+    let wasm = module.emit_wasm();
+    let mut module = Module::from_buffer(&wasm)?;
+    let raw = module.customs.remove_raw(LinkingSection::NAME).unwrap();
+    assert_eq!(raw.data, data);
+    Ok(())
+}
+
+#[test]
+fn on_raw_section_sees_unknown_sections() -> anyhow::Result<()> {
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x42, 0x03, 0x01, 0x02, 0x03, // unknown section 0x42
+    ];
+    assert!(Module::from_buffer(&wasm).is_err());
+
+    let mut config = ModuleConfig::new();
+    config.on_raw_section(|module, id, contents| {
+        assert_eq!(id, 0x42);
+        module.customs.add(RawCustomSection {
+            name: "stashed".to_string(),
+            data: contents.to_vec(),
+        });
+        Ok(())
+    });
+    let mut module = config.parse(&wasm)?;
+    let stashed = module.customs.remove_raw("stashed").unwrap();
+    assert_eq!(stashed.data, [1, 2, 3]);
+    Ok(())
+}
+
+#[test]
+fn stamp_provenance_without_producers_section() -> anyhow::Result<()> {
+    fn count(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack
+            .windows(needle.len())
+            .filter(|w| w == &needle)
+            .count()
+    }
+
+    let mut config = ModuleConfig::new();
+    config.generate_producers_section(false);
+    let wasm = Module::with_config(config.clone()).emit_wasm();
+    assert_eq!(count(&wasm, b"processed-by"), 0);
+
+    config.stamp_provenance(true);
+    let wasm = Module::with_config(config.clone()).emit_wasm();
+    assert_eq!(count(&wasm, b"processed-by"), 1);
+    assert_eq!(count(&wasm, b"walrus"), 1);
+
+    // Processing the module again doesn't add another stamp.
+    let wasm = config.parse(&wasm)?.emit_wasm();
+    assert_eq!(count(&wasm, b"walrus"), 1);
+    Ok(())
+}
+
+#[test]
+fn align_output_pads_module() -> anyhow::Result<()> {
+    for &align in &[1, 2, 7, 64, 256, 4096] {
+        let mut config = ModuleConfig::new();
+        config.align_output(align);
+        let mut module = Module::with_config(config.clone());
+        module.customs.add(RawCustomSection {
+            name: "hello".to_string(),
+            data: vec![1, 2, 3],
+        });
+        let wasm = module.emit_wasm();
+        assert_eq!(wasm.len() % align, 0, "align = {}", align);
+
+        // The padding is a regular custom section, so the module still parses.
+        let mut module = config.parse(&wasm)?;
+        assert!(module.customs.remove_raw("hello").is_some());
+    }
+    Ok(())
+}
+
+#[test]
+fn custom_sections_keep_file_order() -> anyhow::Result<()> {
+    let mut module = Module::default();
+    for (name, data) in &[("a", 1), ("b", 2), ("a", 3)] {
+        module.customs.add(RawCustomSection {
+            name: name.to_string(),
+            data: vec![*data],
+        });
+    }
+    let wasm = module.emit_wasm();
+
+    let module = Module::from_buffer(&wasm)?;
+    let sections: Vec<_> = module
+        .customs
+        .iter()
+        .map(|(_, s)| {
+            let raw = s.as_any().downcast_ref::<RawCustomSection>().unwrap();
+            (raw.name.as_str(), raw.data[0])
+        })
+        .collect();
+    assert_eq!(sections, [("a", 1), ("b", 2), ("a", 3)]);
+    Ok(())
+}
+
+/// The names of all the custom sections in `wasm`, in order.
+fn custom_section_names(wasm: &[u8]) -> Vec<String> {
+    fn leb128(wasm: &[u8], pos: &mut usize) -> usize {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = wasm[*pos];
+            *pos += 1;
+            result |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return result;
+            }
+            shift += 7;
+        }
+    }
+
+    let mut names = Vec::new();
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let id = wasm[pos];
+        pos += 1;
+        let size = leb128(wasm, &mut pos);
+        let end = pos + size;
+        if id == 0 {
+            let len = leb128(wasm, &mut pos);
+            names.push(String::from_utf8(wasm[pos..pos + len].to_vec()).unwrap());
+        }
+        pos = end;
+    }
+    names
+}
+
+#[test]
+fn only_one_name_and_producers_section() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module $named
+                (func $f))
+        "#,
+    )?;
+    let mut module = Module::from_buffer(&wasm)?;
+    for name in &["name", "producers", "name", "producers"] {
+        module.customs.add(RawCustomSection {
+            name: name.to_string(),
+            data: vec![],
+        });
+    }
+    module.producers.add_language("wat", "1");
+
+    let wasm = module.emit_wasm();
+    let names = custom_section_names(&wasm);
+    assert_eq!(names, ["name", "producers"]);
+
+    // The generated name section is the one that was kept.
+    let module = Module::from_buffer(&wasm)?;
+    assert_eq!(module.name.as_deref(), Some("named"));
+    Ok(())
+}
+
+#[test]
+fn only_first_raw_name_section_without_generation() {
+    let mut config = ModuleConfig::new();
+    config
+        .generate_name_section(false)
+        .generate_producers_section(false);
+    let mut module = Module::with_config(config);
+    for data in 0..2 {
+        module.customs.add(RawCustomSection {
+            name: "name".to_string(),
+            data: vec![data],
+        });
+    }
+
+    let wasm = module.emit_wasm();
+    assert_eq!(custom_section_names(&wasm), ["name"]);
+}
+
+#[test]
+fn add_custom_section() {
+    let wasm = wat::parse_str("(module (func (export \"f\")))").unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    let manifest = module.add_custom_section("manifest", b"{}".to_vec());
+    let second = module.add_custom_section("manifest", b"[]".to_vec());
+    module.add_custom_section("other", vec![1, 2, 3]);
+
+    module.customs.get_mut(manifest).unwrap().data = b"{\"a\":1}".to_vec();
+    assert_eq!(module.customs.get(second).unwrap().data, b"[]");
+
+    let wasm = module.emit_wasm();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    let customs: Vec<_> = module
+        .customs
+        .iter()
+        .map(|(_, s)| s.name().to_string())
+        .filter(|name| name != "producers")
+        .collect();
+    assert_eq!(customs, ["manifest", "manifest", "other"]);
+    assert_eq!(
+        module.customs.remove_raw("manifest").unwrap().data,
+        b"{\"a\":1}"
+    );
+    assert_eq!(module.customs.remove_raw("manifest").unwrap().data, b"[]");
+}
@@ -0,0 +1,65 @@
+use walrus::ir::InstrLocId;
+use walrus::{InstrPosition, Module};
+
+#[test]
+fn describe_loc() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (func $f (export "f") (result i32)
+                (block (result i32)
+                  i32.const 1
+                  i32.const 2
+                  i32.add)))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+
+    let (func, local) = module.funcs.iter_local().next().unwrap();
+    let entry = local.block(local.entry_block());
+    let block = match &entry[0].0 {
+        walrus::ir::Instr::Block(b) => b.seq,
+        other => panic!("unexpected instruction: {:?}", other),
+    };
+    let (_, add_loc) = local.block(block)[2];
+
+    assert_eq!(
+        module.find_instr(add_loc),
+        Some(InstrPosition {
+            func,
+            seq: block,
+            index: 2,
+        })
+    );
+    let description = module.describe_loc(add_loc);
+    assert!(
+        description.starts_with(&format!(
+            "func `f`, seq {}, instr 2 (offset 0x",
+            block.index()
+        )),
+        "{}",
+        description
+    );
+
+    // Anonymous functions are described by their index.
+    module.funcs.get_mut(func).name = None;
+    assert!(module
+        .describe_loc(add_loc)
+        .starts_with(&format!("func {}, ", func.index())));
+
+    // Instructions that weren't parsed have no location.
+    assert_eq!(module.find_instr(InstrLocId::default()), None);
+    assert_eq!(
+        module.describe_loc(InstrLocId::default()),
+        "synthesized instruction (no original offset)"
+    );
+
+    // Neither do ones that have been removed.
+    let gone = InstrLocId::new(0xdead);
+    assert_eq!(module.find_instr(gone), None);
+    assert_eq!(
+        module.describe_loc(gone),
+        "offset 0xdead (no longer in the module)"
+    );
+}
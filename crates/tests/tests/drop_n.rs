@@ -0,0 +1,41 @@
+use walrus::ir::Instr;
+use walrus::{FunctionBuilder, Module, ValType};
+
+#[test]
+fn drop_n_discards_multi_value_results() {
+    let mut module = Module::default();
+
+    let mut builder = FunctionBuilder::new(
+        &mut module.types,
+        &[],
+        &[ValType::I32, ValType::I64, ValType::F32],
+    );
+    builder.func_body().i32_const(1).i64_const(2).f32_const(3.0);
+    let three = builder.finish(vec![], &mut module.funcs);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().call(three).drop_n(3);
+    let caller = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("caller", caller);
+
+    let body = module.funcs.get(caller).kind.unwrap_local();
+    let instrs = body.block(body.entry_block());
+    assert_eq!(instrs.len(), 4);
+    assert!(instrs[1..]
+        .iter()
+        .all(|(instr, _)| matches!(instr, Instr::Drop(_))));
+
+    module.validate().unwrap();
+    Module::from_buffer(&module.emit_wasm()).unwrap();
+}
+
+#[test]
+fn drop_zero_is_a_no_op() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().drop_n(0);
+    let f = builder.finish(vec![], &mut module.funcs);
+
+    let body = module.funcs.get(f).kind.unwrap_local();
+    assert!(body.block(body.entry_block()).is_empty());
+}
@@ -0,0 +1,40 @@
+use walrus::{Module, TypeUse, ValType};
+
+#[test]
+fn duplicate_types_are_merged() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (type $a (func (param i32)))
+              (type $b (func (param i32)))
+              (type $c (func (param i32) (result i32)))
+              (type $d (func (param i32) (result i32)))
+              (import "env" "f" (func $f (type $a)))
+              (table 1 funcref)
+              (func $g (type $b)
+                local.get 0
+                block (type $c)
+                end
+                block (type $d)
+                end
+                i32.const 0
+                call_indirect (type $b)))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+
+    let unary = module.types.find(&[ValType::I32], &[]).unwrap();
+    let identity = module.types.find(&[ValType::I32], &[ValType::I32]).unwrap();
+    let uses = module.used_types();
+    assert_eq!(uses.len(), 2);
+    assert_eq!(uses[&unary].len(), 3);
+    assert!(uses[&unary].contains(&TypeUse::FunctionSignature(
+        module.funcs.by_name("f").unwrap()
+    )));
+    assert_eq!(uses[&identity].len(), 2);
+
+    // Only the two distinct types are emitted.
+    let wat = wasmprinter::print_bytes(&module.emit_wasm()).unwrap();
+    assert_eq!(wat.matches("(type (;").count(), 2, "{}", wat);
+}
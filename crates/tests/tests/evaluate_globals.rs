@@ -0,0 +1,44 @@
+use walrus::ir::{ConstValue, Value};
+use walrus::{InitExpr, Module, ValType};
+
+#[test]
+fn evaluate_globals() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (import "env" "base" (global $base i32))
+              (global $from_import i32 (global.get $base))
+              (global $heap_base (mut i32) (i32.const 1024))
+              (global $pi f64 (f64.const 3.5))
+              (global $null funcref (ref.null func)))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+
+    let find = |module: &Module, value: Value| {
+        module
+            .globals
+            .iter_local()
+            .find(|g| g.const_value() == Some(value.into()))
+            .unwrap()
+            .id()
+    };
+    let heap_base = find(&module, Value::I32(1024));
+    let pi = find(&module, Value::F64(3.5));
+
+    // Chains through other local globals are followed.
+    let alias = module
+        .globals
+        .add_local(ValType::I32, false, InitExpr::Global(heap_base));
+    let alias2 = module
+        .globals
+        .add_local(ValType::I32, false, InitExpr::Global(alias));
+
+    let values = module.evaluate_globals();
+    assert_eq!(values.len(), 4);
+    assert_eq!(values[&heap_base], ConstValue::I32(1024));
+    assert_eq!(values[&pi], ConstValue::F64(3.5f64.to_bits()));
+    assert_eq!(values[&alias], ConstValue::I32(1024));
+    assert_eq!(values[&alias2], ConstValue::I32(1024));
+}
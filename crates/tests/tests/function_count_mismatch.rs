@@ -0,0 +1,44 @@
+use walrus::{ErrorKind, Module};
+
+const HEADER: &[u8] = b"\0asm\x01\0\0\0";
+// One type: `() -> ()`.
+const TYPE_SECTION: &[u8] = &[0x01, 0x04, 0x01, 0x60, 0x00, 0x00];
+// Two functions, both of type 0.
+const FUNCTION_SECTION: &[u8] = &[0x03, 0x03, 0x02, 0x00, 0x00];
+// One empty function body.
+const CODE_SECTION: &[u8] = &[0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b];
+
+fn mismatch(sections: &[&[u8]]) -> Option<(u32, u32)> {
+    let mut wasm = HEADER.to_vec();
+    for section in sections {
+        wasm.extend_from_slice(section);
+    }
+    let err = Module::from_buffer(&wasm).unwrap_err();
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::FunctionCountMismatch { declared, code }) => Some((*declared, *code)),
+        _ => None,
+    }
+}
+
+#[test]
+fn fewer_bodies_than_functions() {
+    assert_eq!(
+        mismatch(&[TYPE_SECTION, FUNCTION_SECTION, CODE_SECTION]),
+        Some((2, 1))
+    );
+}
+
+#[test]
+fn more_bodies_than_functions() {
+    let function_section: &[u8] = &[0x03, 0x01, 0x00];
+    let code_section: &[u8] = &[0x0a, 0x07, 0x02, 0x02, 0x00, 0x0b, 0x02, 0x00, 0x0b];
+    assert_eq!(
+        mismatch(&[TYPE_SECTION, function_section, code_section]),
+        Some((0, 2))
+    );
+}
+
+#[test]
+fn missing_code_section() {
+    assert_eq!(mismatch(&[TYPE_SECTION, FUNCTION_SECTION]), Some((2, 0)));
+}
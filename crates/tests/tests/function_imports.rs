@@ -15,6 +15,10 @@ fn run(wat_path: &Path) -> Result<(), anyhow::Error> {
     assert!(module.imports.find("snek", "cobra").is_none());
     assert!(module.imports.find("cat", "siamese").is_some());
 
+    // Only imported functions here, so there are no local functions.
+    assert_eq!(module.funcs.iter().count(), 3);
+    assert_eq!(module.funcs.iter_local().count(), 0);
+
     Ok(())
 }
 
@@ -0,0 +1,40 @@
+use walrus::ir::{Instr, LocalGet, LocalSet, LocalTee};
+use walrus::{LocalId, Module};
+
+#[test]
+fn declared_locals() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (func (param $a i32) (param $b f64) (result i32)
+                (local $x i64) (local $unused f32) (local $y i32)
+                local.get $b
+                drop
+                i64.const 1
+                local.set $x
+                local.get $a
+                local.tee $y))
+        "#,
+    )
+    .unwrap();
+    let module = Module::from_buffer(&wasm).unwrap();
+    let (_, func) = module.funcs.iter_local().next().unwrap();
+
+    let locals: Vec<LocalId> = func
+        .block(func.entry_block())
+        .iter()
+        .filter_map(|(instr, _)| match instr {
+            Instr::LocalGet(LocalGet { local })
+            | Instr::LocalSet(LocalSet { local })
+            | Instr::LocalTee(LocalTee { local }) => Some(*local),
+            _ => None,
+        })
+        .collect();
+    let (b, x, a, y) = (locals[0], locals[1], locals[2], locals[3]);
+    assert_eq!(func.args(), [a, b]);
+
+    // `$unused` isn't referenced by the body, so it isn't declared.
+    let mut expected = vec![x, y];
+    expected.sort();
+    assert_eq!(func.declared_locals(), expected);
+}
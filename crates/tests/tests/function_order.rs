@@ -0,0 +1,74 @@
+use walrus::{FunctionBuilder, Module, ModuleConfig};
+
+const WAT: &str = r#"
+    (module
+      (import "env" "f" (func $imported))
+      (func $small)
+      (func $large (result i32)
+        i32.const 1
+        i32.const 2
+        i32.add
+        i32.const 3
+        i32.add
+        i32.const 4
+        i32.add)
+      (func $medium (result i32)
+        i32.const 1)
+      (export "small" (func $small))
+      (export "large" (func $large))
+      (export "medium" (func $medium)))
+"#;
+
+fn function_names(module: &Module) -> Vec<String> {
+    module
+        .funcs
+        .iter()
+        .map(|f| f.name.clone().unwrap_or_default())
+        .collect()
+}
+
+fn parse(wasm: &[u8], preserve: bool) -> Module {
+    let mut config = ModuleConfig::new();
+    config.preserve_function_order(preserve);
+    config.parse(wasm).unwrap()
+}
+
+#[test]
+fn round_trip_keeps_function_indices() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let mut module = parse(&wasm, true);
+    let before = function_names(&module);
+    assert_eq!(before, ["imported", "small", "large", "medium"]);
+
+    let after = parse(&module.emit_wasm(), true);
+    assert_eq!(function_names(&after), before);
+}
+
+#[test]
+fn added_functions_are_appended() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let mut module = parse(&wasm, true);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.name("added".to_string());
+    builder.func_body().i32_const(1).drop();
+    builder.finish(vec![], &mut module.funcs);
+
+    let after = parse(&module.emit_wasm(), true);
+    assert_eq!(
+        function_names(&after),
+        ["imported", "small", "large", "medium", "added"]
+    );
+}
+
+#[test]
+fn functions_are_sorted_by_size_by_default() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let mut module = parse(&wasm, false);
+
+    let after = parse(&module.emit_wasm(), false);
+    assert_eq!(
+        function_names(&after),
+        ["imported", "large", "medium", "small"]
+    );
+}
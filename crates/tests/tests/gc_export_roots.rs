@@ -0,0 +1,41 @@
+use walrus::ModuleConfig;
+
+const WAT: &str = r#"
+    (module
+      (global $g (mut i32) (i32.const 0))
+      (func $shared (result i32) (i32.const 1))
+      (func $internal (result i32) (call $shared) (global.get $g) (i32.add))
+      (func $main (export "main") (result i32) (call $shared))
+      (export "__internal_debug" (func $internal))
+      (export "__internal_global" (global $g)))
+"#;
+
+#[test]
+fn all_exports_are_roots_by_default() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let mut module = ModuleConfig::new().parse(&wasm).unwrap();
+    walrus::passes::gc::run(&mut module);
+
+    assert_eq!(module.exports.iter().count(), 3);
+    assert_eq!(module.funcs.iter().count(), 3);
+    assert_eq!(module.globals.iter().count(), 1);
+}
+
+#[test]
+fn unrooted_exports_are_pruned() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let mut module = ModuleConfig::new()
+        .treat_export_as_root(|export| !export.name.starts_with("__internal_"))
+        .parse(&wasm)
+        .unwrap();
+    walrus::passes::gc::run(&mut module);
+
+    let exports: Vec<_> = module.exports.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(exports, ["main"]);
+    // `$shared` is kept alive by `$main`; `$internal` and `$g` are gone.
+    assert_eq!(module.funcs.iter().count(), 2);
+    assert_eq!(module.globals.iter().count(), 0);
+
+    let wasm = module.emit_wasm();
+    walrus::Module::from_buffer(&wasm).unwrap();
+}
@@ -0,0 +1,101 @@
+use walrus::{ExportItem, ImportKind, Module, ModuleConfig};
+
+fn gc(wat: &str) -> Module {
+    let wasm = wat::parse_str(wat).unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    walrus::passes::gc::run(&mut module);
+
+    // The result must still be a valid module.
+    Module::from_buffer(&module.emit_wasm()).unwrap();
+    module
+}
+
+fn assert_reexported(module: &Module, name: &str) {
+    assert_eq!(module.imports.iter().count(), 1);
+    let import = module.imports.iter().next().unwrap();
+    assert_eq!(
+        (import.module.as_str(), import.name.as_str()),
+        ("env", name)
+    );
+
+    assert_eq!(module.exports.iter().count(), 1);
+    let export = module.exports.iter().next().unwrap();
+    assert_eq!(export.name, name);
+
+    let same = match (&import.kind, export.item) {
+        (ImportKind::Function(a), ExportItem::Function(b)) => *a == b,
+        (ImportKind::Table(a), ExportItem::Table(b)) => *a == b,
+        (ImportKind::Memory(a), ExportItem::Memory(b)) => *a == b,
+        (ImportKind::Global(a), ExportItem::Global(b)) => *a == b,
+        _ => false,
+    };
+    assert!(same, "{:?} doesn't re-export {:?}", export, import);
+}
+
+#[test]
+fn reexported_function() {
+    let module = gc(r#"
+        (module
+          (import "env" "f" (func $f (param i32) (result i32)))
+          (export "f" (func $f)))
+    "#);
+    assert_reexported(&module, "f");
+    assert_eq!(module.funcs.iter().count(), 1);
+    assert_eq!(module.types.iter().count(), 1);
+}
+
+#[test]
+fn reexported_global() {
+    let module = gc(r#"
+        (module
+          (import "env" "g" (global $g (mut i32)))
+          (export "g" (global $g)))
+    "#);
+    assert_reexported(&module, "g");
+    assert_eq!(module.globals.iter().count(), 1);
+}
+
+#[test]
+fn reexported_table() {
+    let module = gc(r#"
+        (module
+          (import "env" "t" (table $t 1 funcref))
+          (export "t" (table $t)))
+    "#);
+    assert_reexported(&module, "t");
+    assert_eq!(module.tables.iter().count(), 1);
+}
+
+#[test]
+fn reexported_memory() {
+    let module = gc(r#"
+        (module
+          (import "env" "m" (memory $m 1))
+          (export "m" (memory $m)))
+    "#);
+    assert_reexported(&module, "m");
+    assert_eq!(module.memories.iter().count(), 1);
+}
+
+#[test]
+fn unrooted_reexport_drops_the_import() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (import "env" "f" (func $f))
+              (import "env" "g" (global $g i32))
+              (export "f" (func $f))
+              (export "__internal_g" (global $g)))
+        "#,
+    )
+    .unwrap();
+    let mut module = ModuleConfig::new()
+        .treat_export_as_root(|export| !export.name.starts_with("__internal_"))
+        .parse(&wasm)
+        .unwrap();
+    walrus::passes::gc::run(&mut module);
+
+    assert_reexported(&module, "f");
+    assert_eq!(module.globals.iter().count(), 0);
+    Module::from_buffer(&module.emit_wasm()).unwrap();
+}
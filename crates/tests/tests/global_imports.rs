@@ -0,0 +1,80 @@
+use walrus::ir::Value;
+use walrus::{GlobalId, GlobalKind, InitExpr, Module};
+
+fn module() -> (Module, GlobalId, GlobalId) {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (import "env" "__stack_pointer" (global $sp (mut i32)))
+              (import "env" "__memory_base" (global $base i32))
+              (func (export "bump") (result i32)
+                (global.set $sp (i32.add (global.get $sp) (global.get $base)))
+                (global.get $sp)))
+        "#,
+    )
+    .unwrap();
+    let module = Module::from_buffer(&wasm).unwrap();
+    let sp = module.imports.find("env", "__stack_pointer").unwrap();
+    let base = module.imports.find("env", "__memory_base").unwrap();
+    let global = |id| match module.imports.get(id).kind {
+        walrus::ImportKind::Global(g) => g,
+        _ => unreachable!(),
+    };
+    let (sp, base) = (global(sp), global(base));
+    (module, sp, base)
+}
+
+#[test]
+fn rename_global_import() {
+    let (mut module, _, base) = module();
+    module
+        .rename_global_import(base, "GOT.mem", "__memory_base")
+        .unwrap();
+    assert!(module.imports.find("env", "__memory_base").is_none());
+    let import = module.globals.get(base).import().unwrap();
+    assert_eq!(
+        module.imports.find("GOT.mem", "__memory_base"),
+        Some(import)
+    );
+
+    // Renaming a global to its current name is fine.
+    module
+        .rename_global_import(base, "GOT.mem", "__memory_base")
+        .unwrap();
+    Module::from_buffer(&module.emit_wasm()).unwrap();
+}
+
+#[test]
+fn rename_global_import_collision() {
+    let (mut module, sp, _) = module();
+    assert!(module
+        .rename_global_import(sp, "env", "__memory_base")
+        .is_err());
+    assert!(module.imports.find("env", "__stack_pointer").is_some());
+}
+
+#[test]
+fn internalize_global_import() {
+    let (mut module, sp, _) = module();
+    module
+        .internalize_global_import(sp, InitExpr::Value(Value::I32(1024)))
+        .unwrap();
+
+    assert!(module.imports.find("env", "__stack_pointer").is_none());
+    assert_eq!(module.imports.iter().count(), 1);
+    match module.globals.get(sp).kind {
+        GlobalKind::Local(InitExpr::Value(Value::I32(1024))) => {}
+        ref kind => panic!("unexpected global kind: {:?}", kind),
+    }
+    assert!(module.globals.get(sp).mutable);
+
+    // Internalizing a global twice is an error.
+    assert!(module
+        .internalize_global_import(sp, InitExpr::Value(Value::I32(0)))
+        .is_err());
+
+    module.validate().unwrap();
+    let wasm = module.emit_wasm();
+    let module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(module.globals.iter_local().count(), 1);
+}
@@ -0,0 +1,47 @@
+use walrus::Module;
+
+#[test]
+fn id_index_map_matches_emitted_indices() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (import "env" "g" (global $imported_global i32))
+              (import "env" "f" (func $imported_func (result i32)))
+              (func $local (export "local") (result i32)
+                call $imported_func)
+              (global $local_global i32 (i32.const 1))
+              (memory 1)
+              (data (i32.const 0) "hi"))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+
+    // Imports added after the local function still come before it in the
+    // function index space.
+    let ty = module.types.add(&[], &[]);
+    let (late_import, _) = module.add_import_func("env", "late", ty);
+    let indices = module.id_index_map();
+
+    let func = |name: &str| {
+        module
+            .funcs
+            .iter()
+            .find(|f| f.name.as_deref() == Some(name))
+            .unwrap()
+            .id()
+    };
+    assert_eq!(indices.get_func_index(func("imported_func")), 0);
+    assert_eq!(indices.get_func_index(late_import), 1);
+    assert_eq!(indices.get_func_index(func("local")), 2);
+
+    let imported = module.globals.iter_imported().next().unwrap().id();
+    let local = module.globals.iter_local().next().unwrap().id();
+    assert_eq!(indices.get_global_index(imported), 0);
+    assert_eq!(indices.get_global_index(local), 1);
+
+    let memory = module.memories.iter().next().unwrap().id();
+    assert_eq!(indices.get_memory_index(memory), 0);
+    let data = module.data.iter().next().unwrap().id();
+    assert_eq!(indices.get_data_index(data), 0);
+}
@@ -0,0 +1,101 @@
+use walrus::ir::{BinaryOp, Const, Instr, InstrSeqType, Value};
+use walrus::{FunctionBuilder, Module, ModuleTypes, ValType};
+
+fn build(make_if: impl FnOnce(&mut walrus::InstrSeqBuilder, &ModuleTypes)) -> Module {
+    let mut module = Module::default();
+    let cond = module.locals.add(ValType::I32);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    let mut body = builder.func_body();
+    body.local_get(cond);
+    make_if(&mut body, &module.types);
+    let id = builder.finish(vec![cond], &mut module.funcs);
+    module.exports.add("f", id);
+    module
+}
+
+fn check(mut module: Module) {
+    module.validate().unwrap();
+
+    // The emitted `if` keeps its shape through a round trip.
+    let module = Module::from_buffer(&module.emit_wasm()).unwrap();
+    let func = module.funcs.iter_local().next().unwrap().1;
+    let entry = func.block(func.entry_block());
+    let if_else = entry.instrs[1].0.unwrap_if_else();
+    let consequent = &func.block(if_else.consequent).instrs;
+    assert!(matches!(
+        consequent[0].0,
+        Instr::Const(Const {
+            value: Value::I32(1)
+        })
+    ));
+    let alternative = &func.block(if_else.alternative).instrs;
+    assert_eq!(alternative.len(), 1);
+    assert!(alternative[0].0.is_unreachable());
+}
+
+#[test]
+fn if_else_with_unreachable_arm() {
+    check(build(|body, _| {
+        body.if_else(
+            ValType::I32,
+            |then| {
+                then.i32_const(1);
+            },
+            |else_| {
+                else_.unreachable();
+            },
+        );
+    }));
+}
+
+#[test]
+fn if_then_with_result() {
+    check(build(|body, types| {
+        body.if_then(types, ValType::I32, |then| {
+            then.i32_const(1);
+        });
+    }));
+}
+
+#[test]
+fn if_then_without_result() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .i32_const(0)
+        .if_then(&module.types, None, |then| {
+            then.i32_const(1).drop();
+        });
+    let id = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", id);
+    module.validate().unwrap();
+
+    let func = module.funcs.get(id).kind.unwrap_local();
+    let entry = func.block(func.entry_block());
+    let if_else = entry.instrs[1].0.unwrap_if_else();
+    assert!(func.block(if_else.alternative).instrs.is_empty());
+}
+
+#[test]
+fn if_then_passing_params_through() {
+    let mut module = Module::default();
+    let x = module.locals.add(ValType::I32);
+    let ty = InstrSeqType::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    builder
+        .func_body()
+        .local_get(x)
+        .local_get(x)
+        .if_then(&module.types, ty, |then| {
+            then.i32_const(1).binop(BinaryOp::I32Add);
+        });
+    let id = builder.finish(vec![x], &mut module.funcs);
+    module.exports.add("f", id);
+    module.validate().unwrap();
+
+    let func = module.funcs.get(id).kind.unwrap_local();
+    let entry = func.block(func.entry_block());
+    let if_else = entry.instrs[2].0.unwrap_if_else();
+    assert!(func.block(if_else.alternative).instrs.is_empty());
+}
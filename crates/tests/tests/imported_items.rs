@@ -0,0 +1,38 @@
+use walrus::{InitExpr, Module};
+
+#[test]
+fn local_and_imported_items() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (import "env" "g" (global i32))
+              (import "env" "t" (table 1 funcref))
+              (import "env" "m" (memory 1))
+              (global i32 (i32.const 7))
+              (global i64 (i64.const 8))
+              (table 2 funcref))
+        "#,
+    )
+    .unwrap();
+    let module = Module::from_buffer(&wasm).unwrap();
+
+    let imported: Vec<_> = module.globals.iter_imported().collect();
+    assert_eq!(imported.len(), 1);
+    assert!(imported[0].is_imported());
+    assert!(imported[0].import().is_some());
+    assert!(imported[0].init_expr().is_none());
+
+    let local: Vec<_> = module.globals.iter_local().collect();
+    assert_eq!(local.len(), 2);
+    assert!(local.iter().all(|g| !g.is_imported()));
+    match local[0].init_expr() {
+        Some(InitExpr::Value(walrus::ir::Value::I32(7))) => {}
+        other => panic!("unexpected initializer: {:?}", other),
+    }
+
+    assert_eq!(module.tables.iter_imported().count(), 1);
+    assert_eq!(module.tables.iter_local().count(), 1);
+    assert_eq!(module.tables.iter_local().next().unwrap().initial, 2);
+    assert_eq!(module.memories.iter_imported().count(), 1);
+    assert_eq!(module.memories.iter_local().count(), 0);
+}
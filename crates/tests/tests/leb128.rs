@@ -0,0 +1,93 @@
+use walrus::{ErrorKind, Module};
+
+const HEADER: &[u8] = b"\0asm\x01\0\0\0";
+
+fn module(sections: &[u8]) -> Vec<u8> {
+    let mut wasm = HEADER.to_vec();
+    wasm.extend_from_slice(sections);
+    wasm
+}
+
+fn leb128_error_offset(error: &anyhow::Error) -> Option<usize> {
+    match error.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::InvalidLeb128 { offset }) => Some(*offset),
+        _ => None,
+    }
+}
+
+#[test]
+fn over_long_section_size() {
+    // A type section whose size takes six bytes.
+    let wasm = module(&[0x01, 0x81, 0x80, 0x80, 0x80, 0x80, 0x00]);
+    let error = Module::from_buffer(&wasm).unwrap_err();
+    assert!(leb128_error_offset(&error).is_some(), "{:?}", error);
+}
+
+#[test]
+fn overflowing_count() {
+    // A type section whose count doesn't fit in a `u32`.
+    let wasm = module(&[0x01, 0x05, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+    let error = Module::from_buffer(&wasm).unwrap_err();
+    assert!(leb128_error_offset(&error).is_some(), "{:?}", error);
+}
+
+#[test]
+fn overflowing_i32_immediate() {
+    let wasm = module(&[
+        // (type (func))
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, //
+        // (func (type 0))
+        0x03, 0x02, 0x01, 0x00, //
+        // A body of `i32.const` with a 5-byte immediate whose unused bits
+        // aren't a sign extension, then `drop` and `end`.
+        0x0a, 0x0b, 0x01, 0x09, 0x00, 0x41, 0x80, 0x80, 0x80, 0x80, 0x40, 0x1a, 0x0b,
+    ]);
+    let error = Module::from_buffer(&wasm).unwrap_err();
+    assert!(leb128_error_offset(&error).is_some(), "{:?}", error);
+
+    let (_, errors) = Module::from_buffer_lenient(&wasm);
+    assert_eq!(errors.len(), 1);
+    assert!(leb128_error_offset(&errors[0].error).is_some());
+}
+
+#[test]
+fn truncated_leb128() {
+    // Cutting a module short anywhere inside a section must be rejected with
+    // an error, not a panic.
+    let wasm = module(&[0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f]);
+    for len in HEADER.len() + 1..wasm.len() {
+        assert!(Module::from_buffer(&wasm[..len]).is_err());
+    }
+    // A type section whose count has its continuation bit set, but ends.
+    let wasm = module(&[0x01, 0x01, 0x80]);
+    assert!(Module::from_buffer(&wasm).is_err());
+}
+
+#[test]
+fn overflowing_i64_immediate() {
+    let wasm = module(&[
+        // (type (func))
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, //
+        // (func (type 0))
+        0x03, 0x02, 0x01, 0x00, //
+        // A body of `i64.const` with a 10-byte immediate whose unused bits
+        // aren't a sign extension, then `drop` and `end`.
+        0x0a, 0x10, 0x01, 0x0e, 0x00, 0x42, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80,
+        0x40, 0x1a, 0x0b,
+    ]);
+    let error = Module::from_buffer(&wasm).unwrap_err();
+    assert_eq!(
+        leb128_error_offset(&error),
+        Some(HEADER.len() + 25),
+        "{:?}",
+        error
+    );
+}
+
+#[test]
+fn other_errors_are_not_leb128_errors() {
+    // A type whose form isn't `func`.
+    let wasm = module(&[0x01, 0x04, 0x01, 0x61, 0x00, 0x00]);
+    let error = Module::from_buffer(&wasm).unwrap_err();
+    assert_eq!(leb128_error_offset(&error), None, "{:?}", error);
+}
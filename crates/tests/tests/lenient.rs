@@ -0,0 +1,52 @@
+use walrus::{ExportItem, FunctionId, FunctionKind, Module};
+
+fn exported_func(module: &Module, name: &str) -> FunctionId {
+    match module.exports.iter().find(|e| e.name == name).unwrap().item {
+        ExportItem::Function(f) => f,
+        _ => panic!("`{}` is not an exported function", name),
+    }
+}
+
+#[test]
+fn lenient_parse_stubs_invalid_functions() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (func $good (export "good") (result i32)
+                    i32.const 1)
+                (func $bad1 (export "bad1") (result i32)
+                    i64.const 1)
+                (func $bad2 (export "bad2") (param i32)
+                    local.get 0))
+        "#,
+    )?;
+
+    assert!(Module::from_buffer(&wasm).is_err());
+
+    let (mut module, errors) = Module::from_buffer_lenient(&wasm);
+    assert_eq!(errors.len(), 2);
+
+    let bad1 = exported_func(&module, "bad1");
+    let bad2 = exported_func(&module, "bad2");
+    let mut failed = errors.iter().map(|e| e.func).collect::<Vec<_>>();
+    failed.sort();
+    let mut expected = vec![Some(bad1), Some(bad2)];
+    expected.sort();
+    assert_eq!(failed, expected);
+
+    for id in [bad1, bad2].iter() {
+        let local = match &module.funcs.get(*id).kind {
+            FunctionKind::Local(l) => l,
+            _ => panic!("expected a local function"),
+        };
+        let body = local.block(local.entry_block());
+        assert_eq!(body.len(), 1);
+        assert!(body[0].0.is_unreachable());
+    }
+
+    // The stubbed module is valid again.
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm)?;
+
+    Ok(())
+}
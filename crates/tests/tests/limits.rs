@@ -0,0 +1,80 @@
+use walrus::{ErrorKind, Limit, ModuleConfig};
+
+fn limit_exceeded(config: &ModuleConfig, wat: &str) -> Option<(Limit, u64, u64)> {
+    let wasm = wat::parse_str(wat).unwrap();
+    let err = match config.parse(&wasm) {
+        Ok(_) => return None,
+        Err(err) => err,
+    };
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::LimitExceeded {
+            which,
+            limit,
+            actual,
+        }) => Some((*which, *limit, *actual)),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn no_limits_by_default() {
+    let config = ModuleConfig::new();
+    let wat = r#"
+        (module
+            (memory 1000)
+            (table 1000 funcref)
+            (func (local i32 i32 i32 i32)))
+    "#;
+    assert_eq!(limit_exceeded(&config, wat), None);
+}
+
+#[test]
+fn limits_are_enforced() {
+    let mut config = ModuleConfig::new();
+    config.max_functions(2);
+    let wat = r#"
+        (module
+            (import "env" "f" (func))
+            (func)
+            (func))
+    "#;
+    assert_eq!(limit_exceeded(&config, wat), Some((Limit::Functions, 2, 3)));
+
+    let mut config = ModuleConfig::new();
+    config.max_locals_per_function(3);
+    let wat = "(module (func (param i32) (local i32 i32) (local i64 i64)))";
+    assert_eq!(
+        limit_exceeded(&config, wat),
+        Some((Limit::LocalsPerFunction, 3, 4))
+    );
+
+    let mut config = ModuleConfig::new();
+    config.max_memory_pages(10);
+    assert_eq!(limit_exceeded(&config, "(module (memory 10 100))"), None);
+    assert_eq!(
+        limit_exceeded(&config, "(module (memory 11))"),
+        Some((Limit::MemoryPages, 10, 11))
+    );
+
+    let mut config = ModuleConfig::new();
+    config.max_table_size(10);
+    assert_eq!(
+        limit_exceeded(&config, r#"(module (import "env" "t" (table 20 funcref)))"#),
+        Some((Limit::TableSize, 10, 20))
+    );
+
+    let mut config = ModuleConfig::new();
+    config.max_types(1);
+    assert_eq!(
+        limit_exceeded(&config, "(module (func) (func (param i32)))"),
+        Some((Limit::Types, 1, 2))
+    );
+
+    let mut config = ModuleConfig::new();
+    config.max_module_size(8);
+    assert_eq!(limit_exceeded(&config, "(module)"), None);
+    match limit_exceeded(&config, "(module (func))") {
+        Some((Limit::ModuleSize, 8, actual)) => assert!(actual > 8),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,75 @@
+use walrus::{FunctionBuilder, Module, ValType};
+
+fn read_leb(bytes: &[u8], pos: &mut usize) -> u32 {
+    let mut result = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*pos];
+        *pos += 1;
+        result |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return result;
+        }
+        shift += 7;
+    }
+}
+
+/// Get the `(count, type)` local declarations of the first function body in
+/// `wasm`.
+fn local_runs(wasm: &[u8]) -> Vec<(u32, u8)> {
+    let mut pos = 8;
+    loop {
+        let id = wasm[pos];
+        pos += 1;
+        let size = read_leb(wasm, &mut pos) as usize;
+        if id != 10 {
+            pos += size;
+            continue;
+        }
+        let _functions = read_leb(wasm, &mut pos);
+        let _body_size = read_leb(wasm, &mut pos);
+        let runs = read_leb(wasm, &mut pos);
+        return (0..runs)
+            .map(|_| {
+                let count = read_leb(wasm, &mut pos);
+                let ty = wasm[pos];
+                pos += 1;
+                (count, ty)
+            })
+            .collect();
+    }
+}
+
+/// Locals are declared with one `(count, type)` run per type, no matter what
+/// order they were created or used in.
+#[test]
+fn locals_are_grouped_by_type() {
+    let mut module = Module::default();
+    let tys = [
+        ValType::I32,
+        ValType::I64,
+        ValType::I32,
+        ValType::F32,
+        ValType::I64,
+        ValType::I32,
+    ];
+    let locals: Vec<_> = tys.iter().map(|ty| module.locals.add(*ty)).collect();
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let mut body = builder.func_body();
+    for (local, ty) in locals.iter().zip(&tys) {
+        match ty {
+            ValType::I32 => body.i32_const(0),
+            ValType::I64 => body.i64_const(0),
+            _ => body.f32_const(0.0),
+        };
+        body.local_set(*local);
+    }
+    builder.finish(vec![], &mut module.funcs);
+
+    let runs = local_runs(&module.emit_wasm());
+    assert_eq!(runs.len(), 3);
+    let mut counts: Vec<_> = runs.iter().map(|(count, _)| *count).collect();
+    counts.sort();
+    assert_eq!(counts, [1, 2, 3]);
+}
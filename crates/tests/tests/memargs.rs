@@ -0,0 +1,54 @@
+use walrus::ir::{Instr, MemArg};
+use walrus::Module;
+
+#[test]
+fn each_memarg_mut() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (memory 1 1 shared)
+              (func (param i32)
+                local.get 0
+                i32.load offset=4 align=1
+                drop
+                local.get 0
+                i64.const 0
+                i64.store16 align=1
+                local.get 0
+                v128.load32_splat align=1
+                drop
+                local.get 0
+                i32.const 1
+                i32.atomic.rmw.add
+                drop))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+
+    // Alignments are in bytes, so these are all byte-aligned apart from the
+    // atomic access, which must be naturally aligned.
+    let mut seen = Vec::new();
+    module.each_memarg_mut(|arg| {
+        seen.push(arg.align);
+        arg.align = 1;
+    });
+    assert_eq!(seen, [1, 1, 1, 4]);
+
+    let func = module.funcs.iter_local().next().unwrap().1;
+    let args: Vec<MemArg> = func
+        .block(func.entry_block())
+        .instrs
+        .iter()
+        .filter_map(|(instr, _)| match instr {
+            Instr::Load(e) => Some(e.arg),
+            Instr::Store(e) => Some(e.arg),
+            Instr::LoadSimd(e) => Some(e.arg),
+            Instr::AtomicRmw(e) => Some(e.arg),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(args.len(), 4);
+    assert!(args.iter().all(|arg| arg.align == 1));
+    assert_eq!(args[0].offset, 4);
+}
@@ -0,0 +1,32 @@
+use std::collections::HashSet;
+use walrus::{GlobalId, Module};
+
+#[test]
+fn mutated_globals() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (import "env" "imported" (global $imported (mut i32)))
+              (global $set (mut i32) (i32.const 0))
+              (global $never_set (mut i32) (i32.const 0))
+              (global $exported (mut i32) (i32.const 0))
+              (global $immutable i32 (i32.const 0))
+              (func (export "f")
+                (block
+                  (global.set $set (global.get $never_set))))
+              (export "exported" (global $exported))
+              (export "immutable" (global $immutable)))
+        "#,
+    )
+    .unwrap();
+    let module = Module::from_buffer(&wasm).unwrap();
+
+    let ids: Vec<GlobalId> = module.globals.iter().map(|g| g.id()).collect();
+    let (imported, set, exported) = (ids[0], ids[1], ids[3]);
+
+    assert_eq!(module.mutated_globals(), vec![set].into_iter().collect());
+    assert_eq!(
+        module.externally_mutable_globals(),
+        vec![imported, exported].into_iter().collect::<HashSet<_>>()
+    );
+}
@@ -0,0 +1,102 @@
+use walrus::{FunctionBuilder, Module, ModuleConfig, ValType};
+
+/// Build a module whose functions and locals are stored in a different order
+/// than their indices, so that a name section emitted in storage order would
+/// be unsorted, and check that strict consumers accept it.
+#[test]
+fn name_section_is_sorted_by_index() {
+    let mut config = ModuleConfig::new();
+    config.generate_name_section(true);
+    let mut module = Module::with_config(config.clone());
+
+    // Declare a local before the parameter, even though parameters come first
+    // in the local index space.
+    let tmp = module.locals.add(ValType::I32);
+    module.locals.get_mut(tmp).name = Some("tmp".to_string());
+    let param = module.locals.add(ValType::I32);
+    module.locals.get_mut(param).name = Some("param".to_string());
+
+    // Add a local function before an import, even though imported functions
+    // come first in the function index space.
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
+    builder.name("local".to_string());
+    builder.func_body().local_get(param).local_set(tmp);
+    let local = builder.finish(vec![param], &mut module.funcs);
+    module.exports.add("local", local);
+
+    let ty = module.types.add(&[], &[]);
+    let (import, _) = module.add_import_func("env", "imported", ty);
+    module.funcs.get_mut(import).name = Some("imported".to_string());
+
+    let wasm = module.emit_wasm();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("names.wasm");
+    std::fs::write(&path, &wasm).unwrap();
+    walrus_tests_utils::wasm_validate(&path).unwrap();
+
+    let module = config.parse(&wasm).unwrap();
+    let mut names: Vec<_> = module.funcs.iter().filter_map(|f| f.name.clone()).collect();
+    names.sort();
+    assert_eq!(names, ["imported", "local"]);
+    let mut locals: Vec<_> = module
+        .locals
+        .iter()
+        .filter_map(|l| l.name.clone())
+        .collect();
+    locals.sort();
+    assert_eq!(locals, ["param", "tmp"]);
+}
+
+#[test]
+fn module_name_round_trips() {
+    let mut config = ModuleConfig::new();
+    config.generate_name_section(true);
+    let mut module = Module::with_config(config.clone());
+    assert_eq!(module.name(), None);
+
+    module.set_name(Some("my-module".to_string()));
+    let wasm = module.emit_wasm();
+    let mut module = config.parse(&wasm).unwrap();
+    assert_eq!(module.name(), Some("my-module"));
+
+    module.set_name(None);
+    let wasm = module.emit_wasm();
+    let module = config.parse(&wasm).unwrap();
+    assert_eq!(module.name(), None);
+
+    // Without the name section, the name is dropped.
+    let mut config = ModuleConfig::new();
+    config.generate_name_section(false);
+    let mut module = Module::with_config(config.clone());
+    module.set_name(Some("my-module".to_string()));
+    let wasm = module.emit_wasm();
+    let module = Module::from_buffer(&wasm).unwrap();
+    assert_eq!(module.name(), None);
+}
+
+#[test]
+fn local_names_round_trip() {
+    let mut config = ModuleConfig::new();
+    config.generate_name_section(true);
+    let mut module = Module::with_config(config.clone());
+
+    let param = module.locals.add(ValType::I32);
+    module.locals.get_mut(param).name = Some("param".to_string());
+    let tmp = module.locals.add(ValType::I32);
+    module.locals.get_mut(tmp).name = Some("tmp".to_string());
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
+    builder.func_body().local_get(param).local_set(tmp);
+    let func = builder.finish(vec![param], &mut module.funcs);
+    module.exports.add("f", func);
+
+    let wasm = module.emit_wasm();
+    let module = config.parse(&wasm).unwrap();
+    let mut locals: Vec<_> = module
+        .locals
+        .iter()
+        .filter_map(|l| l.name.clone())
+        .collect();
+    locals.sort();
+    assert_eq!(locals, ["param", "tmp"]);
+}
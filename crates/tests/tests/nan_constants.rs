@@ -0,0 +1,64 @@
+use walrus::ir::ConstValue;
+use walrus::{FunctionBuilder, InitExpr, Module, ModuleConfig, ValType};
+
+const WAT: &str = r#"
+    (module
+      (global f64 (f64.const -nan:0x4000000000001))
+      (func (result f32)
+        f32.const nan:0x200001))
+"#;
+
+/// The value of the function's constant and of the global's initializer.
+fn constants(module: &Module) -> (ConstValue, ConstValue) {
+    let (_, func) = module.funcs.iter_local().next().unwrap();
+    let (instr, _) = &func.block(func.entry_block())[0];
+    let global: ConstValue = match module.globals.iter().next().unwrap().kind {
+        walrus::GlobalKind::Local(InitExpr::Value(value)) => value.into(),
+        ref other => panic!("unexpected global: {:?}", other),
+    };
+    (instr.as_const().unwrap(), global)
+}
+
+#[test]
+fn signaling_nans_round_trip_exactly_by_default() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    let expected = (
+        ConstValue::F32(0x7fa0_0001),
+        ConstValue::F64(0xfff4_0000_0000_0001),
+    );
+    assert_eq!(constants(&module), expected);
+
+    let module = Module::from_buffer(&module.emit_wasm()).unwrap();
+    assert_eq!(constants(&module), expected);
+}
+
+#[test]
+fn canonicalize_nans() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let module = ModuleConfig::new()
+        .canonicalize_nans(true)
+        .parse(&wasm)
+        .unwrap();
+    assert_eq!(
+        constants(&module),
+        (
+            ConstValue::F32(0x7fc0_0000),
+            ConstValue::F64(0x7ff8_0000_0000_0000),
+        )
+    );
+}
+
+#[test]
+fn built_signaling_nans_are_emitted_exactly() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::F32]);
+    builder.func_body().f32_const(f32::from_bits(0x7fa0_0001));
+    let func = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", func);
+
+    let module = Module::from_buffer(&module.emit_wasm()).unwrap();
+    let (_, func) = module.funcs.iter_local().next().unwrap();
+    let (instr, _) = &func.block(func.entry_block())[0];
+    assert_eq!(instr.as_const(), Some(ConstValue::F32(0x7fa0_0001)));
+}
@@ -0,0 +1,37 @@
+//! walrus has no `nop` instruction in its IR: `nop`s are dropped while
+//! parsing, so they never make it into the emitted wasm.
+
+use walrus::Module;
+
+fn emit(wat: &str) -> Vec<u8> {
+    let mut config = walrus::ModuleConfig::new();
+    config.generate_producers_section(false);
+    let wasm = wat::parse_str(wat).unwrap();
+    config.parse(&wasm).unwrap().emit_wasm()
+}
+
+#[test]
+fn nops_are_removed() {
+    let with_nops = emit(
+        r#"
+            (module
+              (func (export "f") (result i32)
+                nop
+                (block (result i32)
+                  nop
+                  i32.const 1
+                  nop)
+                nop))
+        "#,
+    );
+    let without_nops = emit(
+        r#"
+            (module
+              (func (export "f") (result i32)
+                (block (result i32)
+                  i32.const 1)))
+        "#,
+    );
+    assert_eq!(with_nops, without_nops);
+    Module::from_buffer(&with_nops).unwrap();
+}
@@ -0,0 +1,210 @@
+use walrus::ir::{Instr, Unop};
+use walrus::{FunctionBuilder, InstrSeqBuilder, Module, ValType};
+
+#[test]
+fn conversion_builders() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::F64], &[ValType::I64]);
+    let x = module.locals.add(ValType::F64);
+    builder
+        .func_body()
+        .local_get(x)
+        .f32_demote_f64()
+        .f64_promote_f32()
+        .i32_trunc_sat_f64_u()
+        .f32_convert_i32_s()
+        .i32_reinterpret_f32()
+        .i64_extend_i32_u()
+        .i64_extend8_s();
+    let id = builder.finish(vec![x], &mut module.funcs);
+    module.exports.add("f", id);
+
+    let func = module.funcs.get(id).kind.unwrap_local();
+    let ops: Vec<_> = func
+        .block(func.entry_block())
+        .iter()
+        .filter_map(|(instr, _)| match instr {
+            Instr::Unop(Unop { op }) => Some(format!("{:?}", op)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        ops,
+        [
+            "F32DemoteF64",
+            "F64PromoteF32",
+            "I32TruncUSatF64",
+            "F32ConvertSI32",
+            "I32ReinterpretF32",
+            "I64ExtendUI32",
+            "I64Extend8S",
+        ]
+    );
+
+    // The conversions type check, so the module round trips.
+    let wasm = module.emit_wasm();
+    Module::from_buffer(&wasm).unwrap();
+}
+
+#[test]
+fn numeric_op_builders() {
+    // Each operator's operand and result types, so that every builder method
+    // can be checked to produce an instruction with the right type.
+    type Op = for<'a, 'b> fn(&'a mut InstrSeqBuilder<'b>) -> &'a mut InstrSeqBuilder<'b>;
+    let ops: &[(Op, usize, ValType, ValType)] = &[
+        (|b| b.i32_eqz(), 1, ValType::I32, ValType::I32),
+        (|b| b.i32_clz(), 1, ValType::I32, ValType::I32),
+        (|b| b.i32_ctz(), 1, ValType::I32, ValType::I32),
+        (|b| b.i32_popcnt(), 1, ValType::I32, ValType::I32),
+        (|b| b.i64_eqz(), 1, ValType::I64, ValType::I32),
+        (|b| b.i64_clz(), 1, ValType::I64, ValType::I64),
+        (|b| b.i64_ctz(), 1, ValType::I64, ValType::I64),
+        (|b| b.i64_popcnt(), 1, ValType::I64, ValType::I64),
+        (|b| b.f32_abs(), 1, ValType::F32, ValType::F32),
+        (|b| b.f32_neg(), 1, ValType::F32, ValType::F32),
+        (|b| b.f32_ceil(), 1, ValType::F32, ValType::F32),
+        (|b| b.f32_floor(), 1, ValType::F32, ValType::F32),
+        (|b| b.f32_trunc(), 1, ValType::F32, ValType::F32),
+        (|b| b.f32_nearest(), 1, ValType::F32, ValType::F32),
+        (|b| b.f32_sqrt(), 1, ValType::F32, ValType::F32),
+        (|b| b.f64_abs(), 1, ValType::F64, ValType::F64),
+        (|b| b.f64_neg(), 1, ValType::F64, ValType::F64),
+        (|b| b.f64_ceil(), 1, ValType::F64, ValType::F64),
+        (|b| b.f64_floor(), 1, ValType::F64, ValType::F64),
+        (|b| b.f64_trunc(), 1, ValType::F64, ValType::F64),
+        (|b| b.f64_nearest(), 1, ValType::F64, ValType::F64),
+        (|b| b.f64_sqrt(), 1, ValType::F64, ValType::F64),
+        (|b| b.i32_eq(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_ne(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_lt_s(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_lt_u(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_gt_s(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_gt_u(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_le_s(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_le_u(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_ge_s(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_ge_u(), 2, ValType::I32, ValType::I32),
+        (|b| b.i64_eq(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_ne(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_lt_s(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_lt_u(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_gt_s(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_gt_u(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_le_s(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_le_u(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_ge_s(), 2, ValType::I64, ValType::I32),
+        (|b| b.i64_ge_u(), 2, ValType::I64, ValType::I32),
+        (|b| b.f32_eq(), 2, ValType::F32, ValType::I32),
+        (|b| b.f32_ne(), 2, ValType::F32, ValType::I32),
+        (|b| b.f32_lt(), 2, ValType::F32, ValType::I32),
+        (|b| b.f32_gt(), 2, ValType::F32, ValType::I32),
+        (|b| b.f32_le(), 2, ValType::F32, ValType::I32),
+        (|b| b.f32_ge(), 2, ValType::F32, ValType::I32),
+        (|b| b.f64_eq(), 2, ValType::F64, ValType::I32),
+        (|b| b.f64_ne(), 2, ValType::F64, ValType::I32),
+        (|b| b.f64_lt(), 2, ValType::F64, ValType::I32),
+        (|b| b.f64_gt(), 2, ValType::F64, ValType::I32),
+        (|b| b.f64_le(), 2, ValType::F64, ValType::I32),
+        (|b| b.f64_ge(), 2, ValType::F64, ValType::I32),
+        (|b| b.i32_add(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_sub(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_mul(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_div_s(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_div_u(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_rem_s(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_rem_u(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_and(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_or(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_xor(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_shl(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_shr_s(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_shr_u(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_rotl(), 2, ValType::I32, ValType::I32),
+        (|b| b.i32_rotr(), 2, ValType::I32, ValType::I32),
+        (|b| b.i64_add(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_sub(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_mul(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_div_s(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_div_u(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_rem_s(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_rem_u(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_and(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_or(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_xor(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_shl(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_shr_s(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_shr_u(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_rotl(), 2, ValType::I64, ValType::I64),
+        (|b| b.i64_rotr(), 2, ValType::I64, ValType::I64),
+        (|b| b.f32_add(), 2, ValType::F32, ValType::F32),
+        (|b| b.f32_sub(), 2, ValType::F32, ValType::F32),
+        (|b| b.f32_mul(), 2, ValType::F32, ValType::F32),
+        (|b| b.f32_div(), 2, ValType::F32, ValType::F32),
+        (|b| b.f32_min(), 2, ValType::F32, ValType::F32),
+        (|b| b.f32_max(), 2, ValType::F32, ValType::F32),
+        (|b| b.f32_copysign(), 2, ValType::F32, ValType::F32),
+        (|b| b.f64_add(), 2, ValType::F64, ValType::F64),
+        (|b| b.f64_sub(), 2, ValType::F64, ValType::F64),
+        (|b| b.f64_mul(), 2, ValType::F64, ValType::F64),
+        (|b| b.f64_div(), 2, ValType::F64, ValType::F64),
+        (|b| b.f64_min(), 2, ValType::F64, ValType::F64),
+        (|b| b.f64_max(), 2, ValType::F64, ValType::F64),
+        (|b| b.f64_copysign(), 2, ValType::F64, ValType::F64),
+        (|b| b.i32_wrap_i64(), 1, ValType::I64, ValType::I32),
+        (|b| b.i64_extend_i32_s(), 1, ValType::I32, ValType::I64),
+        (|b| b.i64_extend_i32_u(), 1, ValType::I32, ValType::I64),
+        (|b| b.i32_extend8_s(), 1, ValType::I32, ValType::I32),
+        (|b| b.i32_extend16_s(), 1, ValType::I32, ValType::I32),
+        (|b| b.i64_extend8_s(), 1, ValType::I64, ValType::I64),
+        (|b| b.i64_extend16_s(), 1, ValType::I64, ValType::I64),
+        (|b| b.i64_extend32_s(), 1, ValType::I64, ValType::I64),
+        (|b| b.i32_trunc_f32_s(), 1, ValType::F32, ValType::I32),
+        (|b| b.i32_trunc_f32_u(), 1, ValType::F32, ValType::I32),
+        (|b| b.i32_trunc_f64_s(), 1, ValType::F64, ValType::I32),
+        (|b| b.i32_trunc_f64_u(), 1, ValType::F64, ValType::I32),
+        (|b| b.i64_trunc_f32_s(), 1, ValType::F32, ValType::I64),
+        (|b| b.i64_trunc_f32_u(), 1, ValType::F32, ValType::I64),
+        (|b| b.i64_trunc_f64_s(), 1, ValType::F64, ValType::I64),
+        (|b| b.i64_trunc_f64_u(), 1, ValType::F64, ValType::I64),
+        (|b| b.i32_trunc_sat_f32_s(), 1, ValType::F32, ValType::I32),
+        (|b| b.i32_trunc_sat_f32_u(), 1, ValType::F32, ValType::I32),
+        (|b| b.i32_trunc_sat_f64_s(), 1, ValType::F64, ValType::I32),
+        (|b| b.i32_trunc_sat_f64_u(), 1, ValType::F64, ValType::I32),
+        (|b| b.i64_trunc_sat_f32_s(), 1, ValType::F32, ValType::I64),
+        (|b| b.i64_trunc_sat_f32_u(), 1, ValType::F32, ValType::I64),
+        (|b| b.i64_trunc_sat_f64_s(), 1, ValType::F64, ValType::I64),
+        (|b| b.i64_trunc_sat_f64_u(), 1, ValType::F64, ValType::I64),
+        (|b| b.f32_convert_i32_s(), 1, ValType::I32, ValType::F32),
+        (|b| b.f32_convert_i32_u(), 1, ValType::I32, ValType::F32),
+        (|b| b.f32_convert_i64_s(), 1, ValType::I64, ValType::F32),
+        (|b| b.f32_convert_i64_u(), 1, ValType::I64, ValType::F32),
+        (|b| b.f64_convert_i32_s(), 1, ValType::I32, ValType::F64),
+        (|b| b.f64_convert_i32_u(), 1, ValType::I32, ValType::F64),
+        (|b| b.f64_convert_i64_s(), 1, ValType::I64, ValType::F64),
+        (|b| b.f64_convert_i64_u(), 1, ValType::I64, ValType::F64),
+        (|b| b.f32_demote_f64(), 1, ValType::F64, ValType::F32),
+        (|b| b.f64_promote_f32(), 1, ValType::F32, ValType::F64),
+        (|b| b.i32_reinterpret_f32(), 1, ValType::F32, ValType::I32),
+        (|b| b.i64_reinterpret_f64(), 1, ValType::F64, ValType::I64),
+        (|b| b.f32_reinterpret_i32(), 1, ValType::I32, ValType::F32),
+        (|b| b.f64_reinterpret_i64(), 1, ValType::I64, ValType::F64),
+    ];
+
+    let mut module = Module::default();
+    for (op, arity, operand, result) in ops {
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[*result]);
+        let mut body = builder.func_body();
+        for _ in 0..*arity {
+            match operand {
+                ValType::I32 => body.i32_const(1),
+                ValType::I64 => body.i64_const(1),
+                ValType::F32 => body.f32_const(1.0),
+                ValType::F64 => body.f64_const(1.0),
+                _ => unreachable!(),
+            };
+        }
+        op(&mut body);
+        builder.finish(vec![], &mut module.funcs);
+    }
+    module.validate().unwrap();
+}
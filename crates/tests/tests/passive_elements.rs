@@ -0,0 +1,50 @@
+use walrus::{FunctionBuilder, Module, ValType};
+
+#[test]
+fn table_init_from_passive_segment() {
+    let mut module = Module::default();
+    let table = module.tables.add_local(2, None, ValType::Funcref);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body();
+    let callee = builder.finish(vec![], &mut module.funcs);
+
+    // A passive segment that nothing uses is removed by GC, which shifts the
+    // index of the used one down.
+    module.elements.add_passive(ValType::Funcref, vec![None]);
+    let elem = module
+        .elements
+        .add_passive(ValType::Funcref, vec![Some(callee), None]);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder
+        .func_body()
+        .i32_const(0) // table offset
+        .i32_const(0) // segment offset
+        .i32_const(2) // length
+        .table_init(table, elem)
+        .elem_drop(elem);
+    let init = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("init", init);
+
+    walrus::passes::gc::run(&mut module);
+    module.validate().unwrap();
+
+    let module = Module::from_buffer(&module.emit_wasm()).unwrap();
+    let elements: Vec<_> = module.elements.iter().collect();
+    assert_eq!(elements.len(), 1);
+    assert!(matches!(elements[0].kind, walrus::ElementKind::Passive));
+    assert_eq!(elements[0].members.len(), 2);
+    assert!(elements[0].members[0].is_some());
+    assert!(elements[0].members[1].is_none());
+
+    let (_, func) = module
+        .funcs
+        .iter_local()
+        .find(|(_, f)| f.size() > 1)
+        .unwrap();
+    let instrs = &func.block(func.entry_block()).instrs;
+    let table_init = instrs[3].0.unwrap_table_init();
+    assert_eq!(table_init.elem, elements[0].id());
+    assert_eq!(instrs[4].0.unwrap_elem_drop().elem, elements[0].id());
+}
@@ -0,0 +1,39 @@
+use walrus::passes;
+use walrus_tests_utils::{assert_transform_preserves_behavior, transform_preserves_behavior};
+
+const WAT: &str = r#"
+    (module
+      (func (export "sum") (result i64)
+        i64.const 0x1234_5678_9abc
+        i64.const 0x1234_5678_9abc
+        i64.add
+        i64.const 0x1234_5678_9abc
+        i64.add
+        return
+        drop
+        unreachable))
+"#;
+
+#[test]
+fn passes_preserve_behavior() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    assert_transform_preserves_behavior(&wasm, |module| {
+        passes::dce(module);
+        passes::hoist_constants(module, 2);
+    });
+}
+
+#[test]
+fn changed_behavior_is_detected() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let result = transform_preserves_behavior(&wasm, |module| {
+        passes::hoist_constants(module, 2);
+        for (_, func) in module.funcs.iter_local_mut() {
+            let entry = func.entry_block();
+            // Change the value of the hoisted constant.
+            func.block_mut(entry).instrs[0].0 =
+                walrus::ir::Instr::const_(walrus::ir::ConstValue::I64(1));
+        }
+    });
+    assert!(result.is_err());
+}
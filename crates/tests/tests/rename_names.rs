@@ -0,0 +1,53 @@
+use walrus::ir::BinaryOp;
+use walrus::{FunctionBuilder, Module, NameKind, ValType};
+
+#[test]
+fn rename_names() {
+    let mut module = Module::default();
+    module.name = Some("m".to_string());
+
+    // Local names are set by hand, since they aren't read back from the name
+    // section when parsing.
+    let a = module.locals.add(ValType::I32);
+    module.locals.get_mut(a).name = Some("a".to_string());
+    let b = module.locals.add(ValType::I32);
+    module.locals.get_mut(b).name = Some("b".to_string());
+
+    let mut builder = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32],
+    );
+    builder.name("add".to_string());
+    builder
+        .func_body()
+        .local_get(a)
+        .local_get(b)
+        .binop(BinaryOp::I32Add);
+    let add = builder.finish(vec![a, b], &mut module.funcs);
+    module.exports.add("add", add);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.name("keep".to_string());
+    builder.func_body();
+    let keep = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("keep", keep);
+
+    module.rename_names(|kind, name| match kind {
+        NameKind::Module => Some(name.to_uppercase()),
+        NameKind::Function(_) if name == "keep" => None,
+        NameKind::Function(_) => Some(format!("f_{}", name)),
+        NameKind::Local(id) if id == a => Some("l0".to_string()),
+        NameKind::Local(_) => Some("l1".to_string()),
+        _ => None,
+    });
+
+    assert_eq!(module.locals.get(a).name.as_deref(), Some("l0"));
+    assert_eq!(module.locals.get(b).name.as_deref(), Some("l1"));
+
+    let module = Module::from_buffer(&module.emit_wasm()).unwrap();
+    assert_eq!(module.name.as_deref(), Some("M"));
+    let mut funcs: Vec<_> = module.funcs.iter().map(|f| f.name.clone()).collect();
+    funcs.sort();
+    assert_eq!(funcs, [Some("f_add".to_string()), Some("keep".to_string())]);
+}
@@ -0,0 +1,71 @@
+use walrus::ir::{dfs_in_order, Call, Instr, RefFunc, Visitor};
+use walrus::{FunctionId, GlobalKind, InitExpr, Module};
+
+fn calls_to(module: &Module, target: FunctionId) -> usize {
+    struct Count(FunctionId, usize);
+
+    impl<'instr> Visitor<'instr> for Count {
+        fn visit_call(&mut self, call: &Call) {
+            if call.func == self.0 {
+                self.1 += 1;
+            }
+        }
+    }
+
+    let mut count = Count(target, 0);
+    for (_, func) in module.funcs.iter_local() {
+        dfs_in_order(&mut count, func, func.entry_block());
+    }
+    count.1
+}
+
+#[test]
+fn replace_all_calls() -> anyhow::Result<()> {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (import "env" "malloc" (func $malloc (param i32) (result i32)))
+                (func $traced_malloc (param i32) (result i32)
+                    local.get 0
+                    call $malloc)
+                (func $other (param i32))
+                (func $user (param i32) (result i32)
+                    local.get 0
+                    call $malloc
+                    (if (result i32)
+                        (then (call $malloc (i32.const 1)))
+                        (else (i32.const 0))))
+                (func $malloc_ref (result funcref)
+                    ref.func $malloc)
+                (global $malloc_global funcref (ref.func $malloc))
+                (table 1 funcref)
+                (elem (i32.const 0) $malloc))
+        "#,
+    )?;
+    let mut module = Module::from_buffer(&wasm)?;
+    let malloc = module.funcs.by_name("malloc").unwrap();
+    let traced = module.funcs.by_name("traced_malloc").unwrap();
+    let other = module.funcs.by_name("other").unwrap();
+
+    assert!(module.replace_all_calls(malloc, other, true).is_err());
+    assert_eq!(calls_to(&module, malloc), 3);
+
+    assert_eq!(module.replace_all_calls(malloc, traced, true)?, 3);
+    assert_eq!(calls_to(&module, malloc), 0);
+    assert_eq!(calls_to(&module, traced), 3);
+
+    let elem = module.elements.iter().next().unwrap();
+    assert_eq!(elem.members, [Some(traced)]);
+
+    let malloc_ref = module.funcs.by_name("malloc_ref").unwrap();
+    let body = module.funcs.get(malloc_ref).kind.unwrap_local();
+    match &body.block(body.entry_block())[0].0 {
+        Instr::RefFunc(RefFunc { func }) => assert_eq!(*func, traced),
+        other => panic!("unexpected instruction: {:?}", other),
+    }
+    let global = module.globals.iter().next().unwrap();
+    assert!(matches!(global.kind, GlobalKind::Local(InitExpr::RefFunc(f)) if f == traced));
+
+    Module::from_buffer(&module.emit_wasm())?;
+    Ok(())
+}
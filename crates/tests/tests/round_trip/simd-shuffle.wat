@@ -0,0 +1,17 @@
+(module
+  (func $shuffle (export "shuffle") (param v128 v128 v128) (result v128)
+    local.get 0
+    local.get 1
+    i8x16.shuffle 16 1 18 3 20 5 22 7 24 9 26 11 28 13 30 31
+    local.get 2
+    i8x16.swizzle))
+
+;; CHECK: (module
+;; NEXT:    (type (;0;) (func (param v128 v128 v128) (result v128)))
+;; NEXT:    (func $shuffle (type 0) (param v128 v128 v128) (result v128)
+;; NEXT:      local.get 0
+;; NEXT:      local.get 1
+;; NEXT:      i8x16.shuffle 16 1 18 3 20 5 22 7 24 9 26 11 28 13 30 31
+;; NEXT:      local.get 2
+;; NEXT:      i8x16.swizzle)
+;; NEXT:    (export "shuffle" (func $shuffle)))
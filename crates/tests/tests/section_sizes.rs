@@ -0,0 +1,51 @@
+use walrus::Module;
+
+/// Walk the sections of `wasm`, returning each section's id and the number of
+/// bytes used to encode its size.
+fn section_size_widths(wasm: &[u8]) -> Vec<(u8, usize)> {
+    let mut widths = Vec::new();
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let id = wasm[pos];
+        pos += 1;
+        let (mut size, mut width) = (0usize, 0);
+        loop {
+            let byte = wasm[pos + width];
+            size |= usize::from(byte & 0x7f) << (7 * width);
+            width += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        widths.push((id, width));
+        pos += width + size;
+    }
+    assert_eq!(pos, wasm.len());
+    widths
+}
+
+#[test]
+fn section_sizes_are_fixed_width() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (type (func))
+              (import "env" "f" (func (type 0)))
+              (memory 1)
+              (global i32 (i32.const 0))
+              (func (export "g") (call 0))
+              (data (i32.const 0) "hello"))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    let wasm = module.emit_wasm();
+
+    let widths = section_size_widths(&wasm);
+    // Type, import, function, memory, global, export, code, data, and at
+    // least the producers custom section.
+    assert!(widths.len() >= 9, "{:?}", widths);
+    for (id, width) in widths {
+        assert_eq!(width, 5, "section {} has a {}-byte size", id, width);
+    }
+}
@@ -0,0 +1,26 @@
+use walrus::{DataKind, ElementKind, Module, ModuleConfig, ValType};
+
+#[test]
+fn segment_names_round_trip() {
+    let mut config = ModuleConfig::new();
+    config.generate_name_section(true);
+    let mut module = Module::with_config(config.clone());
+
+    let unnamed = module.data.add(DataKind::Passive, b"gone".to_vec());
+    let rodata = module.data.add(DataKind::Passive, b"hi".to_vec());
+    module.data.get_mut(rodata).name = Some(".rodata".to_string());
+    let elem = module
+        .elements
+        .add(ElementKind::Passive, ValType::Funcref, Vec::new());
+    module.elements.get_mut(elem).name = Some("callbacks".to_string());
+
+    // Removing the first segment shifts the named one down to index 0.
+    module.data.delete(unnamed);
+
+    let wasm = module.emit_wasm();
+    let module = config.parse(&wasm).unwrap();
+    let data: Vec<_> = module.data.iter().map(|d| d.name.as_deref()).collect();
+    assert_eq!(data, [Some(".rodata")]);
+    let elements: Vec<_> = module.elements.iter().map(|e| e.name.as_deref()).collect();
+    assert_eq!(elements, [Some("callbacks")]);
+}
@@ -0,0 +1,25 @@
+use walrus::{FunctionBuilder, Module};
+
+#[test]
+fn shrink_to_fit_keeps_ids() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let mut body = builder.func_body();
+    for _ in 0..1000 {
+        body.i32_const(1).drop();
+    }
+    let func = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", func);
+
+    let local = module.funcs.get_mut(func).kind.unwrap_local_mut();
+    let entry = local.entry_block();
+    local.block_mut(entry).instrs.truncate(2);
+
+    module.shrink_to_fit();
+
+    let local = module.funcs.get(func).kind.unwrap_local();
+    let instrs = &local.block(entry).instrs;
+    assert_eq!(instrs.len(), 2);
+    assert!(instrs.capacity() < 1000);
+    Module::from_buffer(&module.emit_wasm()).unwrap();
+}
@@ -0,0 +1,85 @@
+use walrus::ir::Value;
+use walrus::{FunctionBuilder, InstrSeqBuilder, Module, ValType};
+
+/// Build and validate a function with the given results, whose body is built
+/// by `f`.
+fn check(results: &[ValType], f: impl FnOnce(&mut InstrSeqBuilder)) {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], results);
+    f(&mut builder.func_body());
+    let func = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", func);
+    module.validate().unwrap();
+    Module::from_buffer(&module.emit_wasm()).unwrap();
+}
+
+fn v128<'a, 'b>(body: &'b mut InstrSeqBuilder<'a>) -> &'b mut InstrSeqBuilder<'a> {
+    body.const_(Value::V128(0x0102_0304))
+}
+
+#[test]
+fn splat() {
+    check(&[ValType::V128], |body| {
+        body.i32_const(1)
+            .i8x16_splat()
+            .drop()
+            .i64_const(1)
+            .i64x2_splat()
+            .drop()
+            .f64_const(1.0)
+            .f64x2_splat();
+    });
+}
+
+#[test]
+fn lanes() {
+    check(&[ValType::I32, ValType::F64], |body| {
+        v128(body).i32_const(7).i8x16_replace_lane(15);
+        body.i8x16_extract_lane_u(15);
+        v128(body).f64_const(2.0).f64x2_replace_lane(1);
+        body.f64x2_extract_lane(1);
+    });
+}
+
+#[test]
+#[should_panic(expected = "lane index 16 is out of bounds for 16 lanes")]
+fn extract_lane_out_of_bounds() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    v128(&mut builder.func_body()).i8x16_extract_lane_s(16);
+}
+
+#[test]
+#[should_panic(expected = "lane index 2 is out of bounds for 2 lanes")]
+fn replace_lane_out_of_bounds() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    v128(&mut builder.func_body())
+        .i64_const(0)
+        .i64x2_replace_lane(2);
+}
+
+#[test]
+fn comparisons() {
+    check(&[ValType::V128], |body| {
+        v128(body);
+        v128(body).i8x16_lt_u();
+        v128(body).i32x4_ge_s();
+        v128(body).i64x2_ne();
+        v128(body).f32x4_le();
+    });
+}
+
+#[test]
+fn arithmetic() {
+    check(&[ValType::V128], |body| {
+        v128(body);
+        v128(body).i8x16_add_sat_s();
+        v128(body).i16x8_mul();
+        v128(body).i32x4_max_u();
+        v128(body).i64x2_sub();
+        v128(body).f32x4_pmin();
+        v128(body).f64x2_div();
+        body.f64x2_sqrt().i32x4_neg().i8x16_abs();
+    });
+}
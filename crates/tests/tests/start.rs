@@ -0,0 +1,45 @@
+use walrus::{ExportItem, Module};
+
+#[test]
+fn demote_start_to_export() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (global (mut i32) (i32.const 0))
+              (func $init
+                i32.const 1
+                global.set 0)
+              (start $init))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    module.demote_start_to_export("_initialize").unwrap();
+    assert!(module.start.is_none());
+    assert!(module.demote_start_to_export("_initialize").is_err());
+
+    let module = Module::from_buffer(&module.emit_wasm()).unwrap();
+    assert!(module.start.is_none());
+    let export = module.exports.iter().next().unwrap();
+    assert_eq!(export.name, "_initialize");
+    match export.item {
+        ExportItem::Function(f) => assert_eq!(module.funcs.get(f).name.as_deref(), Some("init")),
+        other => panic!("unexpected export: {:?}", other),
+    }
+}
+
+#[test]
+fn demote_start_to_export_rejects_taken_names() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (func $init)
+              (func (export "_initialize"))
+              (start $init))
+        "#,
+    )
+    .unwrap();
+    let mut module = Module::from_buffer(&wasm).unwrap();
+    assert!(module.demote_start_to_export("_initialize").is_err());
+    assert!(module.start.is_some());
+}
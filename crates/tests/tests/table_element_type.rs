@@ -0,0 +1,54 @@
+use walrus::{Module, ValType};
+
+fn module(wat: &str) -> Module {
+    Module::from_buffer(&wat::parse_str(wat).unwrap()).unwrap()
+}
+
+#[test]
+fn unused_table_can_change_type() {
+    let mut module = module("(module (table 1 funcref))");
+    let table = module.tables.iter().next().unwrap().id();
+    module
+        .set_table_element_type(table, ValType::Externref)
+        .unwrap();
+    assert_eq!(module.tables.get(table).element_ty, ValType::Externref);
+    Module::from_buffer(&module.emit_wasm()).unwrap();
+}
+
+#[test]
+fn call_indirect_requires_funcref() {
+    let mut module = module(
+        r#"
+            (module
+                (type (func))
+                (table 1 funcref)
+                (func (call_indirect (type 0) (i32.const 0))))
+        "#,
+    );
+    let table = module.tables.iter().next().unwrap().id();
+    let err = module
+        .set_table_element_type(table, ValType::Externref)
+        .unwrap_err();
+    assert!(err.to_string().contains("call_indirect"), "{}", err);
+    assert_eq!(module.tables.get(table).element_ty, ValType::Funcref);
+}
+
+#[test]
+fn active_segment_must_match() {
+    let mut module = module(
+        r#"
+            (module
+                (table 1 funcref)
+                (func $f)
+                (elem (i32.const 0) $f))
+        "#,
+    );
+    let table = module.tables.iter().next().unwrap().id();
+    assert!(module
+        .set_table_element_type(table, ValType::Externref)
+        .is_err());
+    assert!(module.set_table_element_type(table, ValType::I32).is_err());
+    module
+        .set_table_element_type(table, ValType::Funcref)
+        .unwrap();
+}
@@ -0,0 +1,58 @@
+use std::fs;
+use walrus::ir::Instr;
+use walrus::{Module, ModuleConfig};
+use walrus_tests_utils::wasm_interp;
+
+const WAT: &str = r#"
+    (module
+      (type $pair (func (param i32) (result i32 i64)))
+      (table 1 funcref)
+      (elem (i32.const 0) $pair)
+      (func $pair (type $pair)
+        local.get 0
+        i64.const 7)
+      (func (export "direct") (result i32 i64)
+        i32.const 1
+        return_call $pair)
+      (func (export "indirect") (result i32 i64)
+        i32.const 2
+        i32.const 0
+        return_call_indirect (type $pair)))
+"#;
+
+#[test]
+fn tail_calls_are_rejected_by_default() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    assert!(Module::from_buffer(&wasm).is_err());
+}
+
+#[test]
+fn lower_tail_calls() {
+    let wasm = wat::parse_str(WAT).unwrap();
+    let mut config = ModuleConfig::new();
+    config.lower_tail_calls(true);
+    let mut module = config.parse(&wasm).unwrap();
+
+    for (_, func) in module.funcs.iter_local() {
+        let instrs: Vec<&Instr> = func
+            .block(func.entry_block())
+            .iter()
+            .map(|(instr, _)| instr)
+            .collect();
+        if instrs.iter().any(|i| i.is_call() || i.is_call_indirect()) {
+            assert!(instrs.last().unwrap().is_return());
+        }
+    }
+
+    // The lowered module doesn't need the tail call proposal, and behaves the
+    // same as the original, multiple results and all.
+    let lowered = module.emit_wasm();
+    Module::from_buffer(&lowered).unwrap();
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    fs::write(tmp.path(), &wasm).unwrap();
+    let expected = wasm_interp(tmp.path()).unwrap();
+    fs::write(tmp.path(), &lowered).unwrap();
+    let actual = wasm_interp(tmp.path()).unwrap();
+    assert_eq!(expected, actual);
+}
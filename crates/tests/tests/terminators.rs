@@ -0,0 +1,65 @@
+use walrus::ir::Instr;
+use walrus::{FunctionBuilder, FunctionId, Module, ValType};
+
+/// Finish `builder`, and check that the module is still valid by emitting it
+/// and parsing it again.
+fn finish(builder: FunctionBuilder, module: &mut Module) -> walrus::Result<FunctionId> {
+    let f = builder.finish(vec![], &mut module.funcs);
+    module.exports.add("f", f);
+    Module::from_buffer(&module.emit_wasm())?;
+    Ok(f)
+}
+
+#[test]
+fn return_if_unterminated_appends_return() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    let mut body = builder.func_body();
+    body.i32_const(1);
+    assert!(!body.is_terminated());
+    body.return_if_unterminated();
+    assert!(body.is_terminated());
+    let f = finish(builder, &mut module).unwrap();
+
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let instrs = func.block(func.entry_block());
+    assert_eq!(instrs.len(), 2);
+    assert!(matches!(instrs[1].0, Instr::Return(_)));
+}
+
+#[test]
+fn return_if_unterminated_keeps_existing_terminator() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    let mut body = builder.func_body();
+    body.unreachable();
+    assert!(body.is_terminated());
+    body.return_if_unterminated().unreachable_if_unterminated();
+    let f = finish(builder, &mut module).unwrap();
+
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let instrs = func.block(func.entry_block());
+    assert_eq!(instrs.len(), 1);
+    assert!(matches!(instrs[0].0, Instr::Unreachable(_)));
+}
+
+#[test]
+fn unreachable_if_unterminated_appends_unreachable() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::F64]);
+    builder.func_body().unreachable_if_unterminated();
+    let f = finish(builder, &mut module).unwrap();
+
+    let func = module.funcs.get(f).kind.unwrap_local();
+    let instrs = func.block(func.entry_block());
+    assert_eq!(instrs.len(), 1);
+    assert!(matches!(instrs[0].0, Instr::Unreachable(_)));
+}
+
+#[test]
+fn return_if_unterminated_with_wrong_stack_fails_validation() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder.func_body().i64_const(1).return_if_unterminated();
+    assert!(finish(builder, &mut module).is_err());
+}
@@ -0,0 +1,41 @@
+use walrus::{Module, TypeUse, ValType};
+
+#[test]
+fn used_types() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (type $unused (func (param f64)))
+              (type $sig (func (param i32) (result i32)))
+              (type $pair (func (result i32 i32)))
+              (table 1 funcref)
+              (func $f (type $sig)
+                (block (type $pair)
+                  i32.const 1
+                  i32.const 2)
+                drop
+                drop
+                local.get 0
+                i32.const 0
+                call_indirect (type $sig)))
+        "#,
+    )
+    .unwrap();
+    let module = Module::from_buffer(&wasm).unwrap();
+    let f = module.funcs.iter().next().unwrap().id();
+    let sig = module.types.find(&[ValType::I32], &[ValType::I32]).unwrap();
+    let pair = module
+        .types
+        .find(&[], &[ValType::I32, ValType::I32])
+        .unwrap();
+    let unused = module.types.find(&[ValType::F64], &[]).unwrap();
+
+    let used = module.used_types();
+    assert_eq!(
+        used[&sig],
+        [TypeUse::FunctionSignature(f), TypeUse::CallIndirect(f)]
+    );
+    assert_eq!(used[&pair].len(), 1);
+    assert!(matches!(used[&pair][0], TypeUse::BlockType(func, _) if func == f));
+    assert!(!used.contains_key(&unused));
+}
@@ -0,0 +1,243 @@
+use walrus::ir::{BinaryOp, LoadKind, MemArg, Value};
+use walrus::{ErrorKind, FunctionBuilder, Module, ModuleConfig, ValType, ValidationError};
+
+#[test]
+fn valid_module() {
+    let mut module = Module::default();
+    let ty = module.types.add(&[ValType::I32], &[]);
+    let (log, _) = module.add_import_func("env", "log", ty);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().i32_const(1).call(log);
+    builder.finish(vec![], &mut module.funcs);
+
+    module.validate().unwrap();
+}
+
+#[test]
+fn call_with_missing_argument() {
+    let mut module = Module::default();
+    let ty = module.types.add(&[ValType::I32], &[]);
+    let (log, _) = module.add_import_func("env", "log", ty);
+    module.funcs.get_mut(log).name = Some("log".to_string());
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.name("caller".to_string());
+    builder.func_body().call(log);
+    builder.finish(vec![], &mut module.funcs);
+
+    let error = format!("{:#}", module.validate().unwrap_err());
+    assert!(error.contains("function `caller` is invalid"), "{}", error);
+    assert!(
+        error.contains("function `log`, which expects [i32] -> []"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn function_body_too_large() {
+    let mut config = ModuleConfig::new();
+    config.max_function_body_size(8);
+    let mut module = Module::with_config(config);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.func_body().i32_const(1).drop();
+    builder.finish(vec![], &mut module.funcs);
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.name("big".to_string());
+    for _ in 0..10 {
+        builder.func_body().i32_const(1).drop();
+    }
+    let big = builder.finish(vec![], &mut module.funcs);
+
+    let error = module.validate().unwrap_err();
+    assert!(format!("{}", error).contains("function `big`"), "{}", error);
+    match error.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::FunctionTooLarge { func, limit, .. }) => {
+            assert_eq!(*func, big);
+            assert_eq!(*limit, 8);
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[test]
+fn unstable_feature_with_only_stable_features() {
+    let build = |config: ModuleConfig| {
+        let mut module = Module::with_config(config);
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+        builder.name("simd".to_string());
+        builder.func_body().const_(Value::V128(0)).drop();
+        builder.finish(vec![], &mut module.funcs);
+        module
+    };
+
+    build(ModuleConfig::new()).validate().unwrap();
+
+    let mut config = ModuleConfig::new();
+    config.only_stable_features(true);
+    let error = format!("{:#}", build(config).validate().unwrap_err());
+    assert!(error.contains("function `simd` is invalid"), "{}", error);
+    assert!(error.contains("SIMD"), "{}", error);
+}
+
+#[test]
+fn data_past_initial_memory() {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (memory 1)
+              (data (i32.const 65530) "0123456789"))
+        "#,
+    )
+    .unwrap();
+
+    // The module is valid wasm, so this is only an error in strict mode.
+    let module = ModuleConfig::new().parse(&wasm).unwrap();
+    module.validate().unwrap();
+
+    let mut config = ModuleConfig::new();
+    config.strict_data_bounds(true);
+    let mut module = config.parse(&wasm).unwrap();
+    let error = format!("{}", module.validate().unwrap_err());
+    assert!(error.contains("byte 65540"), "{}", error);
+    assert!(error.contains("memory 0 (1 pages)"), "{}", error);
+
+    let memory = module.memories.iter().next().unwrap().id();
+    module.memories.get_mut(memory).initial = 2;
+    module.validate().unwrap();
+}
+
+#[test]
+fn type_mismatch_location() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    let mut block = None;
+    builder.func_body().i32_const(1).drop().block(None, |b| {
+        block = Some(b.id());
+        b.i32_const(1).i64_const(2).binop(BinaryOp::I32Add).drop();
+    });
+    let func = builder.finish(vec![], &mut module.funcs);
+
+    let error = module.validate().unwrap_err();
+    let error = error
+        .downcast_ref::<ValidationError>()
+        .unwrap_or_else(|| panic!("no location in {:?}", error));
+    assert_eq!(error.func, func);
+    assert_eq!(error.seq, block.unwrap());
+    assert_eq!(error.index, 2);
+    assert!(error.message.contains("type mismatch"), "{}", error.message);
+}
+
+#[test]
+fn type_mismatch_at_end_of_function() {
+    let mut module = Module::default();
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder.func_body().i64_const(1);
+    let entry = builder.func_body_id();
+    let func = builder.finish(vec![], &mut module.funcs);
+
+    let error = module.validate().unwrap_err();
+    let error = error
+        .downcast_ref::<ValidationError>()
+        .unwrap_or_else(|| panic!("no location in {:?}", error));
+    assert_eq!(error.func, func);
+    assert_eq!(error.seq, entry);
+    // The problem is with the end of the function body, not the `i64.const`.
+    assert_eq!(error.index, 1);
+}
+
+fn atomic_load(shared: bool, align: u32, strict: bool) -> walrus::Result<()> {
+    let mut config = ModuleConfig::new();
+    config.strict_atomics(strict);
+    let mut module = Module::with_config(config);
+    let memory = module.memories.add_local(shared, 1, Some(1));
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+    builder.name("atomic".to_string());
+    builder
+        .func_body()
+        .i32_const(0)
+        .load(
+            memory,
+            LoadKind::I32 { atomic: true },
+            MemArg { align, offset: 0 },
+        )
+        .drop();
+    builder.finish(vec![], &mut module.funcs);
+    module.validate()
+}
+
+#[test]
+fn aligned_atomic_load_on_shared_memory() {
+    atomic_load(true, 4, true).unwrap();
+}
+
+#[test]
+fn misaligned_atomic_load() {
+    let error = atomic_load(true, 2, false).unwrap_err();
+    assert!(
+        format!("{}", error).contains("function `atomic` is invalid"),
+        "{}",
+        error
+    );
+    let error = error.downcast_ref::<ValidationError>().unwrap();
+    assert_eq!(error.index, 1);
+    assert!(
+        error
+            .message
+            .contains("`i32.atomic.load` must be aligned to exactly 4 bytes"),
+        "{}",
+        error.message
+    );
+}
+
+#[test]
+fn atomic_load_on_unshared_memory() {
+    atomic_load(false, 4, false).unwrap();
+}
+
+#[test]
+fn strict_atomic_load_on_unshared_memory() {
+    let error = atomic_load(false, 4, true).unwrap_err();
+    let error = error.downcast_ref::<ValidationError>().unwrap();
+    assert_eq!(error.index, 1);
+    assert!(error.message.contains("isn't shared"), "{}", error.message);
+}
+
+#[test]
+fn finish_validated() {
+    let mut module = Module::default();
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder
+        .func_body()
+        .i32_const(1)
+        .i32_const(2)
+        .binop(BinaryOp::I32Add);
+    let good = builder.finish_validated(vec![], &mut module).unwrap();
+
+    let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    builder.name("bad".to_string());
+    let body = builder.func_body_id();
+    builder
+        .func_body()
+        .i32_const(1)
+        .i64_const(2)
+        .binop(BinaryOp::I32Add);
+    let error = builder.finish_validated(vec![], &mut module).unwrap_err();
+
+    assert!(
+        format!("{}", error).contains("function `bad` is invalid"),
+        "{}",
+        error
+    );
+    let location = error.downcast_ref::<ValidationError>().unwrap();
+    assert_eq!((location.seq, location.index), (body, 2));
+
+    // The bad function was taken back out, leaving a valid module.
+    let funcs: Vec<_> = module.funcs.iter().map(|f| f.id()).collect();
+    assert_eq!(funcs, [good]);
+    module.validate().unwrap();
+}
@@ -0,0 +1,92 @@
+use walrus::ir::{Instr, Value};
+use walrus::{InstrPosition, Module};
+
+fn module() -> Module {
+    let wasm = wat::parse_str(
+        r#"
+            (module
+              (func (export "f") (param i32) (result i32)
+                (block (result i32)
+                  (loop
+                    i32.const 1
+                    br_if 0)
+                  (if (result i32) (local.get 0)
+                    (then i32.const 2)
+                    (else i32.const 3)))
+                i32.const 4
+                i32.add))
+        "#,
+    )
+    .unwrap();
+    Module::from_buffer(&wasm).unwrap()
+}
+
+fn const_positions(module: &Module) -> Vec<(i32, InstrPosition)> {
+    let mut consts = Vec::new();
+    module.visit_all(|pos, instr, _| {
+        if let Instr::Const(c) = instr {
+            if let Value::I32(n) = c.value {
+                consts.push((n, pos));
+            }
+        }
+    });
+    consts
+}
+
+#[test]
+fn visit_all() {
+    let module = module();
+    let (func, local) = module.funcs.iter_local().next().unwrap();
+    let entry = local.entry_block();
+    let block = match &local.block(entry)[0].0 {
+        Instr::Block(b) => b.seq,
+        other => panic!("unexpected instruction: {:?}", other),
+    };
+    let looop = match &local.block(block)[0].0 {
+        Instr::Loop(l) => l.seq,
+        other => panic!("unexpected instruction: {:?}", other),
+    };
+    let (consequent, alternative) = match &local.block(block)[2].0 {
+        Instr::IfElse(i) => (i.consequent, i.alternative),
+        other => panic!("unexpected instruction: {:?}", other),
+    };
+
+    let pos = |seq, index| InstrPosition { func, seq, index };
+    assert_eq!(
+        const_positions(&module),
+        vec![
+            (1, pos(looop, 0)),
+            (2, pos(consequent, 0)),
+            (3, pos(alternative, 0)),
+            (4, pos(entry, 1)),
+        ]
+    );
+
+    // Every position that's passed along should point back at the same
+    // instruction.
+    let mut count = 0;
+    module.visit_all(|pos, instr, _| {
+        let local = module.funcs.get(pos.func).kind.unwrap_local();
+        assert!(std::ptr::eq(&local.block(pos.seq)[pos.index].0, instr));
+        count += 1;
+    });
+    assert_eq!(count, 10);
+}
+
+#[test]
+fn visit_all_mut() {
+    let mut module = module();
+    let before = const_positions(&module);
+
+    module.visit_all_mut(|_, instr, _| {
+        if let Instr::Const(c) = instr {
+            if let Value::I32(n) = &mut c.value {
+                *n *= 10;
+            }
+        }
+    });
+
+    let after = const_positions(&module);
+    let expected: Vec<_> = before.into_iter().map(|(n, pos)| (n * 10, pos)).collect();
+    assert_eq!(after, expected);
+}
@@ -5,7 +5,7 @@ use std::hash::Hash;
 use std::ops;
 
 /// A set of unique `T`s that are backed by an arena.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ArenaSet<T: Clone + Eq + Hash> {
     arena: TombstoneArena<T>,
     already_in_arena: HashMap<T, Id<T>>,
@@ -20,6 +20,12 @@ impl<T: Clone + Eq + Hash> ArenaSet<T> {
         }
     }
 
+    /// Preallocate space for `additional` more items.
+    pub fn reserve(&mut self, additional: usize) {
+        self.arena.reserve(additional);
+        self.already_in_arena.reserve(additional);
+    }
+
     /// Insert a value into the arena and get its id.
     pub fn insert(&mut self, val: T) -> Id<T> {
         if let Some(id) = self.already_in_arena.get(&val) {
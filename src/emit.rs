@@ -8,7 +8,7 @@ use crate::map::{IdHashMap, IdHashSet};
 use crate::{CodeTransform, Global, GlobalId, Memory, MemoryId, Module, Table, TableId};
 use crate::{Data, DataId, Element, ElementId, Function, FunctionId};
 use crate::{Type, TypeId};
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
 
 pub struct EmitContext<'a> {
     pub module: &'a Module,
@@ -51,6 +51,7 @@ pub struct IdsToIndices {
     memories: IdHashMap<Memory, u32>,
     elements: IdHashMap<Element, u32>,
     data: IdHashMap<Data, u32>,
+    code_ranges: IdHashMap<Function, Range<usize>>,
     pub(crate) locals: IdHashMap<Function, IdHashMap<Local, u32>>,
 }
 
@@ -114,6 +115,21 @@ impl IdsToIndices {
     pub(crate) fn set_data_index(&mut self, id: DataId, idx: u32) {
         self.data.insert(id, idx);
     }
+
+    /// Sets the range of bytes that the given function's body was emitted to
+    pub(crate) fn set_func_code_range(&mut self, id: FunctionId, range: Range<usize>) {
+        self.code_ranges.insert(id, range);
+    }
+
+    /// Get the range of bytes, within the emitted wasm, of the given local
+    /// function's body.
+    ///
+    /// The range starts at the function's locals declarations, just after the
+    /// body's size. Returns `None` if the function is not a local function, or
+    /// the code section has not been emitted yet.
+    pub fn get_func_code_range(&self, id: FunctionId) -> Option<Range<usize>> {
+        self.code_ranges.get(&id).cloned()
+    }
 }
 
 impl<'a> EmitContext<'a> {
@@ -123,6 +139,8 @@ impl<'a> EmitContext<'a> {
 
     pub fn subsection<'b>(&'b mut self, id: u8) -> SubContext<'a, 'b> {
         self.encoder.byte(id);
+        // Section sizes are always written with the maximum width, which
+        // `Module::emit_wasm` documents, so that they can be patched later.
         let start = self.encoder.reserve_u32();
         SubContext {
             cx: self,
@@ -1,5 +1,7 @@
 //! Error types and utilities.
 
+use crate::ir::InstrSeqId;
+use crate::FunctionId;
 pub use anyhow::Error;
 use std::fmt;
 
@@ -14,14 +16,207 @@ pub use anyhow::Result;
 pub enum ErrorKind {
     /// Given invalid input wasm.
     InvalidWasm,
+    /// The input wasm contains a malformed LEB128-encoded integer: one that
+    /// is encoded with more bytes than its type allows, or whose value
+    /// overflows its type.
+    InvalidLeb128 {
+        /// The offset in the input of the byte where the integer was found to
+        /// be malformed.
+        offset: usize,
+    },
+    /// A function's encoded body is larger than the
+    /// `ModuleConfig::max_function_body_size` that `Module::validate` checks.
+    FunctionTooLarge {
+        /// The function that is too large.
+        func: FunctionId,
+        /// The size of the function's encoded body, in bytes.
+        size: u64,
+        /// The configured limit, in bytes.
+        limit: u64,
+    },
+    /// The function section declares a different number of functions than the
+    /// code section has bodies for. A missing code section counts as having
+    /// no bodies.
+    FunctionCountMismatch {
+        /// The number of functions declared by the function section.
+        declared: u32,
+        /// The number of bodies in the code section.
+        code: u32,
+    },
+    /// The input wasm exceeds one of the resource limits set on its
+    /// `ModuleConfig`.
+    LimitExceeded {
+        /// Which limit was exceeded.
+        which: Limit,
+        /// The configured limit.
+        limit: u64,
+        /// The amount that the module asked for.
+        actual: u64,
+    },
 }
 
 impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ErrorKind::InvalidWasm => "The input WebAssembly is invalid".fmt(f),
+            ErrorKind::InvalidLeb128 { offset } => write!(
+                f,
+                "The input WebAssembly has a malformed LEB128 integer at offset {}",
+                offset
+            ),
+            ErrorKind::FunctionTooLarge { func, size, limit } => write!(
+                f,
+                "The body of {:?} is {} bytes, more than the limit of {}",
+                func, size, limit
+            ),
+            ErrorKind::FunctionCountMismatch { declared, code } => write!(
+                f,
+                "The input WebAssembly declares {} functions but has {} function bodies",
+                declared, code
+            ),
+            ErrorKind::LimitExceeded {
+                which,
+                limit,
+                actual,
+            } => write!(
+                f,
+                "The input WebAssembly has {} {}, more than the limit of {}",
+                actual, which, limit
+            ),
+        }
+    }
+}
+
+/// A resource limit that can be set on a `ModuleConfig` when parsing untrusted
+/// modules.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Limit {
+    /// The size of the module, in bytes. See `ModuleConfig::max_module_size`.
+    ModuleSize,
+    /// The number of types. See `ModuleConfig::max_types`.
+    Types,
+    /// The number of functions, including imported ones. See
+    /// `ModuleConfig::max_functions`.
+    Functions,
+    /// The number of locals declared by a single function. See
+    /// `ModuleConfig::max_locals_per_function`.
+    LocalsPerFunction,
+    /// The initial number of pages of a single memory. See
+    /// `ModuleConfig::max_memory_pages`.
+    MemoryPages,
+    /// The initial number of elements of a single table. See
+    /// `ModuleConfig::max_table_size`.
+    TableSize,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::ModuleSize => "bytes".fmt(f),
+            Limit::Types => "types".fmt(f),
+            Limit::Functions => "functions".fmt(f),
+            Limit::LocalsPerFunction => "locals in a function".fmt(f),
+            Limit::MemoryPages => "pages in a memory".fmt(f),
+            Limit::TableSize => "elements in a table".fmt(f),
         }
     }
 }
 
 impl std::error::Error for ErrorKind {}
+
+/// If `error` was caused by a malformed LEB128 integer in `wasm`, attach an
+/// `ErrorKind::InvalidLeb128` to it so that callers can tell it apart.
+///
+/// All LEB128 decoding is done by `wasmparser`, which already rejects
+/// over-long and overflowing encodings rather than truncating them, but its
+/// errors only carry a message and an offset. So this looks at the input
+/// where the error happened instead.
+pub(crate) fn classify_leb128(error: Error, wasm: &[u8]) -> Error {
+    let offset = error
+        .chain()
+        .filter_map(|e| e.downcast_ref::<wasmparser::BinaryReaderError>())
+        .map(|e| e.offset())
+        .find(|&offset| is_malformed_leb128(wasm, offset));
+    match offset {
+        Some(offset) => error.context(ErrorKind::InvalidLeb128 { offset }),
+        None => error,
+    }
+}
+
+/// Is the byte at `offset` the last byte that a 32- or 64-bit LEB128 integer
+/// may take up, and one that makes the integer malformed?
+///
+/// `wasmparser` reports malformed integers at exactly this byte: the fifth
+/// byte of a 32-bit integer, or the tenth of a 64-bit one. Such a byte is
+/// malformed if its continuation bit is set, or if it sets bits past the
+/// integer's width.
+fn is_malformed_leb128(wasm: &[u8], offset: usize) -> bool {
+    let byte = match wasm.get(offset) {
+        Some(byte) => *byte,
+        None => return false,
+    };
+    let preceding = wasm[..offset]
+        .iter()
+        .rev()
+        .take(9)
+        .take_while(|b| *b & 0x80 != 0)
+        .count();
+    let unused_bits = match preceding {
+        4 => 0x70,
+        9 => 0x7e,
+        _ => return false,
+    };
+    byte & 0x80 != 0 || byte & unused_bits != 0
+}
+
+/// An error that was recovered from while parsing a module with
+/// `Module::from_buffer_lenient`.
+#[derive(Debug)]
+pub struct ParseError {
+    /// The function whose body failed to parse, or `None` if the error was not
+    /// specific to a single function.
+    pub func: Option<FunctionId>,
+    /// The underlying error.
+    pub error: Error,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.func {
+            Some(func) => write!(f, "failed to parse {:?}: {:#}", func, self.error),
+            None => write!(f, "{:#}", self.error),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Where in a function `Module::validate` found it to be invalid.
+///
+/// This is attached to the error that `Module::validate` returns for an
+/// invalid function body, and can be retrieved with `downcast_ref`.
+#[derive(Clone, Debug)]
+pub struct ValidationError {
+    /// The invalid function.
+    pub func: FunctionId,
+    /// The instruction sequence containing the invalid instruction.
+    pub seq: InstrSeqId,
+    /// The index of the invalid instruction within `seq`. If this is `seq`'s
+    /// length, then the problem is with the end of the sequence, for example
+    /// if it leaves the wrong values on the stack.
+    pub index: usize,
+    /// The validator's description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (at instruction {} of {:?})",
+            self.message, self.index, self.seq
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
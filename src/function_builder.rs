@@ -1,6 +1,9 @@
 use crate::ir::*;
 use crate::tombstone_arena::TombstoneArena;
-use crate::{FunctionId, LocalFunction, ModuleFunctions, ModuleTypes, TypeId, ValType};
+use crate::{
+    FunctionId, LocalFunction, MemoryId, Module, ModuleFunctions, ModuleLocals, ModuleTypes,
+    Result, TypeId, ValType,
+};
 use std::ops::{Deref, DerefMut};
 
 /// Build instances of `LocalFunction`.
@@ -12,7 +15,7 @@ use std::ops::{Deref, DerefMut};
 ///
 /// * For a bit more realistic example, see
 ///   [`examples/build-wasm-from-scratch.rs`](https://github.com/rustwasm/walrus/blob/master/examples/build-wasm-from-scratch.rs).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FunctionBuilder {
     pub(crate) arena: TombstoneArena<InstrSeq>,
     pub(crate) ty: TypeId,
@@ -35,6 +38,32 @@ impl FunctionBuilder {
         builder
     }
 
+    /// Allocate a new local for each of the given types, returning their ids in
+    /// the same order.
+    ///
+    /// This is handy for declaring all of a function's locals up front.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use walrus::ValType;
+    ///
+    /// let mut module = walrus::Module::default();
+    /// let mut builder = walrus::FunctionBuilder::new(&mut module.types, &[], &[]);
+    /// let locals = walrus::FunctionBuilder::with_locals(
+    ///     &[ValType::I32, ValType::F64],
+    ///     &mut module.locals,
+    /// );
+    ///
+    /// builder
+    ///     .func_body()
+    ///     .i32_const(1)
+    ///     .local_set(locals[0]);
+    /// ```
+    pub fn with_locals(types: &[ValType], locals: &mut ModuleLocals) -> Vec<LocalId> {
+        types.iter().map(|ty| locals.add(*ty)).collect()
+    }
+
     /// Create a builder that doesn't have a function body / entry
     /// sequence. Callers are responsible for initializing its entry.
     pub(crate) fn without_entry(ty: TypeId) -> FunctionBuilder {
@@ -155,6 +184,43 @@ impl FunctionBuilder {
         funcs.add_local(func)
     }
 
+    /// Like `finish`, but checks that the function is valid before leaving it
+    /// in `module`.
+    ///
+    /// The function is added to `module`, and then `module` is validated with
+    /// `Module::validate`. If that fails, the function is removed again and
+    /// the validation error is returned, which points at the offending
+    /// instruction when the problem is in the function's body, such as a
+    /// type mismatch or a body whose results don't match its signature.
+    ///
+    /// Since this validates the whole module, it costs about as much as
+    /// emitting it, and any problems elsewhere in `module` are reported too.
+    /// It's meant for catching bugs in code generators where they're
+    /// introduced, by keeping the module valid as it's being built up; use
+    /// `finish` when that isn't needed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut module = walrus::Module::default();
+    /// let mut builder =
+    ///     walrus::FunctionBuilder::new(&mut module.types, &[], &[walrus::ValType::I32]);
+    ///
+    /// // Oops, this function is supposed to return an `i32`.
+    /// builder.func_body().i64_const(1234);
+    ///
+    /// assert!(builder.finish_validated(vec![], &mut module).is_err());
+    /// assert_eq!(module.funcs.iter().count(), 0);
+    /// ```
+    pub fn finish_validated(self, args: Vec<LocalId>, module: &mut Module) -> Result<FunctionId> {
+        let id = self.finish(args, &mut module.funcs);
+        if let Err(error) = module.validate() {
+            module.funcs.delete(id);
+            return Err(error);
+        }
+        Ok(id)
+    }
+
     /// Returns the [crate::LocalFunction] built by this builder.
     pub fn local_func(self, args: Vec<LocalId>) -> LocalFunction {
         LocalFunction::new(args, self)
@@ -208,6 +274,56 @@ impl InstrSeqBuilder<'_> {
         self
     }
 
+    /// Does this builder's sequence end with an instruction that
+    /// unconditionally transfers control elsewhere, such as `unreachable`,
+    /// `return` or `br`?
+    pub fn is_terminated(&self) -> bool {
+        self.instrs().last().map_or(false, |(instr, _)| {
+            instr.following_instructions_are_unreachable()
+        })
+    }
+
+    /// Append an `unreachable` instruction to this builder's sequence, unless
+    /// it is already terminated.
+    ///
+    /// Since the stack is polymorphic after `unreachable`, this always leaves
+    /// the sequence well-typed at its end, whatever its result type.
+    pub fn unreachable_if_unterminated(&mut self) -> &mut Self {
+        if !self.is_terminated() {
+            self.unreachable();
+        }
+        self
+    }
+
+    /// Append a `return` instruction to this builder's sequence, unless it is
+    /// already terminated.
+    ///
+    /// Note that, like `return_`, this does not check that the values on the
+    /// stack match the function's results. The builder doesn't know the types
+    /// of the locals, globals and functions that instructions refer to, so it
+    /// can't track what is on the stack, and a mismatch only shows up when
+    /// the emitted module is validated.
+    pub fn return_if_unterminated(&mut self) -> &mut Self {
+        if !self.is_terminated() {
+            self.return_();
+        }
+        self
+    }
+
+    /// Append `n` `drop` instructions to this builder's sequence, discarding
+    /// the top `n` values on the stack, such as the results of a multi-value
+    /// call.
+    ///
+    /// Wasm has no instruction for dropping several values at once, so this is
+    /// only sugar. Like `drop`, it does not check that the stack actually holds
+    /// `n` values; that is up to the caller.
+    pub fn drop_n(&mut self, n: usize) -> &mut Self {
+        for _ in 0..n {
+            self.drop();
+        }
+        self
+    }
+
     /// Creates an `i32.const` instruction for the specified value.
     #[inline]
     pub fn i32_const(&mut self, val: i32) -> &mut Self {
@@ -232,6 +348,69 @@ impl InstrSeqBuilder<'_> {
         self.const_(Value::F64(val))
     }
 
+    /// Creates a load instruction of the given kind, reading from `offset`
+    /// bytes past the address on top of the stack.
+    ///
+    /// Unlike `load`, this checks the alignment hint: `align` is given in bytes
+    /// (like `MemArg::align`, not as the exponent used in the binary format),
+    /// and `None` means the natural alignment of the access.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` isn't a power of two, if it is greater than the
+    /// number of bytes loaded, or if `kind` is atomic and `align` isn't the
+    /// natural alignment.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use walrus::ir::LoadKind;
+    ///
+    /// let mut module = walrus::Module::default();
+    /// let memory = module.memories.add_local(false, 1, None);
+    /// let mut builder = walrus::FunctionBuilder::new(&mut module.types, &[], &[]);
+    ///
+    /// // i32.const 0
+    /// // i32.load offset=8 align=2
+    /// // drop
+    /// builder
+    ///     .func_body()
+    ///     .i32_const(0)
+    ///     .load_with_align(memory, LoadKind::I32 { atomic: false }, 8, Some(2))
+    ///     .drop();
+    /// ```
+    pub fn load_with_align(
+        &mut self,
+        memory: MemoryId,
+        kind: LoadKind,
+        offset: u32,
+        align: Option<u32>,
+    ) -> &mut Self {
+        let arg = checked_mem_arg(kind.width(), kind.atomic(), offset, align);
+        self.load(memory, kind, arg)
+    }
+
+    /// Creates a store instruction of the given kind, writing to `offset` bytes
+    /// past the address under the value on top of the stack.
+    ///
+    /// The alignment hint is handled the same way as by `load_with_align`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` isn't a power of two, if it is greater than the
+    /// number of bytes stored, or if `kind` is atomic and `align` isn't the
+    /// natural alignment.
+    pub fn store_with_align(
+        &mut self,
+        memory: MemoryId,
+        kind: StoreKind,
+        offset: u32,
+        align: Option<u32>,
+    ) -> &mut Self {
+        let arg = checked_mem_arg(kind.width(), kind.atomic(), offset, align);
+        self.store(memory, kind, arg)
+    }
+
     /// Append a new, nested `block ... end` to this builder's sequence.
     ///
     /// # Example:
@@ -496,6 +675,729 @@ impl InstrSeqBuilder<'_> {
             },
         )
     }
+
+    /// Append a new, nested `if <consequent> end`, with no `else` arm, to this
+    /// builder's sequence.
+    ///
+    /// An `if` without an `else` only validates if its results are the same as
+    /// its parameters, which it then passes straight through when its
+    /// condition is false. That is the case when `ty` is empty, or is a
+    /// multi-value type (looked up in `types`) whose parameters and results
+    /// match, and then the `else` arm is left empty. Otherwise, the missing
+    /// `else` arm is built as a lone `unreachable`, which is polymorphic and
+    /// so produces whatever the `if` needs: the `if` traps when its condition
+    /// is false.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use walrus::ValType;
+    ///
+    /// let mut module = walrus::Module::default();
+    /// let mut builder = walrus::FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+    ///
+    /// // (if (result i32) (i32.const 1)
+    /// //   (then (i32.const 2))
+    /// //   (else (unreachable)))
+    /// builder
+    ///     .func_body()
+    ///     .i32_const(1)
+    ///     .if_then(&module.types, ValType::I32, |then| {
+    ///         then.i32_const(2);
+    ///     });
+    /// ```
+    pub fn if_then(
+        &mut self,
+        types: &ModuleTypes,
+        ty: impl Into<InstrSeqType>,
+        consequent: impl FnOnce(&mut InstrSeqBuilder),
+    ) -> &mut Self {
+        let ty = ty.into();
+        let passes_through = match ty {
+            InstrSeqType::Simple(result) => result.is_none(),
+            InstrSeqType::MultiValue(ty) => {
+                let (params, results) = types.params_results(ty);
+                params == results
+            }
+        };
+        self.if_else(ty, consequent, |alternative| {
+            if !passes_through {
+                alternative.unreachable();
+            }
+        })
+    }
+}
+
+/// Defines a builder method for each of the given unary operators, which
+/// appends a `unop` of the corresponding `UnaryOp`.
+macro_rules! unops {
+    ($($(#[$attr:meta])* $method:ident => $op:ident,)*) => {
+        impl InstrSeqBuilder<'_> {
+            $(
+                $(#[$attr])*
+                #[inline]
+                pub fn $method(&mut self) -> &mut Self {
+                    self.unop(UnaryOp::$op)
+                }
+            )*
+        }
+    };
+}
+
+/// Defines a builder method for each of the given binary operators, which
+/// appends a `binop` of the corresponding `BinaryOp`.
+macro_rules! binops {
+    ($($(#[$attr:meta])* $method:ident => $op:ident,)*) => {
+        impl InstrSeqBuilder<'_> {
+            $(
+                $(#[$attr])*
+                #[inline]
+                pub fn $method(&mut self) -> &mut Self {
+                    self.binop(BinaryOp::$op)
+                }
+            )*
+        }
+    };
+}
+
+unops! {
+    // Integer operations.
+    /// Creates an `i32.eqz` instruction.
+    i32_eqz => I32Eqz,
+    /// Creates an `i32.clz` instruction.
+    i32_clz => I32Clz,
+    /// Creates an `i32.ctz` instruction.
+    i32_ctz => I32Ctz,
+    /// Creates an `i32.popcnt` instruction.
+    i32_popcnt => I32Popcnt,
+    /// Creates an `i64.eqz` instruction.
+    i64_eqz => I64Eqz,
+    /// Creates an `i64.clz` instruction.
+    i64_clz => I64Clz,
+    /// Creates an `i64.ctz` instruction.
+    i64_ctz => I64Ctz,
+    /// Creates an `i64.popcnt` instruction.
+    i64_popcnt => I64Popcnt,
+
+    // Float operations.
+    /// Creates an `f32.abs` instruction.
+    f32_abs => F32Abs,
+    /// Creates an `f32.neg` instruction.
+    f32_neg => F32Neg,
+    /// Creates an `f32.ceil` instruction.
+    f32_ceil => F32Ceil,
+    /// Creates an `f32.floor` instruction.
+    f32_floor => F32Floor,
+    /// Creates an `f32.trunc` instruction.
+    f32_trunc => F32Trunc,
+    /// Creates an `f32.nearest` instruction.
+    f32_nearest => F32Nearest,
+    /// Creates an `f32.sqrt` instruction.
+    f32_sqrt => F32Sqrt,
+    /// Creates an `f64.abs` instruction.
+    f64_abs => F64Abs,
+    /// Creates an `f64.neg` instruction.
+    f64_neg => F64Neg,
+    /// Creates an `f64.ceil` instruction.
+    f64_ceil => F64Ceil,
+    /// Creates an `f64.floor` instruction.
+    f64_floor => F64Floor,
+    /// Creates an `f64.trunc` instruction.
+    f64_trunc => F64Trunc,
+    /// Creates an `f64.nearest` instruction.
+    f64_nearest => F64Nearest,
+    /// Creates an `f64.sqrt` instruction.
+    f64_sqrt => F64Sqrt,
+}
+
+binops! {
+    // Integer comparisons.
+    /// Creates an `i32.eq` instruction.
+    i32_eq => I32Eq,
+    /// Creates an `i32.ne` instruction.
+    i32_ne => I32Ne,
+    /// Creates an `i32.lt_s` instruction.
+    i32_lt_s => I32LtS,
+    /// Creates an `i32.lt_u` instruction.
+    i32_lt_u => I32LtU,
+    /// Creates an `i32.gt_s` instruction.
+    i32_gt_s => I32GtS,
+    /// Creates an `i32.gt_u` instruction.
+    i32_gt_u => I32GtU,
+    /// Creates an `i32.le_s` instruction.
+    i32_le_s => I32LeS,
+    /// Creates an `i32.le_u` instruction.
+    i32_le_u => I32LeU,
+    /// Creates an `i32.ge_s` instruction.
+    i32_ge_s => I32GeS,
+    /// Creates an `i32.ge_u` instruction.
+    i32_ge_u => I32GeU,
+    /// Creates an `i64.eq` instruction.
+    i64_eq => I64Eq,
+    /// Creates an `i64.ne` instruction.
+    i64_ne => I64Ne,
+    /// Creates an `i64.lt_s` instruction.
+    i64_lt_s => I64LtS,
+    /// Creates an `i64.lt_u` instruction.
+    i64_lt_u => I64LtU,
+    /// Creates an `i64.gt_s` instruction.
+    i64_gt_s => I64GtS,
+    /// Creates an `i64.gt_u` instruction.
+    i64_gt_u => I64GtU,
+    /// Creates an `i64.le_s` instruction.
+    i64_le_s => I64LeS,
+    /// Creates an `i64.le_u` instruction.
+    i64_le_u => I64LeU,
+    /// Creates an `i64.ge_s` instruction.
+    i64_ge_s => I64GeS,
+    /// Creates an `i64.ge_u` instruction.
+    i64_ge_u => I64GeU,
+
+    // Float comparisons.
+    /// Creates an `f32.eq` instruction.
+    f32_eq => F32Eq,
+    /// Creates an `f32.ne` instruction.
+    f32_ne => F32Ne,
+    /// Creates an `f32.lt` instruction.
+    f32_lt => F32Lt,
+    /// Creates an `f32.gt` instruction.
+    f32_gt => F32Gt,
+    /// Creates an `f32.le` instruction.
+    f32_le => F32Le,
+    /// Creates an `f32.ge` instruction.
+    f32_ge => F32Ge,
+    /// Creates an `f64.eq` instruction.
+    f64_eq => F64Eq,
+    /// Creates an `f64.ne` instruction.
+    f64_ne => F64Ne,
+    /// Creates an `f64.lt` instruction.
+    f64_lt => F64Lt,
+    /// Creates an `f64.gt` instruction.
+    f64_gt => F64Gt,
+    /// Creates an `f64.le` instruction.
+    f64_le => F64Le,
+    /// Creates an `f64.ge` instruction.
+    f64_ge => F64Ge,
+
+    // Integer arithmetic, bitwise operations, shifts, and rotations.
+    /// Creates an `i32.add` instruction.
+    i32_add => I32Add,
+    /// Creates an `i32.sub` instruction.
+    i32_sub => I32Sub,
+    /// Creates an `i32.mul` instruction.
+    i32_mul => I32Mul,
+    /// Creates an `i32.div_s` instruction.
+    i32_div_s => I32DivS,
+    /// Creates an `i32.div_u` instruction.
+    i32_div_u => I32DivU,
+    /// Creates an `i32.rem_s` instruction.
+    i32_rem_s => I32RemS,
+    /// Creates an `i32.rem_u` instruction.
+    i32_rem_u => I32RemU,
+    /// Creates an `i32.and` instruction.
+    i32_and => I32And,
+    /// Creates an `i32.or` instruction.
+    i32_or => I32Or,
+    /// Creates an `i32.xor` instruction.
+    i32_xor => I32Xor,
+    /// Creates an `i32.shl` instruction.
+    i32_shl => I32Shl,
+    /// Creates an `i32.shr_s` instruction.
+    i32_shr_s => I32ShrS,
+    /// Creates an `i32.shr_u` instruction.
+    i32_shr_u => I32ShrU,
+    /// Creates an `i32.rotl` instruction.
+    i32_rotl => I32Rotl,
+    /// Creates an `i32.rotr` instruction.
+    i32_rotr => I32Rotr,
+    /// Creates an `i64.add` instruction.
+    i64_add => I64Add,
+    /// Creates an `i64.sub` instruction.
+    i64_sub => I64Sub,
+    /// Creates an `i64.mul` instruction.
+    i64_mul => I64Mul,
+    /// Creates an `i64.div_s` instruction.
+    i64_div_s => I64DivS,
+    /// Creates an `i64.div_u` instruction.
+    i64_div_u => I64DivU,
+    /// Creates an `i64.rem_s` instruction.
+    i64_rem_s => I64RemS,
+    /// Creates an `i64.rem_u` instruction.
+    i64_rem_u => I64RemU,
+    /// Creates an `i64.and` instruction.
+    i64_and => I64And,
+    /// Creates an `i64.or` instruction.
+    i64_or => I64Or,
+    /// Creates an `i64.xor` instruction.
+    i64_xor => I64Xor,
+    /// Creates an `i64.shl` instruction.
+    i64_shl => I64Shl,
+    /// Creates an `i64.shr_s` instruction.
+    i64_shr_s => I64ShrS,
+    /// Creates an `i64.shr_u` instruction.
+    i64_shr_u => I64ShrU,
+    /// Creates an `i64.rotl` instruction.
+    i64_rotl => I64Rotl,
+    /// Creates an `i64.rotr` instruction.
+    i64_rotr => I64Rotr,
+
+    // Float arithmetic.
+    /// Creates an `f32.add` instruction.
+    f32_add => F32Add,
+    /// Creates an `f32.sub` instruction.
+    f32_sub => F32Sub,
+    /// Creates an `f32.mul` instruction.
+    f32_mul => F32Mul,
+    /// Creates an `f32.div` instruction.
+    f32_div => F32Div,
+    /// Creates an `f32.min` instruction.
+    f32_min => F32Min,
+    /// Creates an `f32.max` instruction.
+    f32_max => F32Max,
+    /// Creates an `f32.copysign` instruction.
+    f32_copysign => F32Copysign,
+    /// Creates an `f64.add` instruction.
+    f64_add => F64Add,
+    /// Creates an `f64.sub` instruction.
+    f64_sub => F64Sub,
+    /// Creates an `f64.mul` instruction.
+    f64_mul => F64Mul,
+    /// Creates an `f64.div` instruction.
+    f64_div => F64Div,
+    /// Creates an `f64.min` instruction.
+    f64_min => F64Min,
+    /// Creates an `f64.max` instruction.
+    f64_max => F64Max,
+    /// Creates an `f64.copysign` instruction.
+    f64_copysign => F64Copysign,
+}
+
+unops! {
+    // Integer wrapping and extension.
+    /// Creates an `i32.wrap_i64` instruction.
+    i32_wrap_i64 => I32WrapI64,
+    /// Creates an `i64.extend_i32_s` instruction.
+    i64_extend_i32_s => I64ExtendSI32,
+    /// Creates an `i64.extend_i32_u` instruction.
+    i64_extend_i32_u => I64ExtendUI32,
+    /// Creates an `i32.extend8_s` instruction.
+    i32_extend8_s => I32Extend8S,
+    /// Creates an `i32.extend16_s` instruction.
+    i32_extend16_s => I32Extend16S,
+    /// Creates an `i64.extend8_s` instruction.
+    i64_extend8_s => I64Extend8S,
+    /// Creates an `i64.extend16_s` instruction.
+    i64_extend16_s => I64Extend16S,
+    /// Creates an `i64.extend32_s` instruction.
+    i64_extend32_s => I64Extend32S,
+
+    // Truncation from floats to integers, which traps if the result doesn't
+    // fit.
+    /// Creates an `i32.trunc_f32_s` instruction.
+    i32_trunc_f32_s => I32TruncSF32,
+    /// Creates an `i32.trunc_f32_u` instruction.
+    i32_trunc_f32_u => I32TruncUF32,
+    /// Creates an `i32.trunc_f64_s` instruction.
+    i32_trunc_f64_s => I32TruncSF64,
+    /// Creates an `i32.trunc_f64_u` instruction.
+    i32_trunc_f64_u => I32TruncUF64,
+    /// Creates an `i64.trunc_f32_s` instruction.
+    i64_trunc_f32_s => I64TruncSF32,
+    /// Creates an `i64.trunc_f32_u` instruction.
+    i64_trunc_f32_u => I64TruncUF32,
+    /// Creates an `i64.trunc_f64_s` instruction.
+    i64_trunc_f64_s => I64TruncSF64,
+    /// Creates an `i64.trunc_f64_u` instruction.
+    i64_trunc_f64_u => I64TruncUF64,
+
+    // Saturating truncation from floats to integers, which never traps.
+    /// Creates an `i32.trunc_sat_f32_s` instruction.
+    i32_trunc_sat_f32_s => I32TruncSSatF32,
+    /// Creates an `i32.trunc_sat_f32_u` instruction.
+    i32_trunc_sat_f32_u => I32TruncUSatF32,
+    /// Creates an `i32.trunc_sat_f64_s` instruction.
+    i32_trunc_sat_f64_s => I32TruncSSatF64,
+    /// Creates an `i32.trunc_sat_f64_u` instruction.
+    i32_trunc_sat_f64_u => I32TruncUSatF64,
+    /// Creates an `i64.trunc_sat_f32_s` instruction.
+    i64_trunc_sat_f32_s => I64TruncSSatF32,
+    /// Creates an `i64.trunc_sat_f32_u` instruction.
+    i64_trunc_sat_f32_u => I64TruncUSatF32,
+    /// Creates an `i64.trunc_sat_f64_s` instruction.
+    i64_trunc_sat_f64_s => I64TruncSSatF64,
+    /// Creates an `i64.trunc_sat_f64_u` instruction.
+    i64_trunc_sat_f64_u => I64TruncUSatF64,
+
+    // Conversion from integers to floats.
+    /// Creates an `f32.convert_i32_s` instruction.
+    f32_convert_i32_s => F32ConvertSI32,
+    /// Creates an `f32.convert_i32_u` instruction.
+    f32_convert_i32_u => F32ConvertUI32,
+    /// Creates an `f32.convert_i64_s` instruction.
+    f32_convert_i64_s => F32ConvertSI64,
+    /// Creates an `f32.convert_i64_u` instruction.
+    f32_convert_i64_u => F32ConvertUI64,
+    /// Creates an `f64.convert_i32_s` instruction.
+    f64_convert_i32_s => F64ConvertSI32,
+    /// Creates an `f64.convert_i32_u` instruction.
+    f64_convert_i32_u => F64ConvertUI32,
+    /// Creates an `f64.convert_i64_s` instruction.
+    f64_convert_i64_s => F64ConvertSI64,
+    /// Creates an `f64.convert_i64_u` instruction.
+    f64_convert_i64_u => F64ConvertUI64,
+
+    // Conversion between float widths.
+    /// Creates an `f32.demote_f64` instruction.
+    f32_demote_f64 => F32DemoteF64,
+    /// Creates an `f64.promote_f32` instruction.
+    f64_promote_f32 => F64PromoteF32,
+
+    // Reinterpreting the bits of a value as another type.
+    /// Creates an `i32.reinterpret_f32` instruction.
+    i32_reinterpret_f32 => I32ReinterpretF32,
+    /// Creates an `i64.reinterpret_f64` instruction.
+    i64_reinterpret_f64 => I64ReinterpretF64,
+    /// Creates an `f32.reinterpret_i32` instruction.
+    f32_reinterpret_i32 => F32ReinterpretI32,
+    /// Creates an `f64.reinterpret_i64` instruction.
+    f64_reinterpret_i64 => F64ReinterpretI64,
+}
+
+unops! {
+    // SIMD splats, which fill every lane with the same scalar.
+    /// Creates an `i8x16.splat` instruction.
+    i8x16_splat => I8x16Splat,
+    /// Creates an `i16x8.splat` instruction.
+    i16x8_splat => I16x8Splat,
+    /// Creates an `i32x4.splat` instruction.
+    i32x4_splat => I32x4Splat,
+    /// Creates an `i64x2.splat` instruction.
+    i64x2_splat => I64x2Splat,
+    /// Creates an `f32x4.splat` instruction.
+    f32x4_splat => F32x4Splat,
+    /// Creates an `f64x2.splat` instruction.
+    f64x2_splat => F64x2Splat,
+
+    // SIMD bitwise not.
+    /// Creates a `v128.not` instruction.
+    v128_not => V128Not,
+
+    // SIMD lane-wise arithmetic.
+    /// Creates an `i8x16.abs` instruction.
+    i8x16_abs => I8x16Abs,
+    /// Creates an `i8x16.neg` instruction.
+    i8x16_neg => I8x16Neg,
+    /// Creates an `i16x8.abs` instruction.
+    i16x8_abs => I16x8Abs,
+    /// Creates an `i16x8.neg` instruction.
+    i16x8_neg => I16x8Neg,
+    /// Creates an `i32x4.abs` instruction.
+    i32x4_abs => I32x4Abs,
+    /// Creates an `i32x4.neg` instruction.
+    i32x4_neg => I32x4Neg,
+    /// Creates an `i64x2.abs` instruction.
+    i64x2_abs => I64x2Abs,
+    /// Creates an `i64x2.neg` instruction.
+    i64x2_neg => I64x2Neg,
+    /// Creates an `f32x4.abs` instruction.
+    f32x4_abs => F32x4Abs,
+    /// Creates an `f32x4.neg` instruction.
+    f32x4_neg => F32x4Neg,
+    /// Creates an `f32x4.sqrt` instruction.
+    f32x4_sqrt => F32x4Sqrt,
+    /// Creates an `f64x2.abs` instruction.
+    f64x2_abs => F64x2Abs,
+    /// Creates an `f64x2.neg` instruction.
+    f64x2_neg => F64x2Neg,
+    /// Creates an `f64x2.sqrt` instruction.
+    f64x2_sqrt => F64x2Sqrt,
+}
+
+binops! {
+    // SIMD lane-wise comparisons, which set each lane of the result to all ones
+    // if the comparison holds for that lane and all zeros otherwise.
+    /// Creates an `i8x16.eq` instruction.
+    i8x16_eq => I8x16Eq,
+    /// Creates an `i8x16.ne` instruction.
+    i8x16_ne => I8x16Ne,
+    /// Creates an `i8x16.lt_s` instruction.
+    i8x16_lt_s => I8x16LtS,
+    /// Creates an `i8x16.lt_u` instruction.
+    i8x16_lt_u => I8x16LtU,
+    /// Creates an `i8x16.gt_s` instruction.
+    i8x16_gt_s => I8x16GtS,
+    /// Creates an `i8x16.gt_u` instruction.
+    i8x16_gt_u => I8x16GtU,
+    /// Creates an `i8x16.le_s` instruction.
+    i8x16_le_s => I8x16LeS,
+    /// Creates an `i8x16.le_u` instruction.
+    i8x16_le_u => I8x16LeU,
+    /// Creates an `i8x16.ge_s` instruction.
+    i8x16_ge_s => I8x16GeS,
+    /// Creates an `i8x16.ge_u` instruction.
+    i8x16_ge_u => I8x16GeU,
+    /// Creates an `i16x8.eq` instruction.
+    i16x8_eq => I16x8Eq,
+    /// Creates an `i16x8.ne` instruction.
+    i16x8_ne => I16x8Ne,
+    /// Creates an `i16x8.lt_s` instruction.
+    i16x8_lt_s => I16x8LtS,
+    /// Creates an `i16x8.lt_u` instruction.
+    i16x8_lt_u => I16x8LtU,
+    /// Creates an `i16x8.gt_s` instruction.
+    i16x8_gt_s => I16x8GtS,
+    /// Creates an `i16x8.gt_u` instruction.
+    i16x8_gt_u => I16x8GtU,
+    /// Creates an `i16x8.le_s` instruction.
+    i16x8_le_s => I16x8LeS,
+    /// Creates an `i16x8.le_u` instruction.
+    i16x8_le_u => I16x8LeU,
+    /// Creates an `i16x8.ge_s` instruction.
+    i16x8_ge_s => I16x8GeS,
+    /// Creates an `i16x8.ge_u` instruction.
+    i16x8_ge_u => I16x8GeU,
+    /// Creates an `i32x4.eq` instruction.
+    i32x4_eq => I32x4Eq,
+    /// Creates an `i32x4.ne` instruction.
+    i32x4_ne => I32x4Ne,
+    /// Creates an `i32x4.lt_s` instruction.
+    i32x4_lt_s => I32x4LtS,
+    /// Creates an `i32x4.lt_u` instruction.
+    i32x4_lt_u => I32x4LtU,
+    /// Creates an `i32x4.gt_s` instruction.
+    i32x4_gt_s => I32x4GtS,
+    /// Creates an `i32x4.gt_u` instruction.
+    i32x4_gt_u => I32x4GtU,
+    /// Creates an `i32x4.le_s` instruction.
+    i32x4_le_s => I32x4LeS,
+    /// Creates an `i32x4.le_u` instruction.
+    i32x4_le_u => I32x4LeU,
+    /// Creates an `i32x4.ge_s` instruction.
+    i32x4_ge_s => I32x4GeS,
+    /// Creates an `i32x4.ge_u` instruction.
+    i32x4_ge_u => I32x4GeU,
+    /// Creates an `i64x2.eq` instruction.
+    i64x2_eq => I64x2Eq,
+    /// Creates an `i64x2.ne` instruction.
+    i64x2_ne => I64x2Ne,
+    /// Creates an `i64x2.lt_s` instruction.
+    i64x2_lt_s => I64x2LtS,
+    /// Creates an `i64x2.gt_s` instruction.
+    i64x2_gt_s => I64x2GtS,
+    /// Creates an `i64x2.le_s` instruction.
+    i64x2_le_s => I64x2LeS,
+    /// Creates an `i64x2.ge_s` instruction.
+    i64x2_ge_s => I64x2GeS,
+    /// Creates an `f32x4.eq` instruction.
+    f32x4_eq => F32x4Eq,
+    /// Creates an `f32x4.ne` instruction.
+    f32x4_ne => F32x4Ne,
+    /// Creates an `f32x4.lt` instruction.
+    f32x4_lt => F32x4Lt,
+    /// Creates an `f32x4.gt` instruction.
+    f32x4_gt => F32x4Gt,
+    /// Creates an `f32x4.le` instruction.
+    f32x4_le => F32x4Le,
+    /// Creates an `f32x4.ge` instruction.
+    f32x4_ge => F32x4Ge,
+    /// Creates an `f64x2.eq` instruction.
+    f64x2_eq => F64x2Eq,
+    /// Creates an `f64x2.ne` instruction.
+    f64x2_ne => F64x2Ne,
+    /// Creates an `f64x2.lt` instruction.
+    f64x2_lt => F64x2Lt,
+    /// Creates an `f64x2.gt` instruction.
+    f64x2_gt => F64x2Gt,
+    /// Creates an `f64x2.le` instruction.
+    f64x2_le => F64x2Le,
+    /// Creates an `f64x2.ge` instruction.
+    f64x2_ge => F64x2Ge,
+
+    // SIMD bitwise operations.
+    /// Creates a `v128.and` instruction.
+    v128_and => V128And,
+    /// Creates a `v128.or` instruction.
+    v128_or => V128Or,
+    /// Creates a `v128.xor` instruction.
+    v128_xor => V128Xor,
+    /// Creates a `v128.andnot` instruction.
+    v128_andnot => V128AndNot,
+
+    // SIMD lane-wise integer arithmetic.
+    /// Creates an `i8x16.add` instruction.
+    i8x16_add => I8x16Add,
+    /// Creates an `i8x16.add_sat_s` instruction.
+    i8x16_add_sat_s => I8x16AddSatS,
+    /// Creates an `i8x16.add_sat_u` instruction.
+    i8x16_add_sat_u => I8x16AddSatU,
+    /// Creates an `i8x16.sub` instruction.
+    i8x16_sub => I8x16Sub,
+    /// Creates an `i8x16.sub_sat_s` instruction.
+    i8x16_sub_sat_s => I8x16SubSatS,
+    /// Creates an `i8x16.sub_sat_u` instruction.
+    i8x16_sub_sat_u => I8x16SubSatU,
+    /// Creates an `i8x16.min_s` instruction.
+    i8x16_min_s => I8x16MinS,
+    /// Creates an `i8x16.min_u` instruction.
+    i8x16_min_u => I8x16MinU,
+    /// Creates an `i8x16.max_s` instruction.
+    i8x16_max_s => I8x16MaxS,
+    /// Creates an `i8x16.max_u` instruction.
+    i8x16_max_u => I8x16MaxU,
+    /// Creates an `i16x8.add` instruction.
+    i16x8_add => I16x8Add,
+    /// Creates an `i16x8.add_sat_s` instruction.
+    i16x8_add_sat_s => I16x8AddSatS,
+    /// Creates an `i16x8.add_sat_u` instruction.
+    i16x8_add_sat_u => I16x8AddSatU,
+    /// Creates an `i16x8.sub` instruction.
+    i16x8_sub => I16x8Sub,
+    /// Creates an `i16x8.sub_sat_s` instruction.
+    i16x8_sub_sat_s => I16x8SubSatS,
+    /// Creates an `i16x8.sub_sat_u` instruction.
+    i16x8_sub_sat_u => I16x8SubSatU,
+    /// Creates an `i16x8.mul` instruction.
+    i16x8_mul => I16x8Mul,
+    /// Creates an `i16x8.min_s` instruction.
+    i16x8_min_s => I16x8MinS,
+    /// Creates an `i16x8.min_u` instruction.
+    i16x8_min_u => I16x8MinU,
+    /// Creates an `i16x8.max_s` instruction.
+    i16x8_max_s => I16x8MaxS,
+    /// Creates an `i16x8.max_u` instruction.
+    i16x8_max_u => I16x8MaxU,
+    /// Creates an `i32x4.add` instruction.
+    i32x4_add => I32x4Add,
+    /// Creates an `i32x4.sub` instruction.
+    i32x4_sub => I32x4Sub,
+    /// Creates an `i32x4.mul` instruction.
+    i32x4_mul => I32x4Mul,
+    /// Creates an `i32x4.min_s` instruction.
+    i32x4_min_s => I32x4MinS,
+    /// Creates an `i32x4.min_u` instruction.
+    i32x4_min_u => I32x4MinU,
+    /// Creates an `i32x4.max_s` instruction.
+    i32x4_max_s => I32x4MaxS,
+    /// Creates an `i32x4.max_u` instruction.
+    i32x4_max_u => I32x4MaxU,
+    /// Creates an `i64x2.add` instruction.
+    i64x2_add => I64x2Add,
+    /// Creates an `i64x2.sub` instruction.
+    i64x2_sub => I64x2Sub,
+    /// Creates an `i64x2.mul` instruction.
+    i64x2_mul => I64x2Mul,
+
+    // SIMD lane-wise float arithmetic.
+    /// Creates an `f32x4.add` instruction.
+    f32x4_add => F32x4Add,
+    /// Creates an `f32x4.sub` instruction.
+    f32x4_sub => F32x4Sub,
+    /// Creates an `f32x4.mul` instruction.
+    f32x4_mul => F32x4Mul,
+    /// Creates an `f32x4.div` instruction.
+    f32x4_div => F32x4Div,
+    /// Creates an `f32x4.min` instruction.
+    f32x4_min => F32x4Min,
+    /// Creates an `f32x4.max` instruction.
+    f32x4_max => F32x4Max,
+    /// Creates an `f32x4.pmin` instruction.
+    f32x4_pmin => F32x4PMin,
+    /// Creates an `f32x4.pmax` instruction.
+    f32x4_pmax => F32x4PMax,
+    /// Creates an `f64x2.add` instruction.
+    f64x2_add => F64x2Add,
+    /// Creates an `f64x2.sub` instruction.
+    f64x2_sub => F64x2Sub,
+    /// Creates an `f64x2.mul` instruction.
+    f64x2_mul => F64x2Mul,
+    /// Creates an `f64x2.div` instruction.
+    f64x2_div => F64x2Div,
+    /// Creates an `f64x2.min` instruction.
+    f64x2_min => F64x2Min,
+    /// Creates an `f64x2.max` instruction.
+    f64x2_max => F64x2Max,
+    /// Creates an `f64x2.pmin` instruction.
+    f64x2_pmin => F64x2PMin,
+    /// Creates an `f64x2.pmax` instruction.
+    f64x2_pmax => F64x2PMax,
+}
+
+/// Defines a builder method for each of the given lane instructions, which
+/// takes the index of the lane to access and checks that it is in bounds for
+/// the instruction's number of lanes.
+macro_rules! lane_ops {
+    ($($(#[$attr:meta])* $method:ident => $kind:ident $ty:ident::$op:ident, $lanes:expr;)*) => {
+        impl InstrSeqBuilder<'_> {
+            $(
+                $(#[$attr])*
+                ///
+                /// # Panics
+                ///
+                /// Panics if `lane` is out of bounds for the number of lanes.
+                #[inline]
+                pub fn $method(&mut self, lane: u8) -> &mut Self {
+                    assert!(
+                        lane < $lanes,
+                        "lane index {} is out of bounds for {} lanes",
+                        lane,
+                        $lanes
+                    );
+                    self.$kind($ty::$op { idx: lane })
+                }
+            )*
+        }
+    };
+}
+
+lane_ops! {
+    /// Creates an `i8x16.extract_lane_s` instruction accessing lane `lane`.
+    i8x16_extract_lane_s => unop UnaryOp::I8x16ExtractLaneS, 16;
+    /// Creates an `i8x16.extract_lane_u` instruction accessing lane `lane`.
+    i8x16_extract_lane_u => unop UnaryOp::I8x16ExtractLaneU, 16;
+    /// Creates an `i16x8.extract_lane_s` instruction accessing lane `lane`.
+    i16x8_extract_lane_s => unop UnaryOp::I16x8ExtractLaneS, 8;
+    /// Creates an `i16x8.extract_lane_u` instruction accessing lane `lane`.
+    i16x8_extract_lane_u => unop UnaryOp::I16x8ExtractLaneU, 8;
+    /// Creates an `i32x4.extract_lane` instruction accessing lane `lane`.
+    i32x4_extract_lane => unop UnaryOp::I32x4ExtractLane, 4;
+    /// Creates an `i64x2.extract_lane` instruction accessing lane `lane`.
+    i64x2_extract_lane => unop UnaryOp::I64x2ExtractLane, 2;
+    /// Creates an `f32x4.extract_lane` instruction accessing lane `lane`.
+    f32x4_extract_lane => unop UnaryOp::F32x4ExtractLane, 4;
+    /// Creates an `f64x2.extract_lane` instruction accessing lane `lane`.
+    f64x2_extract_lane => unop UnaryOp::F64x2ExtractLane, 2;
+    /// Creates an `i8x16.replace_lane` instruction accessing lane `lane`.
+    i8x16_replace_lane => binop BinaryOp::I8x16ReplaceLane, 16;
+    /// Creates an `i16x8.replace_lane` instruction accessing lane `lane`.
+    i16x8_replace_lane => binop BinaryOp::I16x8ReplaceLane, 8;
+    /// Creates an `i32x4.replace_lane` instruction accessing lane `lane`.
+    i32x4_replace_lane => binop BinaryOp::I32x4ReplaceLane, 4;
+    /// Creates an `i64x2.replace_lane` instruction accessing lane `lane`.
+    i64x2_replace_lane => binop BinaryOp::I64x2ReplaceLane, 2;
+    /// Creates an `f32x4.replace_lane` instruction accessing lane `lane`.
+    f32x4_replace_lane => binop BinaryOp::F32x4ReplaceLane, 4;
+    /// Creates an `f64x2.replace_lane` instruction accessing lane `lane`.
+    f64x2_replace_lane => binop BinaryOp::F64x2ReplaceLane, 2;
+}
+
+fn checked_mem_arg(width: u32, atomic: bool, offset: u32, align: Option<u32>) -> MemArg {
+    let align = align.unwrap_or(width);
+    assert!(
+        align.is_power_of_two(),
+        "alignment must be a power of two, got {}",
+        align
+    );
+    assert!(
+        align <= width,
+        "alignment of {} is greater than the access width of {}",
+        align,
+        width
+    );
+    assert!(
+        !atomic || align == width,
+        "atomic accesses must be naturally aligned"
+    );
+    MemArg { align, offset }
 }
 
 impl Deref for InstrSeqBuilder<'_> {
@@ -153,7 +153,7 @@ impl Default for InstrLocId {
 }
 
 /// A sequence of instructions.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct InstrSeq {
     id: InstrSeqId,
 
@@ -589,6 +589,13 @@ pub enum Instr {
 pub type ShuffleIndices = [u8; 16];
 
 /// Constant values that can show up in WebAssembly
+///
+/// Floats are kept as Rust floats rather than as their bits, so that existing
+/// code matching on `Value::F32(x)` keeps working. Their exact bit patterns,
+/// including signaling NaN payloads, are still preserved: they are converted
+/// with `from_bits`/`to_bits` when parsing and emitting, and never go through
+/// any arithmetic in between. Convert to a `ConstValue` to compare floats by
+/// their bits.
 #[derive(Debug, Clone, Copy)]
 pub enum Value {
     /// A constant 32-bit integer
@@ -604,6 +611,16 @@ pub enum Value {
 }
 
 impl Value {
+    /// Replace NaN floats with the canonical NaN of their type, leaving all
+    /// other values untouched.
+    pub(crate) fn canonicalize_nan(self) -> Value {
+        match self {
+            Value::F32(n) if n.is_nan() => Value::F32(f32::from_bits(0x7fc0_0000)),
+            Value::F64(n) if n.is_nan() => Value::F64(f64::from_bits(0x7ff8_0000_0000_0000)),
+            other => other,
+        }
+    }
+
     pub(crate) fn emit(&self, encoder: &mut Encoder) {
         match *self {
             Value::I32(n) => {
@@ -644,6 +661,63 @@ impl fmt::Display for Value {
     }
 }
 
+/// A constant value, as produced by any of the `*.const` instructions.
+///
+/// This is like `Value`, except that floats are stored as their raw bits, so
+/// that NaN payloads are preserved exactly and values can be compared and
+/// hashed. Use `Instr::as_const` and `Instr::const_` to convert between this
+/// and `Const` instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstValue {
+    /// An `i32.const`.
+    I32(i32),
+    /// An `i64.const`.
+    I64(i64),
+    /// An `f32.const`, as the bits of the float.
+    F32(u32),
+    /// An `f64.const`, as the bits of the float.
+    F64(u64),
+    /// A `v128.const`.
+    V128(u128),
+}
+
+impl ConstValue {
+    /// The type of this value.
+    pub fn ty(&self) -> ValType {
+        match self {
+            ConstValue::I32(_) => ValType::I32,
+            ConstValue::I64(_) => ValType::I64,
+            ConstValue::F32(_) => ValType::F32,
+            ConstValue::F64(_) => ValType::F64,
+            ConstValue::V128(_) => ValType::V128,
+        }
+    }
+}
+
+impl From<Value> for ConstValue {
+    fn from(value: Value) -> ConstValue {
+        match value {
+            Value::I32(n) => ConstValue::I32(n),
+            Value::I64(n) => ConstValue::I64(n),
+            Value::F32(n) => ConstValue::F32(n.to_bits()),
+            Value::F64(n) => ConstValue::F64(n.to_bits()),
+            Value::V128(n) => ConstValue::V128(n),
+        }
+    }
+}
+
+impl From<ConstValue> for Value {
+    fn from(value: ConstValue) -> Value {
+        match value {
+            ConstValue::I32(n) => Value::I32(n),
+            ConstValue::I64(n) => Value::I64(n),
+            ConstValue::F32(n) => Value::F32(f32::from_bits(n)),
+            ConstValue::F64(n) => Value::F64(f64::from_bits(n)),
+            ConstValue::V128(n) => Value::V128(n),
+        }
+    }
+}
+
 /// Possible binary operations in wasm
 #[allow(missing_docs)]
 #[derive(Copy, Clone, Debug)]
@@ -1227,7 +1301,49 @@ impl AtomicWidth {
     }
 }
 
+impl BrTable {
+    /// Iterate over all of this `br_table`'s targets: first the entries of
+    /// `blocks`, in order, followed by `default`.
+    ///
+    /// Note that `default` is *not* an entry of `blocks`: a `br_table` with
+    /// `n` blocks branches to `blocks[i]` when `which` is `i < n`, and to
+    /// `default` for any `which >= n`. So this yields `blocks.len() + 1`
+    /// targets, and the same target may show up more than once.
+    pub fn targets(&self) -> impl Iterator<Item = InstrSeqId> + '_ {
+        self.blocks.iter().cloned().chain(Some(self.default))
+    }
+
+    /// Rewrite each of this `br_table`'s targets, including `default`, with the
+    /// given function.
+    ///
+    /// This is useful when blocks are duplicated or removed. The function is
+    /// called once per entry in `blocks`, in order, and then once for
+    /// `default`.
+    pub fn remap_targets(&mut self, mut f: impl FnMut(InstrSeqId) -> InstrSeqId) {
+        for block in self.blocks.iter_mut() {
+            *block = f(*block);
+        }
+        self.default = f(self.default);
+    }
+}
+
 impl Instr {
+    /// Construct a `*.const` instruction for the given value.
+    pub fn const_(value: ConstValue) -> Instr {
+        Const {
+            value: value.into(),
+        }
+        .into()
+    }
+
+    /// If this is a `*.const` instruction, get its value.
+    pub fn as_const(&self) -> Option<ConstValue> {
+        match self {
+            Instr::Const(Const { value }) => Some((*value).into()),
+            _ => None,
+        }
+    }
+
     /// Are any instructions that follow this instruction's instruction (within
     /// the current block) unreachable?
     ///
@@ -1286,6 +1402,115 @@ impl Instr {
             | Instr::Drop(..) => false,
         }
     }
+
+    /// Can executing this instruction trap?
+    ///
+    /// This is conservative: it returns `true` for calls, for anything that
+    /// accesses memory or a table, for integer division and remainder, and for
+    /// non-saturating float-to-int conversions. Since only this instruction is
+    /// looked at, and not any instructions nested within it, `block`, `loop`
+    /// and `if` also return `true`.
+    pub fn may_trap(&self) -> bool {
+        match self {
+            Instr::Binop(Binop { op }) => matches!(
+                op,
+                BinaryOp::I32DivS
+                    | BinaryOp::I32DivU
+                    | BinaryOp::I32RemS
+                    | BinaryOp::I32RemU
+                    | BinaryOp::I64DivS
+                    | BinaryOp::I64DivU
+                    | BinaryOp::I64RemS
+                    | BinaryOp::I64RemU
+            ),
+            Instr::Unop(Unop { op }) => matches!(
+                op,
+                UnaryOp::I32TruncSF32
+                    | UnaryOp::I32TruncUF32
+                    | UnaryOp::I32TruncSF64
+                    | UnaryOp::I32TruncUF64
+                    | UnaryOp::I64TruncSF32
+                    | UnaryOp::I64TruncUF32
+                    | UnaryOp::I64TruncSF64
+                    | UnaryOp::I64TruncUF64
+            ),
+
+            Instr::Block(..)
+            | Instr::Loop(..)
+            | Instr::IfElse(..)
+            | Instr::Unreachable(..)
+            | Instr::Call(..)
+            | Instr::CallIndirect(..)
+            | Instr::MemoryInit(..)
+            | Instr::MemoryCopy(..)
+            | Instr::MemoryFill(..)
+            | Instr::Load(..)
+            | Instr::Store(..)
+            | Instr::AtomicRmw(..)
+            | Instr::Cmpxchg(..)
+            | Instr::AtomicNotify(..)
+            | Instr::AtomicWait(..)
+            | Instr::TableGet(..)
+            | Instr::TableSet(..)
+            | Instr::TableFill(..)
+            | Instr::LoadSimd(..)
+            | Instr::TableInit(..)
+            | Instr::TableCopy(..) => true,
+
+            Instr::LocalGet(..)
+            | Instr::LocalSet(..)
+            | Instr::LocalTee(..)
+            | Instr::GlobalGet(..)
+            | Instr::GlobalSet(..)
+            | Instr::Const(..)
+            | Instr::Select(..)
+            | Instr::Br(..)
+            | Instr::BrIf(..)
+            | Instr::BrTable(..)
+            | Instr::Return(..)
+            | Instr::Drop(..)
+            | Instr::MemorySize(..)
+            | Instr::MemoryGrow(..)
+            | Instr::DataDrop(..)
+            | Instr::TableGrow(..)
+            | Instr::TableSize(..)
+            | Instr::RefNull(..)
+            | Instr::RefIsNull(..)
+            | Instr::RefFunc(..)
+            | Instr::V128Bitselect(..)
+            | Instr::I8x16Swizzle(..)
+            | Instr::I8x16Shuffle(..)
+            | Instr::AtomicFence(..)
+            | Instr::ElemDrop(..) => false,
+        }
+    }
+
+    /// Is this instruction free of side effects?
+    ///
+    /// A pure instruction only pops its operands and pushes its results: it
+    /// can't trap, branch, call a function, or write to a local, global,
+    /// memory or table. It may still read from locals and globals, so pure
+    /// instructions can be reordered with each other, and evaluated or not,
+    /// without changing what a function does.
+    pub fn is_pure(&self) -> bool {
+        match self {
+            Instr::Binop(..) | Instr::Unop(..) => !self.may_trap(),
+            Instr::Const(..)
+            | Instr::LocalGet(..)
+            | Instr::GlobalGet(..)
+            | Instr::Select(..)
+            | Instr::Drop(..)
+            | Instr::MemorySize(..)
+            | Instr::TableSize(..)
+            | Instr::RefNull(..)
+            | Instr::RefIsNull(..)
+            | Instr::RefFunc(..)
+            | Instr::V128Bitselect(..)
+            | Instr::I8x16Swizzle(..)
+            | Instr::I8x16Shuffle(..) => true,
+            _ => false,
+        }
+    }
 }
 
 /// Anything that can be visited by a `Visitor`.
@@ -1,6 +1,20 @@
 //! Implementations of various IR traversals.
 
 use crate::ir::*;
+use crate::FunctionBuilder;
+use std::mem;
+
+/// What a traversal should do with an instruction's children, as returned by
+/// `VisitorMut::enter_instr_mut`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisitAction {
+    /// Visit the instruction, and then the instruction sequences nested
+    /// inside it.
+    Continue,
+    /// Visit the instruction, but skip the instruction sequences nested inside
+    /// it.
+    SkipChildren,
+}
 
 /// Perform an intra-procedural, depth-first, in-order traversal of the IR.
 ///
@@ -123,6 +137,60 @@ pub fn dfs_in_order<'instr>(
     }
 }
 
+/// Get the ids of all the instruction sequences in `func`, in the order that
+/// `dfs_in_order` enters them.
+///
+/// Passes that rewrite sequences one at a time can collect them up front with
+/// this, and then mutate the function as they go.
+pub(crate) fn instr_seq_ids(func: &LocalFunction) -> Vec<InstrSeqId> {
+    #[derive(Default)]
+    struct Seqs(Vec<InstrSeqId>);
+
+    impl<'instr> Visitor<'instr> for Seqs {
+        fn start_instr_seq(&mut self, seq: &'instr InstrSeq) {
+            self.0.push(seq.id());
+        }
+    }
+
+    let mut seqs = Seqs::default();
+    dfs_in_order(&mut seqs, func, func.entry_block());
+    seqs.0
+}
+
+/// Keeps track of where a `dfs_in_order` visitor is in a function: the
+/// instruction sequences it is inside of, and the index of the next
+/// instruction in each of them.
+///
+/// Call `start_instr_seq` and `end_instr_seq` from the visitor's methods of the
+/// same name, and `next_instr` from `visit_instr`.
+#[derive(Debug, Default)]
+pub(crate) struct InstrPositions {
+    stack: Vec<(InstrSeqId, usize)>,
+}
+
+impl InstrPositions {
+    pub(crate) fn start_instr_seq(&mut self, seq: &InstrSeq) {
+        self.stack.push((seq.id(), 0));
+    }
+
+    pub(crate) fn end_instr_seq(&mut self) {
+        self.stack.pop();
+    }
+
+    /// Get the position of the instruction being visited, as its sequence and
+    /// its index within it, and move on to the next one.
+    pub(crate) fn next_instr(&mut self) -> (InstrSeqId, usize) {
+        let top = self.stack.last_mut().unwrap();
+        let position = *top;
+        top.1 += 1;
+        position
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+}
+
 /// Perform an intra-procedural, depth-first, pre-order, mutable traversal of
 /// the IR.
 ///
@@ -193,9 +261,14 @@ pub fn dfs_pre_order_mut(
         seq.visit_mut(visitor);
 
         for (instr, loc) in &mut seq.instrs {
+            let action = visitor.enter_instr_mut(instr, loc);
             visitor.visit_instr_mut(instr, loc);
             instr.visit_mut(visitor);
 
+            if action == VisitAction::SkipChildren {
+                continue;
+            }
+
             match instr {
                 Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => {
                     stack.push(*seq);
@@ -217,6 +290,85 @@ pub fn dfs_pre_order_mut(
     }
 }
 
+/// Perform a pre-order traversal like `dfs_pre_order_mut`, letting `expand`
+/// replace each instruction with any number of instructions.
+///
+/// `expand` is called on each instruction in turn. Returning `None` keeps the
+/// instruction as it is, and the traversal continues into any instruction
+/// sequences nested inside it. Returning `Some(instrs)` replaces the
+/// instruction with `instrs`, which may be empty to delete it. Replacement
+/// instructions are not traversed themselves, so they may include a copy of
+/// the instruction being replaced, such as when wrapping it in prologue and
+/// epilogue code.
+///
+/// `expand` also gets the function's `FunctionBuilder`, for creating the
+/// instruction sequences of any blocks among the replacement instructions. It
+/// must not touch the sequence currently being expanded, which is temporarily
+/// empty and will be overwritten.
+///
+/// Every replacement instruction is given the `InstrLocId` of the instruction
+/// it replaces, so that they all map back to where that instruction was in
+/// the original wasm.
+///
+/// # Example
+///
+/// ```no_run
+/// use walrus::LocalFunction;
+/// use walrus::ir::*;
+///
+/// # let get_my_function = || unimplemented!();
+/// # let helper = unimplemented!();
+/// let my_func: &mut LocalFunction = get_my_function();
+///
+/// // Call `helper` before every `memory.grow`.
+/// let entry = my_func.entry_block();
+/// dfs_pre_order_expand(my_func, entry, |_, instr| {
+///     if instr.is_memory_grow() {
+///         Some(vec![Call { func: helper }.into(), instr.clone()])
+///     } else {
+///         None
+///     }
+/// });
+/// ```
+pub fn dfs_pre_order_expand(
+    func: &mut LocalFunction,
+    start: InstrSeqId,
+    mut expand: impl FnMut(&mut FunctionBuilder, &Instr) -> Option<Vec<Instr>>,
+) {
+    let mut stack = vec![start];
+
+    while let Some(seq_id) = stack.pop() {
+        let instrs = mem::replace(&mut func.block_mut(seq_id).instrs, Vec::new());
+        let mut expanded = Vec::with_capacity(instrs.len());
+
+        for (instr, loc) in instrs {
+            if let Some(replacement) = expand(func.builder_mut(), &instr) {
+                expanded.extend(replacement.into_iter().map(|instr| (instr, loc)));
+                continue;
+            }
+
+            match &instr {
+                Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => {
+                    stack.push(*seq);
+                }
+
+                Instr::IfElse(IfElse {
+                    consequent,
+                    alternative,
+                }) => {
+                    stack.push(*alternative);
+                    stack.push(*consequent);
+                }
+
+                _ => {}
+            }
+            expanded.push((instr, loc));
+        }
+
+        func.block_mut(seq_id).instrs = expanded;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -382,4 +534,83 @@ mod tests {
             expected.iter().map(|s| s.to_string()).collect::<Vec<_>>()
         );
     }
+
+    #[test]
+    fn dfs_pre_order_mut_skip_children() {
+        struct SkipIfElse(TestVisitor);
+
+        impl VisitorMut for SkipIfElse {
+            fn enter_instr_mut(&mut self, instr: &mut Instr, _: &mut InstrLocId) -> VisitAction {
+                if instr.is_if_else() {
+                    VisitAction::SkipChildren
+                } else {
+                    VisitAction::Continue
+                }
+            }
+
+            fn start_instr_seq_mut(&mut self, seq: &mut InstrSeq) {
+                self.0.start_instr_seq_mut(seq);
+            }
+
+            fn end_instr_seq_mut(&mut self, seq: &mut InstrSeq) {
+                self.0.end_instr_seq_mut(seq);
+            }
+
+            fn visit_const_mut(&mut self, c: &mut Const) {
+                self.0.visit_const_mut(c);
+            }
+
+            fn visit_if_else_mut(&mut self, e: &mut IfElse) {
+                self.0.visit_if_else_mut(e);
+            }
+        }
+
+        let mut module = crate::Module::default();
+        let func = make_test_func(&mut module);
+
+        let mut visitor = SkipIfElse(TestVisitor::default());
+        crate::ir::dfs_pre_order_mut(&mut visitor, func, func.entry_block());
+
+        let expected = [
+            "start", "1", "6", "end", "start", "2", "if-else", "5", "end",
+        ];
+        assert_eq!(
+            visitor.0.visits,
+            expected.iter().map(|s| s.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn dfs_pre_order_expand() {
+        let mut module = crate::Module::default();
+        let func = make_test_func(&mut module);
+
+        // Wrap each even constant in a pair of other constants.
+        let entry = func.entry_block();
+        crate::ir::dfs_pre_order_expand(func, entry, |_, instr| match instr {
+            Instr::Const(Const {
+                value: Value::I32(x),
+            }) if x % 2 == 0 => Some(vec![
+                Instr::const_(ConstValue::I32(x * 10)),
+                Instr::Drop(Drop {}),
+                instr.clone(),
+                Instr::const_(ConstValue::I32(x * 10 + 1)),
+                Instr::Drop(Drop {}),
+            ]),
+            _ => None,
+        });
+
+        let mut visitor = TestVisitor::default();
+        crate::ir::dfs_in_order(&mut visitor, func, entry);
+
+        let expected = [
+            "start", "1", "drop", "block", "start", "20", "drop", "2", "21", "drop", "drop",
+            "if-else", "start", "3", "drop", "end", "start", "40", "drop", "4", "41", "drop",
+            "drop", "end", "5", "drop", "end", "60", "drop", "6", "61", "drop", "drop", "end",
+        ];
+        assert_eq!(
+            visitor.visits,
+            expected.iter().map(|s| s.to_string()).collect::<Vec<_>>()
+        );
+    }
 }
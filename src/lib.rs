@@ -33,7 +33,7 @@ mod tombstone_arena;
 mod ty;
 
 pub use crate::emit::IdsToIndices;
-pub use crate::error::{ErrorKind, Result};
+pub use crate::error::{ErrorKind, Limit, ParseError, Result, ValidationError};
 pub use crate::function_builder::{FunctionBuilder, InstrSeqBuilder};
 pub use crate::init_expr::InitExpr;
 pub use crate::ir::{Local, LocalId};
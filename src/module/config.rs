@@ -1,9 +1,10 @@
-use crate::error::Result;
+use crate::error::{ErrorKind, Limit, ParseError, Result};
 use crate::ir::InstrLocId;
-use crate::module::Module;
+use crate::module::{Export, Module};
 use crate::parse::IndicesToIds;
 use std::fmt;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Configuration for a `Module` which currently affects parsing.
 #[derive(Default)]
@@ -15,9 +16,32 @@ pub struct ModuleConfig {
     pub(crate) skip_producers_section: bool,
     pub(crate) skip_name_section: bool,
     pub(crate) preserve_code_transform: bool,
+    pub(crate) preserve_function_order: bool,
+    pub(crate) canonicalize_nans: bool,
+    pub(crate) lower_tail_calls: bool,
+    pub(crate) stamp_provenance: bool,
+    pub(crate) align_output: usize,
+    pub(crate) max_function_body_size: Option<usize>,
+    pub(crate) strict_data_bounds: bool,
+    pub(crate) strict_atomics: bool,
+    pub(crate) limits: Limits,
     pub(crate) on_parse:
         Option<Box<dyn Fn(&mut Module, &IndicesToIds) -> Result<()> + Sync + Send + 'static>>,
     pub(crate) on_instr_loc: Option<Box<dyn Fn(&usize) -> InstrLocId + Sync + Send + 'static>>,
+    pub(crate) on_raw_section:
+        Option<Box<dyn Fn(&mut Module, u8, &[u8]) -> Result<()> + Sync + Send + 'static>>,
+    pub(crate) export_is_root: Option<Arc<dyn Fn(&Export) -> bool + Sync + Send + 'static>>,
+}
+
+/// The resource limits to check while parsing; `None` means unlimited.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Limits {
+    module_size: Option<u64>,
+    types: Option<u64>,
+    functions: Option<u64>,
+    locals_per_function: Option<u64>,
+    memory_pages: Option<u64>,
+    table_size: Option<u64>,
 }
 
 impl Clone for ModuleConfig {
@@ -32,10 +56,23 @@ impl Clone for ModuleConfig {
             skip_producers_section: self.skip_producers_section,
             skip_name_section: self.skip_name_section,
             preserve_code_transform: self.preserve_code_transform,
+            preserve_function_order: self.preserve_function_order,
+            canonicalize_nans: self.canonicalize_nans,
+            lower_tail_calls: self.lower_tail_calls,
+            stamp_provenance: self.stamp_provenance,
+            align_output: self.align_output,
+            max_function_body_size: self.max_function_body_size,
+            strict_data_bounds: self.strict_data_bounds,
+            strict_atomics: self.strict_atomics,
+            limits: self.limits.clone(),
 
             // ... and this is left empty.
             on_parse: None,
             on_instr_loc: None,
+            on_raw_section: None,
+            // Unlike the parsing hooks, this one is needed after parsing,
+            // since the parsed module keeps a clone of its config for GC.
+            export_is_root: self.export_is_root.clone(),
         }
     }
 }
@@ -52,8 +89,19 @@ impl fmt::Debug for ModuleConfig {
             ref skip_producers_section,
             ref skip_name_section,
             ref preserve_code_transform,
+            ref preserve_function_order,
+            ref canonicalize_nans,
+            ref lower_tail_calls,
+            ref stamp_provenance,
+            ref align_output,
+            ref max_function_body_size,
+            ref strict_data_bounds,
+            ref strict_atomics,
+            ref limits,
             ref on_parse,
             ref on_instr_loc,
+            ref on_raw_section,
+            ref export_is_root,
         } = self;
 
         f.debug_struct("ModuleConfig")
@@ -67,8 +115,19 @@ impl fmt::Debug for ModuleConfig {
             .field("skip_producers_section", skip_producers_section)
             .field("skip_name_section", skip_name_section)
             .field("preserve_code_transform", preserve_code_transform)
+            .field("preserve_function_order", preserve_function_order)
+            .field("canonicalize_nans", canonicalize_nans)
+            .field("lower_tail_calls", lower_tail_calls)
+            .field("stamp_provenance", stamp_provenance)
+            .field("align_output", align_output)
+            .field("max_function_body_size", max_function_body_size)
+            .field("strict_data_bounds", strict_data_bounds)
+            .field("strict_atomics", strict_atomics)
+            .field("limits", limits)
             .field("on_parse", &on_parse.as_ref().map(|_| ".."))
             .field("on_instr_loc", &on_instr_loc.as_ref().map(|_| ".."))
+            .field("on_raw_section", &on_raw_section.as_ref().map(|_| ".."))
+            .field("export_is_root", &export_is_root.as_ref().map(|_| ".."))
             .finish()
     }
 }
@@ -143,6 +202,163 @@ impl ModuleConfig {
         self
     }
 
+    /// Indicates whether emitted modules are always stamped with a
+    /// `processed-by: walrus <version>` entry in their "producers" custom
+    /// section, for auditing which tools processed a binary.
+    ///
+    /// The stamp is added even if the full producers section is disabled with
+    /// `generate_producers_section(false)`, in which case the emitted producers
+    /// section contains only the stamp. Stamping is idempotent: re-processing
+    /// a stamped module replaces the existing walrus entry rather than adding
+    /// another one.
+    ///
+    /// By default this flag is `false`.
+    pub fn stamp_provenance(&mut self, stamp: bool) -> &mut ModuleConfig {
+        self.stamp_provenance = stamp;
+        self
+    }
+
+    /// Pad emitted modules so that their length is a multiple of `align` bytes.
+    ///
+    /// The padding is a trailing custom section named "padding" whose payload
+    /// is all zeros, so the module remains valid. An `align` of 1 disables
+    /// padding.
+    ///
+    /// By default modules are not padded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is zero.
+    pub fn align_output(&mut self, align: usize) -> &mut ModuleConfig {
+        assert!(align > 0, "output alignment must be greater than zero");
+        self.align_output = align;
+        self
+    }
+
+    /// Sets the largest function body, in bytes, that `Module::validate`
+    /// accepts.
+    ///
+    /// Engines refuse to compile function bodies past a certain size, and
+    /// unlike other validation errors that only shows up once the module is
+    /// loaded. `Module::validate` fails with an `ErrorKind::FunctionTooLarge`
+    /// error naming the first function whose encoded body is larger than this.
+    ///
+    /// By default this is 7,654,321 bytes, the limit shared by the major web
+    /// engines.
+    pub fn max_function_body_size(&mut self, bytes: usize) -> &mut ModuleConfig {
+        self.max_function_body_size = Some(bytes);
+        self
+    }
+
+    /// The configured `max_function_body_size`, or its default.
+    pub(crate) fn function_body_size_limit(&self) -> usize {
+        self.max_function_body_size.unwrap_or(7_654_321)
+    }
+
+    /// Sets whether `Module::validate` rejects active data segments that
+    /// extend past the initial size of their memory.
+    ///
+    /// Such segments are valid wasm, but instantiating the module fails
+    /// because the data doesn't fit in the memory, so they are almost
+    /// certainly a bug. `Module::emit_wasm` always logs a warning about them;
+    /// with this set, `Module::validate` fails instead. Imported memories are
+    /// never checked, since the memory provided at instantiation may be
+    /// larger than the import's minimum size.
+    ///
+    /// By default this is `false`.
+    pub fn strict_data_bounds(&mut self, strict: bool) -> &mut ModuleConfig {
+        self.strict_data_bounds = strict;
+        self
+    }
+
+    /// Sets whether `Module::validate` rejects atomic accesses to memories
+    /// that aren't shared.
+    ///
+    /// Such accesses are valid wasm, and all of them except
+    /// `memory.atomic.wait` behave just like they would on a shared memory;
+    /// `memory.atomic.wait` always traps on an unshared memory. Code meant to
+    /// run on multiple threads probably wants its memory to be shared though,
+    /// so with this set, `Module::validate` fails if any atomic instruction
+    /// accesses an unshared memory.
+    ///
+    /// By default this is `false`.
+    pub fn strict_atomics(&mut self, strict: bool) -> &mut ModuleConfig {
+        self.strict_atomics = strict;
+        self
+    }
+
+    /// Reject modules larger than `bytes` bytes when parsing.
+    ///
+    /// This and the other `max_*` limits are meant as a defense against
+    /// resource exhaustion when parsing untrusted modules: they are checked as
+    /// soon as the relevant counts are read, before walrus builds up the
+    /// corresponding parts of the module. Parsing a module that exceeds a limit
+    /// fails with an `ErrorKind::LimitExceeded` error.
+    ///
+    /// By default none of the limits are set.
+    pub fn max_module_size(&mut self, bytes: usize) -> &mut ModuleConfig {
+        self.limits.module_size = Some(bytes as u64);
+        self
+    }
+
+    /// Reject modules with more than `types` types when parsing.
+    pub fn max_types(&mut self, types: u32) -> &mut ModuleConfig {
+        self.limits.types = Some(types.into());
+        self
+    }
+
+    /// Reject modules with more than `functions` functions, counting both
+    /// imported and local functions, when parsing.
+    pub fn max_functions(&mut self, functions: u32) -> &mut ModuleConfig {
+        self.limits.functions = Some(functions.into());
+        self
+    }
+
+    /// Reject modules with a function that declares more than `locals` locals,
+    /// not counting its parameters, when parsing.
+    pub fn max_locals_per_function(&mut self, locals: u32) -> &mut ModuleConfig {
+        self.limits.locals_per_function = Some(locals.into());
+        self
+    }
+
+    /// Reject modules with a memory whose initial size is more than `pages`
+    /// pages when parsing.
+    ///
+    /// Only the initial size is checked, since that is what has to be
+    /// allocated at instantiation.
+    pub fn max_memory_pages(&mut self, pages: u32) -> &mut ModuleConfig {
+        self.limits.memory_pages = Some(pages.into());
+        self
+    }
+
+    /// Reject modules with a table whose initial size is more than `elements`
+    /// elements when parsing.
+    pub fn max_table_size(&mut self, elements: u32) -> &mut ModuleConfig {
+        self.limits.table_size = Some(elements.into());
+        self
+    }
+
+    /// Check `actual` against the configured limit for `which`.
+    pub(crate) fn check_limit(&self, which: Limit, actual: u64) -> Result<()> {
+        let limit = match which {
+            Limit::ModuleSize => self.limits.module_size,
+            Limit::Types => self.limits.types,
+            Limit::Functions => self.limits.functions,
+            Limit::LocalsPerFunction => self.limits.locals_per_function,
+            Limit::MemoryPages => self.limits.memory_pages,
+            Limit::TableSize => self.limits.table_size,
+        };
+        match limit {
+            Some(limit) if actual > limit => Err(ErrorKind::LimitExceeded {
+                which,
+                limit,
+                actual,
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
     /// Indicates whether this module is allowed to use only stable WebAssembly
     /// features or not.
     ///
@@ -151,6 +367,10 @@ impl ModuleConfig {
     /// the codebase, even if set to `true` some unstable features may still be
     /// allowed.
     ///
+    /// `Module::validate` also rejects modules that use unstable features
+    /// when this is set, so it can be used to check that transformed or
+    /// generated code stays within the stable baseline.
+    ///
     /// By default this flag is `false`
     pub fn only_stable_features(&mut self, only: bool) -> &mut ModuleConfig {
         self.only_stable_features = only;
@@ -180,6 +400,34 @@ impl ModuleConfig {
         self
     }
 
+    /// Provide a function that decides which exports `passes::gc` treats as
+    /// roots.
+    ///
+    /// By default every export is a root, so GC never removes an export or
+    /// anything it refers to. Exports for which `f` returns `false` are
+    /// instead deleted by GC, along with their items if nothing else uses
+    /// them. This is useful for stripping debug-only or internal exports,
+    /// such as ones named `__internal_*`, to make a module smaller.
+    ///
+    /// Note that only one such function may be registered and subsequent
+    /// registrations will override the old ones. Cloning a `ModuleConfig`
+    /// shares this function with the clone.
+    pub fn treat_export_as_root<F>(&mut self, f: F) -> &mut ModuleConfig
+    where
+        F: Fn(&Export) -> bool + Send + Sync + 'static,
+    {
+        self.export_is_root = Some(Arc::new(f) as _);
+        self
+    }
+
+    /// Whether `passes::gc` should treat `export` as a root.
+    pub(crate) fn export_is_root(&self, export: &Export) -> bool {
+        match &self.export_is_root {
+            Some(f) => f(export),
+            None => true,
+        }
+    }
+
     /// Provide a function that is invoked on source location ID step.
     ///
     /// Note that cloning a `ModuleConfig` will result in a config that does not
@@ -192,6 +440,26 @@ impl ModuleConfig {
         self
     }
 
+    /// Provide a function that is invoked with the id and raw contents of every
+    /// section that `walrus` doesn't know how to parse, such as sections from
+    /// future proposals.
+    ///
+    /// Such sections are normally rejected as invalid. With this function
+    /// registered, they are instead handed to it and then skipped, without
+    /// being validated. They are not emitted again either, but the function can
+    /// stash them away, for example as a custom section, to re-emit them
+    /// itself.
+    ///
+    /// Note that cloning a `ModuleConfig` will result in a config that does not
+    /// have an `on_raw_section` function, even if the original did.
+    pub fn on_raw_section<F>(&mut self, f: F) -> &mut ModuleConfig
+    where
+        F: Fn(&mut Module, u8, &[u8]) -> Result<()> + Send + Sync + 'static,
+    {
+        self.on_raw_section = Some(Box::new(f) as _);
+        self
+    }
+
     /// Sets a flag to whether code transform is preverved during parsing.
     ///
     /// By default this flag is `false`.
@@ -200,12 +468,77 @@ impl ModuleConfig {
         self
     }
 
+    /// Sets whether local functions are emitted in the order they were
+    /// parsed, rather than sorted by size.
+    ///
+    /// By default walrus emits the largest functions first, which helps
+    /// engines that compile functions in parallel, but also means that a
+    /// function's index in the output generally differs from its index in the
+    /// input. With this flag set, every function keeps its original relative
+    /// order and functions added after parsing are appended after all the
+    /// parsed ones, so that a module that is parsed and emitted without
+    /// changes keeps all of its function indices.
+    ///
+    /// Deleting a function, or adding a function import, still shifts the
+    /// indices of every function after it in the index space: this only
+    /// preserves the order of the functions, not gaps in it.
+    ///
+    /// By default this flag is `false`.
+    pub fn preserve_function_order(&mut self, preserve: bool) -> &mut ModuleConfig {
+        self.preserve_function_order = preserve;
+        self
+    }
+
+    /// Sets whether NaN float constants are canonicalized while parsing.
+    ///
+    /// By default the exact bits of every `f32.const` and `f64.const`,
+    /// including the sign and payload of NaNs, are preserved, so a module
+    /// round-trips bit for bit. With this flag set, any NaN in an `f32.const`
+    /// or `f64.const` instruction or in a global's initializer is replaced
+    /// with the positive canonical NaN of its type instead, which is useful
+    /// for tools that want deterministic output. `v128.const` values are
+    /// always preserved as-is, since their lanes aren't typed.
+    ///
+    /// By default this flag is `false`.
+    pub fn canonicalize_nans(&mut self, canonicalize: bool) -> &mut ModuleConfig {
+        self.canonicalize_nans = canonicalize;
+        self
+    }
+
+    /// Sets whether modules using the tail call proposal are accepted, with
+    /// their tail calls lowered to ordinary calls while parsing.
+    ///
+    /// walrus doesn't represent tail calls, so by default modules containing
+    /// `return_call` or `return_call_indirect` fail to parse. With this flag
+    /// set, each `return_call` is replaced with a `call` followed by a
+    /// `return`, and likewise for `return_call_indirect`. The callee's results
+    /// are exactly the caller's results, so the `return` passes all of them
+    /// on, however many there are. The module behaves the same, except that
+    /// each lowered tail call uses up stack space until the callee returns,
+    /// so deeply recursive code may run out of stack where it didn't before.
+    /// This makes it possible to take in modules that use tail calls and emit
+    /// them for engines that don't support them.
+    ///
+    /// By default this flag is `false`.
+    pub fn lower_tail_calls(&mut self, lower: bool) -> &mut ModuleConfig {
+        self.lower_tail_calls = lower;
+        self
+    }
+
     /// Parses an in-memory WebAssembly file into a `Module` using this
     /// configuration.
     pub fn parse(&self, wasm: &[u8]) -> Result<Module> {
         Module::parse(wasm, self)
     }
 
+    /// Parses an in-memory WebAssembly file into a `Module` using this
+    /// configuration, recovering from as many errors as possible.
+    ///
+    /// See `Module::from_buffer_lenient` for details.
+    pub fn parse_lenient(&self, wasm: &[u8]) -> (Module, Vec<ParseError>) {
+        Module::parse_lenient(wasm, self)
+    }
+
     /// Parses a WebAssembly file into a `Module` using this configuration.
     pub fn parse_file<P>(&self, path: P) -> Result<Module>
     where
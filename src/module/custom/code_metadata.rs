@@ -0,0 +1,240 @@
+//! Generic support for the `metadata.code.*` family of custom sections.
+//!
+//! Specified upstream at
+//! https://github.com/WebAssembly/tool-conventions/blob/main/CodeMetadata.md
+
+use crate::encode::Encoder;
+use crate::error::Result;
+use crate::ir::InstrLocId;
+use crate::parse::IndicesToIds;
+use crate::{CodeTransform, CustomSection, FunctionId, IdsToIndices};
+use anyhow::bail;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+
+/// The payload of a `metadata.code.*` custom section.
+///
+/// Implement this for your own metadata type to get a custom section that can
+/// be parsed, emitted, and that keeps its instruction offsets up to date as
+/// functions are transformed, via `CodeMetadataSection<T>`.
+pub trait CodeMetadata: Clone + Debug + Send + Sync + Sized + 'static {
+    /// The name of the custom section that this metadata is stored in, for
+    /// example `"metadata.code.branch_hint"`.
+    const SECTION_NAME: &'static str;
+
+    /// Decode a single metadata payload from its raw bytes.
+    fn decode(data: &[u8]) -> Result<Self>;
+
+    /// Encode this metadata payload, appending its raw bytes to `dst`.
+    fn encode(&self, dst: &mut Vec<u8>);
+}
+
+/// A piece of metadata attached to a single instruction.
+#[derive(Clone, Debug)]
+pub struct CodeMetadataEntry<T> {
+    /// The function containing the instruction.
+    pub func: FunctionId,
+    /// The instruction that this metadata is attached to.
+    pub instr: InstrLocId,
+    /// The metadata itself.
+    pub value: T,
+}
+
+/// A `metadata.code.*` custom section, which attaches a payload of type `T` to
+/// individual instructions.
+///
+/// In the binary format, instructions are referenced by their function's index
+/// and their byte offset within that function's body. Here they are instead
+/// referenced by `FunctionId` and `InstrLocId`, so that the metadata survives
+/// transformations of the module.
+///
+/// Since these sections reference functions by index, they must be parsed in
+/// a `ModuleConfig::on_parse` callback:
+///
+/// ```no_run
+/// use walrus::{BranchHintSection, ModuleConfig};
+///
+/// let mut config = ModuleConfig::new();
+/// config.preserve_code_transform(true);
+/// config.on_parse(|module, indices| {
+///     if let Some(raw) = module.customs.remove_raw(BranchHintSection::NAME) {
+///         let section = BranchHintSection::parse(&raw.data, indices)?;
+///         module.customs.add(section);
+///     }
+///     Ok(())
+/// });
+/// ```
+///
+/// Offsets are recomputed on emit from the module's code transform, so the
+/// module must be configured with `preserve_code_transform(true)`; otherwise,
+/// and for any instruction that no longer exists in the emitted code, the
+/// metadata is dropped. Note that `InstrLocId`s are assumed to be the default
+/// ones assigned while parsing (the instruction's offset in the original
+/// binary), so this does not work together with `ModuleConfig::on_instr_loc`.
+///
+/// Also note that, like all custom sections, this section is emitted after the
+/// code section, even though the tool conventions ask for it to come before.
+#[derive(Clone, Debug)]
+pub struct CodeMetadataSection<T> {
+    entries: Vec<CodeMetadataEntry<T>>,
+    // Pairs of an index into `entries` and the offset, within the emitted
+    // wasm, of that entry's instruction. Filled in by `apply_code_transform`.
+    transformed: Option<Vec<(usize, usize)>>,
+}
+
+impl<T: CodeMetadata> CodeMetadataSection<T> {
+    /// The name of this custom section.
+    pub const NAME: &'static str = T::SECTION_NAME;
+
+    /// Create a new, empty section.
+    pub fn new() -> Self {
+        CodeMetadataSection {
+            entries: Vec::new(),
+            transformed: None,
+        }
+    }
+
+    /// Parse this section from its raw data, as found in the original Wasm
+    /// binary.
+    pub fn parse(data: &[u8], indices: &IndicesToIds) -> Result<Self> {
+        let mut section = CodeMetadataSection::new();
+        let mut reader = wasmparser::BinaryReader::new(data);
+        for _ in 0..reader.read_var_u32()? {
+            let func = indices.get_func(reader.read_var_u32()?)?;
+            let code_offset = indices.get_func_code_offset(func)?;
+            for _ in 0..reader.read_var_u32()? {
+                let offset = reader.read_var_u32()? as usize;
+                let size = reader.read_var_u32()? as usize;
+                let value = T::decode(reader.read_bytes(size)?)?;
+                let instr = InstrLocId::new((code_offset + offset) as u32);
+                section.add(func, instr, value);
+            }
+        }
+        if !reader.eof() {
+            bail!(
+                "trailing bytes at the end of the `{}` section",
+                T::SECTION_NAME
+            );
+        }
+        Ok(section)
+    }
+
+    /// Attach the given metadata to an instruction.
+    pub fn add(&mut self, func: FunctionId, instr: InstrLocId, value: T) {
+        self.entries.push(CodeMetadataEntry { func, instr, value });
+    }
+
+    /// Iterate over all the metadata in this section.
+    pub fn iter(&self) -> impl Iterator<Item = &CodeMetadataEntry<T>> {
+        self.entries.iter()
+    }
+
+    /// Iterate mutably over all the metadata in this section.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut CodeMetadataEntry<T>> {
+        self.entries.iter_mut()
+    }
+}
+
+impl<T: CodeMetadata> Default for CodeMetadataSection<T> {
+    fn default() -> Self {
+        CodeMetadataSection::new()
+    }
+}
+
+impl<T: CodeMetadata> CustomSection for CodeMetadataSection<T> {
+    fn name(&self) -> &str {
+        T::SECTION_NAME
+    }
+
+    fn data(&self, ids_to_indices: &IdsToIndices) -> Cow<[u8]> {
+        // Group by function index, since the section must be sorted by it.
+        let mut funcs = BTreeMap::new();
+        for &(i, offset) in self.transformed.iter().flatten() {
+            let entry = &self.entries[i];
+            let range = match ids_to_indices.get_func_code_range(entry.func) {
+                Some(range) => range,
+                None => continue,
+            };
+            // The instruction may have been moved into another function.
+            if !range.contains(&offset) {
+                continue;
+            }
+            let index = ids_to_indices.get_func_index(entry.func);
+            funcs
+                .entry(index)
+                .or_insert_with(Vec::new)
+                .push(((offset - range.start) as u32, &entry.value));
+        }
+
+        let mut data = Vec::new();
+        let mut encoder = Encoder::new(&mut data);
+        encoder.usize(funcs.len());
+        for (index, mut entries) in funcs {
+            entries.sort_by_key(|e| e.0);
+            encoder.u32(index);
+            encoder.usize(entries.len());
+            for (offset, value) in entries {
+                let mut payload = Vec::new();
+                value.encode(&mut payload);
+                encoder.u32(offset);
+                encoder.bytes(&payload);
+            }
+        }
+        data.into()
+    }
+
+    fn apply_code_transform(&mut self, transform: &CodeTransform) {
+        let mut new_offsets = HashMap::new();
+        for (loc, offset) in transform.iter() {
+            new_offsets
+                .entry(loc.data())
+                .or_insert_with(Vec::new)
+                .push(*offset);
+        }
+
+        let mut transformed = Vec::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.instr.is_default() {
+                continue;
+            }
+            if let Some(offsets) = new_offsets.get(&entry.instr.data()) {
+                transformed.extend(offsets.iter().map(|offset| (i, *offset)));
+            }
+        }
+        self.transformed = Some(transformed);
+    }
+
+    fn clone_section(&self) -> Option<Box<dyn CustomSection>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+/// A hint for whether a branch (`br_if` or `if`) is likely to be taken, as
+/// stored in the `metadata.code.branch_hint` custom section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BranchHint {
+    /// The branch is unlikely to be taken.
+    Unlikely = 0,
+    /// The branch is likely to be taken.
+    Likely = 1,
+}
+
+impl CodeMetadata for BranchHint {
+    const SECTION_NAME: &'static str = "metadata.code.branch_hint";
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        match data {
+            [0] => Ok(BranchHint::Unlikely),
+            [1] => Ok(BranchHint::Likely),
+            _ => bail!("invalid branch hint"),
+        }
+    }
+
+    fn encode(&self, dst: &mut Vec<u8>) {
+        dst.push(*self as u8);
+    }
+}
+
+/// The `metadata.code.branch_hint` custom section.
+pub type BranchHintSection = CodeMetadataSection<BranchHint>;
@@ -0,0 +1,312 @@
+//! Read access to the `linking` custom section found in relocatable object
+//! files produced by LLVM, as consumed by `wasm-ld`.
+//!
+//! Specified upstream at
+//! https://github.com/WebAssembly/tool-conventions/blob/main/Linking.md
+
+use crate::error::Result;
+use crate::{CustomSection, IdsToIndices};
+use anyhow::{bail, Context};
+use std::borrow::Cow;
+use wasmparser::BinaryReader;
+
+const WASM_SEGMENT_INFO: u8 = 5;
+const WASM_INIT_FUNCS: u8 = 6;
+const WASM_SYMBOL_TABLE: u8 = 8;
+
+const SYMTAB_FUNCTION: u8 = 0;
+const SYMTAB_DATA: u8 = 1;
+const SYMTAB_GLOBAL: u8 = 2;
+const SYMTAB_SECTION: u8 = 3;
+const SYMTAB_EVENT: u8 = 4;
+const SYMTAB_TABLE: u8 = 5;
+
+/// The `linking` custom section of a relocatable object file.
+///
+/// This gives structured, read-only access to the symbol table, data segment
+/// info, and init functions. Any other subsections (such as COMDAT info) are
+/// skipped over.
+///
+/// All indices in this section are indices into the index spaces of the
+/// original Wasm binary, not `walrus` ids, and relocations are not applied, so
+/// this section is emitted exactly as it was parsed. That means that it is
+/// only meaningful as long as the module's items are not reordered, added, or
+/// removed.
+///
+/// Like other custom sections, it is not parsed by default; take it out of the
+/// module's custom sections and parse it yourself:
+///
+/// ```no_run
+/// # fn foo(module: &mut walrus::Module) -> walrus::Result<()> {
+/// use walrus::LinkingSection;
+///
+/// if let Some(raw) = module.customs.remove_raw(LinkingSection::NAME) {
+///     let linking = LinkingSection::parse(&raw.data)?;
+///     for symbol in linking.symbols() {
+///         println!("{:?}", symbol);
+///     }
+///     module.customs.add(linking);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LinkingSection {
+    version: u32,
+    symbols: Vec<SymbolInfo>,
+    segments: Vec<SegmentInfo>,
+    init_funcs: Vec<InitFunc>,
+    data: Vec<u8>,
+}
+
+/// An entry in the `linking` section's symbol table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolInfo {
+    /// The kind of this symbol, and the item it refers to.
+    pub kind: SymbolKind,
+    /// This symbol's flags, made up of the `SymbolInfo::*` flag constants.
+    pub flags: u32,
+}
+
+impl SymbolInfo {
+    /// The symbol is a weak symbol.
+    pub const BINDING_WEAK: u32 = 0x1;
+    /// The symbol is local to the object file.
+    pub const BINDING_LOCAL: u32 = 0x2;
+    /// The symbol is not exported from the final linked module.
+    pub const VISIBILITY_HIDDEN: u32 = 0x4;
+    /// The symbol refers to an import rather than a definition.
+    pub const UNDEFINED: u32 = 0x10;
+    /// The symbol is intended to be exported from the linked module.
+    pub const EXPORTED: u32 = 0x20;
+    /// The symbol has a name given explicitly in the symbol table.
+    pub const EXPLICIT_NAME: u32 = 0x40;
+    /// The symbol must not be stripped by the linker.
+    pub const NO_STRIP: u32 = 0x80;
+
+    /// Does this symbol refer to an import rather than a definition?
+    pub fn is_undefined(&self) -> bool {
+        self.flags & SymbolInfo::UNDEFINED != 0
+    }
+}
+
+/// The kind of a `SymbolInfo`, and the item that it refers to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A function symbol.
+    Function {
+        /// The index of the function.
+        index: u32,
+        /// The name of the symbol, if it isn't taken from the import.
+        name: Option<String>,
+    },
+    /// A data symbol.
+    Data {
+        /// The name of the symbol.
+        name: String,
+        /// Where the data lives, unless the symbol is undefined.
+        definition: Option<DataSymbolDefinition>,
+    },
+    /// A global symbol.
+    Global {
+        /// The index of the global.
+        index: u32,
+        /// The name of the symbol, if it isn't taken from the import.
+        name: Option<String>,
+    },
+    /// A section symbol, used for relocations against debug sections.
+    Section {
+        /// The index of the section.
+        section: u32,
+    },
+    /// An event symbol, from the exception handling proposal.
+    Event {
+        /// The index of the event.
+        index: u32,
+        /// The name of the symbol, if it isn't taken from the import.
+        name: Option<String>,
+    },
+    /// A table symbol.
+    Table {
+        /// The index of the table.
+        index: u32,
+        /// The name of the symbol, if it isn't taken from the import.
+        name: Option<String>,
+    },
+}
+
+/// The location of the data that a defined data symbol refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataSymbolDefinition {
+    /// The index of the data segment.
+    pub segment: u32,
+    /// The offset of the data within the segment.
+    pub offset: u32,
+    /// The size of the data, in bytes.
+    pub size: u32,
+}
+
+/// Extra information about a data segment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SegmentInfo {
+    /// The name of the segment.
+    pub name: String,
+    /// The alignment of the segment, as a power of two.
+    pub alignment: u32,
+    /// The segment's flags.
+    pub flags: u32,
+}
+
+/// A function to call on startup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitFunc {
+    /// The priority of this function; lower priorities run first.
+    pub priority: u32,
+    /// The index of the function's symbol in the symbol table.
+    pub symbol: u32,
+}
+
+impl LinkingSection {
+    /// The name of this custom section.
+    pub const NAME: &'static str = "linking";
+
+    /// Parse this section from its raw data.
+    pub fn parse(data: &[u8]) -> Result<LinkingSection> {
+        let mut ret = LinkingSection {
+            version: 0,
+            symbols: Vec::new(),
+            segments: Vec::new(),
+            init_funcs: Vec::new(),
+            data: data.to_vec(),
+        };
+        let mut reader = BinaryReader::new(data);
+        ret.version = reader.read_var_u32()?;
+        if ret.version != 2 {
+            bail!("unsupported linking section version {}", ret.version);
+        }
+        while !reader.eof() {
+            let id = reader.read_u8()? as u8;
+            let len = reader.read_var_u32()? as usize;
+            let mut payload = BinaryReader::new(reader.read_bytes(len)?);
+            match id {
+                WASM_SEGMENT_INFO => ret
+                    .parse_segment_info(&mut payload)
+                    .context("failed to parse segment info")?,
+                WASM_INIT_FUNCS => ret
+                    .parse_init_funcs(&mut payload)
+                    .context("failed to parse init functions")?,
+                WASM_SYMBOL_TABLE => ret
+                    .parse_symbol_table(&mut payload)
+                    .context("failed to parse symbol table")?,
+                _ => {
+                    log::debug!("skipping linking subsection {}", id);
+                    continue;
+                }
+            }
+            if !payload.eof() {
+                bail!("trailing bytes in linking subsection {}", id);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// The version of the linking metadata.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The symbol table.
+    pub fn symbols(&self) -> &[SymbolInfo] {
+        &self.symbols
+    }
+
+    /// Extra information about the data segments.
+    pub fn segments(&self) -> &[SegmentInfo] {
+        &self.segments
+    }
+
+    /// Functions to call on startup.
+    pub fn init_funcs(&self) -> &[InitFunc] {
+        &self.init_funcs
+    }
+
+    fn parse_segment_info(&mut self, reader: &mut BinaryReader) -> Result<()> {
+        for _ in 0..reader.read_var_u32()? {
+            self.segments.push(SegmentInfo {
+                name: reader.read_string()?.to_string(),
+                alignment: reader.read_var_u32()?,
+                flags: reader.read_var_u32()?,
+            });
+        }
+        Ok(())
+    }
+
+    fn parse_init_funcs(&mut self, reader: &mut BinaryReader) -> Result<()> {
+        for _ in 0..reader.read_var_u32()? {
+            self.init_funcs.push(InitFunc {
+                priority: reader.read_var_u32()?,
+                symbol: reader.read_var_u32()?,
+            });
+        }
+        Ok(())
+    }
+
+    fn parse_symbol_table(&mut self, reader: &mut BinaryReader) -> Result<()> {
+        for _ in 0..reader.read_var_u32()? {
+            let kind = reader.read_u8()? as u8;
+            let flags = reader.read_var_u32()?;
+            let undefined = flags & SymbolInfo::UNDEFINED != 0;
+            let explicit_name = flags & SymbolInfo::EXPLICIT_NAME != 0;
+
+            let kind = match kind {
+                SYMTAB_FUNCTION | SYMTAB_GLOBAL | SYMTAB_EVENT | SYMTAB_TABLE => {
+                    let index = reader.read_var_u32()?;
+                    let name = if !undefined || explicit_name {
+                        Some(reader.read_string()?.to_string())
+                    } else {
+                        None
+                    };
+                    match kind {
+                        SYMTAB_FUNCTION => SymbolKind::Function { index, name },
+                        SYMTAB_GLOBAL => SymbolKind::Global { index, name },
+                        SYMTAB_EVENT => SymbolKind::Event { index, name },
+                        _ => SymbolKind::Table { index, name },
+                    }
+                }
+                SYMTAB_DATA => {
+                    let name = reader.read_string()?.to_string();
+                    let definition = if undefined {
+                        None
+                    } else {
+                        Some(DataSymbolDefinition {
+                            segment: reader.read_var_u32()?,
+                            offset: reader.read_var_u32()?,
+                            size: reader.read_var_u32()?,
+                        })
+                    };
+                    SymbolKind::Data { name, definition }
+                }
+                SYMTAB_SECTION => SymbolKind::Section {
+                    section: reader.read_var_u32()?,
+                },
+                _ => bail!("unknown symbol kind {}", kind),
+            };
+            self.symbols.push(SymbolInfo { kind, flags });
+        }
+        Ok(())
+    }
+}
+
+impl CustomSection for LinkingSection {
+    fn name(&self) -> &str {
+        LinkingSection::NAME
+    }
+
+    fn data(&self, _: &IdsToIndices) -> Cow<[u8]> {
+        Cow::Borrowed(&self.data)
+    }
+
+    fn clone_section(&self) -> Option<Box<dyn CustomSection>> {
+        Some(Box::new(self.clone()))
+    }
+}
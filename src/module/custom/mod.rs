@@ -1,5 +1,16 @@
 //! Working with custom sections.
 
+mod code_metadata;
+mod linking;
+mod target_features;
+
+pub use self::code_metadata::{
+    BranchHint, BranchHintSection, CodeMetadata, CodeMetadataEntry, CodeMetadataSection,
+};
+pub use self::linking::{
+    DataSymbolDefinition, InitFunc, LinkingSection, SegmentInfo, SymbolInfo, SymbolKind,
+};
+pub use self::target_features::{FeaturePrefix, TargetFeature, TargetFeaturesSection};
 use crate::passes::Roots;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
 use crate::CodeTransform;
@@ -59,6 +70,18 @@ pub trait CustomSection: WalrusAny + Debug + Send + Sync {
     fn apply_code_transform(&mut self, transform: &CodeTransform) {
         let _ = transform;
     }
+
+    /// Make an independent copy of this custom section, for when the module
+    /// containing it is cloned.
+    ///
+    /// Sections that return `None` here are left out of the cloned module.
+    /// Implementations for `Clone` types will usually just be
+    /// `Some(Box::new(self.clone()))`.
+    ///
+    /// The default provided method returns `None`.
+    fn clone_section(&self) -> Option<Box<dyn CustomSection>> {
+        None
+    }
 }
 
 /// A wrapper trait around `any` but implemented for all types that already
@@ -120,6 +143,10 @@ impl CustomSection for RawCustomSection {
     fn data(&self, _: &IdsToIndices) -> Cow<[u8]> {
         self.data.as_slice().into()
     }
+
+    fn clone_section(&self) -> Option<Box<dyn CustomSection>> {
+        Some(Box::new(self.clone()))
+    }
 }
 
 /// A common trait for custom section identifiers.
@@ -290,7 +317,36 @@ pub struct ModuleCustomSections {
     arena: TombstoneArena<Option<Box<dyn CustomSection>>>,
 }
 
+impl Clone for ModuleCustomSections {
+    /// Clone each custom section with `CustomSection::clone_section`, leaving
+    /// out any that don't support it.
+    ///
+    /// The ids of the sections that are kept are valid in both copies.
+    fn clone(&self) -> Self {
+        let mut uncloned = Vec::new();
+        let mut arena = self.arena.clone_with(|id, section| {
+            let clone = section.as_ref()?.clone_section();
+            if clone.is_none() {
+                uncloned.push(id);
+            }
+            clone
+        });
+        for id in uncloned {
+            log::warn!(
+                "custom section `{}` can't be cloned; leaving it out of the clone",
+                self.arena[id].as_ref().unwrap().name()
+            );
+            arena.delete(id);
+        }
+        ModuleCustomSections { arena }
+    }
+}
+
 impl ModuleCustomSections {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Add a new custom section to the module.
     pub fn add<T>(&mut self, custom_section: T) -> TypedCustomSectionId<T>
     where
@@ -317,6 +373,9 @@ impl ModuleCustomSections {
     }
 
     /// Take a raw, unparsed custom section out of this module.
+    ///
+    /// If there are multiple raw custom sections named `name`, this takes the
+    /// first one, in the order given by `iter`.
     pub fn remove_raw(&mut self, name: &str) -> Option<RawCustomSection> {
         let id = self
             .arena
@@ -367,6 +426,11 @@ impl ModuleCustomSections {
     }
 
     /// Iterate over shared references to custom sections and their ids.
+    ///
+    /// Custom sections are yielded in the order they were added to the
+    /// module, which is also the order they are emitted in. Parsing adds
+    /// custom sections in the order they appear in the binary, so this can be
+    /// used to tell apart multiple custom sections with the same name.
     pub fn iter(&self) -> impl Iterator<Item = (UntypedCustomSectionId, &dyn CustomSection)> {
         self.arena.iter().flat_map(|(id, s)| {
             if let Some(s) = s.as_ref() {
@@ -378,6 +442,8 @@ impl ModuleCustomSections {
     }
 
     /// Iterate over exclusive references to custom sections and their ids.
+    ///
+    /// Custom sections are yielded in the same order as `iter`.
     pub fn iter_mut(
         &mut self,
     ) -> impl Iterator<Item = (UntypedCustomSectionId, &mut dyn CustomSection)> {
@@ -0,0 +1,178 @@
+//! The `target_features` custom section, which LLVM uses to record the wasm
+//! features that a module was compiled with.
+//!
+//! Specified upstream at
+//! https://github.com/WebAssembly/tool-conventions/blob/main/Linking.md#target-features-section
+
+use crate::encode::Encoder;
+use crate::error::Result;
+use crate::{CustomSection, IdsToIndices};
+use anyhow::bail;
+use std::borrow::Cow;
+use wasmparser::BinaryReader;
+
+/// The `target_features` custom section.
+///
+/// This lists wasm features, such as `simd128` or `atomics`, along with
+/// whether the module uses them, must not be linked with modules that use
+/// them, or requires every module it's linked with to use them. Keep it up to
+/// date after transformations that start using new features, so that tools
+/// reading it know what the module needs.
+///
+/// Like other custom sections, it is not parsed by default; take it out of the
+/// module's custom sections and parse it yourself:
+///
+/// ```no_run
+/// # fn foo(module: &mut walrus::Module) -> walrus::Result<()> {
+/// use walrus::{FeaturePrefix, TargetFeaturesSection};
+///
+/// let mut features = match module.customs.remove_raw(TargetFeaturesSection::NAME) {
+///     Some(raw) => TargetFeaturesSection::parse(&raw.data)?,
+///     None => TargetFeaturesSection::default(),
+/// };
+/// features.set(FeaturePrefix::Used, "simd128");
+/// module.customs.add(features);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TargetFeaturesSection {
+    features: Vec<TargetFeature>,
+}
+
+/// An entry in the `target_features` section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetFeature {
+    /// How the module relates to this feature.
+    pub prefix: FeaturePrefix,
+    /// The name of the feature, such as `simd128`.
+    pub name: String,
+}
+
+/// How a module relates to a feature in the `target_features` section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FeaturePrefix {
+    /// `+`: the module uses the feature.
+    Used,
+    /// `-`: the module must not be linked with modules that use the feature.
+    Disallowed,
+    /// `=`: every module the module is linked with must use the feature.
+    Required,
+}
+
+impl FeaturePrefix {
+    fn byte(self) -> u8 {
+        match self {
+            FeaturePrefix::Used => b'+',
+            FeaturePrefix::Disallowed => b'-',
+            FeaturePrefix::Required => b'=',
+        }
+    }
+}
+
+impl TargetFeaturesSection {
+    /// The name of this custom section.
+    pub const NAME: &'static str = "target_features";
+
+    /// Parse this section from its raw data.
+    pub fn parse(data: &[u8]) -> Result<TargetFeaturesSection> {
+        let mut reader = BinaryReader::new(data);
+        let mut features = Vec::new();
+        for _ in 0..reader.read_var_u32()? {
+            let prefix = match reader.read_u8()? as u8 {
+                b'+' => FeaturePrefix::Used,
+                b'-' => FeaturePrefix::Disallowed,
+                b'=' => FeaturePrefix::Required,
+                other => bail!("unknown target feature prefix {:#x}", other),
+            };
+            let name = reader.read_string()?.to_string();
+            features.push(TargetFeature { prefix, name });
+        }
+        if !reader.eof() {
+            bail!("trailing bytes in target_features section");
+        }
+        Ok(TargetFeaturesSection { features })
+    }
+
+    /// The features in this section, in order.
+    pub fn features(&self) -> &[TargetFeature] {
+        &self.features
+    }
+
+    /// Get the prefix of the feature called `name`, if it is in this section.
+    pub fn get(&self, name: &str) -> Option<FeaturePrefix> {
+        self.features
+            .iter()
+            .find(|f| f.name == name)
+            .map(|f| f.prefix)
+    }
+
+    /// Set the prefix of the feature called `name`.
+    ///
+    /// If the feature is already in this section, it keeps its place and only
+    /// its prefix changes; otherwise it is added to the end.
+    pub fn set(&mut self, prefix: FeaturePrefix, name: &str) {
+        match self.features.iter_mut().find(|f| f.name == name) {
+            Some(feature) => feature.prefix = prefix,
+            None => self.features.push(TargetFeature {
+                prefix,
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Remove the feature called `name` from this section, returning its
+    /// prefix if it was there.
+    pub fn remove(&mut self, name: &str) -> Option<FeaturePrefix> {
+        let i = self.features.iter().position(|f| f.name == name)?;
+        Some(self.features.remove(i).prefix)
+    }
+}
+
+impl CustomSection for TargetFeaturesSection {
+    fn name(&self) -> &str {
+        TargetFeaturesSection::NAME
+    }
+
+    fn data(&self, _: &IdsToIndices) -> Cow<[u8]> {
+        let mut data = Vec::new();
+        let mut encoder = Encoder::new(&mut data);
+        encoder.usize(self.features.len());
+        for feature in &self.features {
+            encoder.byte(feature.prefix.byte());
+            encoder.str(&feature.name);
+        }
+        Cow::Owned(data)
+    }
+
+    fn clone_section(&self) -> Option<Box<dyn CustomSection>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_update() {
+        let data = b"\x02+\x07simd128-\x07atomics";
+        let mut section = TargetFeaturesSection::parse(data).unwrap();
+        assert_eq!(section.get("simd128"), Some(FeaturePrefix::Used));
+        assert_eq!(section.get("atomics"), Some(FeaturePrefix::Disallowed));
+        assert_eq!(section.get("bulk-memory"), None);
+
+        let indices = IdsToIndices::default();
+        assert_eq!(&section.data(&indices)[..], &data[..]);
+
+        section.set(FeaturePrefix::Used, "atomics");
+        section.set(FeaturePrefix::Required, "bulk-memory");
+        assert_eq!(section.remove("simd128"), Some(FeaturePrefix::Used));
+        assert_eq!(section.remove("simd128"), None);
+        let names: Vec<_> = section.features().iter().map(|f| &f.name[..]).collect();
+        assert_eq!(names, ["atomics", "bulk-memory"]);
+
+        let reparsed = TargetFeaturesSection::parse(&section.data(&indices)).unwrap();
+        assert_eq!(reparsed, section);
+    }
+}
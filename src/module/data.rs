@@ -1,11 +1,13 @@
 //! Data segments within a wasm module.
 
 use crate::emit::{Emit, EmitContext, Section};
-use crate::ir::Value;
+use crate::ir::{ConstValue, Value};
+use crate::module::memories::PAGE_SIZE;
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
-use crate::{GlobalId, InitExpr, MemoryId, Module, Result, ValType};
+use crate::{GlobalId, InitExpr, Memory, MemoryId, Module, Result, ValType};
 use anyhow::{bail, Context};
+use std::cmp;
 
 /// A passive element segment identifier
 pub type DataId = Id<Data>;
@@ -18,17 +20,20 @@ pub type DataId = Id<Data>;
 /// memory (or memories) via the `memory.init` instruction (passive data
 /// segments). See the `kind` member and `DataKind` type for more details on the
 /// active/passive distinction.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Data {
     id: DataId,
     /// What kind of data segment is this? Passive or active?
     pub kind: DataKind,
     /// The data payload of this data segment.
     pub value: Vec<u8>,
+    /// The name of this data segment, used for debugging purposes in the
+    /// `name` custom section.
+    pub name: Option<String>,
 }
 
 /// The kind of data segment: passive or active.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum DataKind {
     /// An active data segment that is automatically initialized at some address
     /// in a static memory.
@@ -84,12 +89,16 @@ impl Data {
 
 /// All passive data sections of a wasm module, used to initialize memories via
 /// various instructions.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleData {
     arena: TombstoneArena<Data>,
 }
 
 impl ModuleData {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Get an element associated with an ID
     pub fn get(&self, id: DataId) -> &Data {
         &self.arena[id]
@@ -113,14 +122,101 @@ impl ModuleData {
         self.arena.iter().map(|(_, f)| f)
     }
 
+    /// Get a mutable reference to this module's data segments.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Data> {
+        self.arena.iter_mut().map(|(_, f)| f)
+    }
+
     /// Add a data segment
     pub fn add(&mut self, kind: DataKind, value: Vec<u8>) -> DataId {
         let id = self.arena.next_id();
-        let id2 = self.arena.alloc(Data { id, kind, value });
+        let id2 = self.arena.alloc(Data {
+            id,
+            kind,
+            value,
+            name: None,
+        });
         debug_assert_eq!(id, id2);
         id
     }
 
+    /// Set the initial contents of `memory`, starting at address 0, to the
+    /// flat image `bytes`.
+    ///
+    /// Every existing active data segment for `memory` is deleted, and the
+    /// image is split into new active segments. Memory starts out zeroed, so
+    /// leading and trailing zeros are never emitted, and any run of at least
+    /// `zero_run_threshold` zeros inside the image is left as a gap between
+    /// two segments rather than being stored. Shorter runs of zeros are kept
+    /// inline, since each extra segment has a few bytes of overhead of its
+    /// own.
+    ///
+    /// Returns the ids of the new data segments, in address order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` doesn't fit in a 32-bit address space.
+    pub fn set_flat_image(
+        &mut self,
+        memory: &mut Memory,
+        bytes: &[u8],
+        zero_run_threshold: usize,
+    ) -> Vec<DataId> {
+        assert!(
+            bytes.len() <= u32::MAX as usize,
+            "memory image is too large"
+        );
+
+        let memory_id = memory.id();
+        let old: Vec<DataId> = self
+            .iter()
+            .filter(|data| match &data.kind {
+                DataKind::Active(active) => active.memory == memory_id,
+                DataKind::Passive => false,
+            })
+            .map(|data| data.id())
+            .collect();
+        for id in old {
+            memory.data_segments.remove(&id);
+            self.delete(id);
+        }
+
+        let mut ids = Vec::new();
+        let mut start = None;
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] != 0 {
+                start = start.or(Some(i));
+                i += 1;
+                continue;
+            }
+            let zeros = i;
+            while i < bytes.len() && bytes[i] == 0 {
+                i += 1;
+            }
+            if let Some(s) = start {
+                if i == bytes.len() || i - zeros >= zero_run_threshold {
+                    ids.push(self.add_image_chunk(memory, s, &bytes[s..zeros]));
+                    start = None;
+                }
+            }
+        }
+        if let Some(s) = start {
+            ids.push(self.add_image_chunk(memory, s, &bytes[s..]));
+        }
+        ids
+    }
+
+    fn add_image_chunk(&mut self, memory: &mut Memory, offset: usize, chunk: &[u8]) -> DataId {
+        let kind = DataKind::Active(ActiveData {
+            memory: memory.id(),
+            location: ActiveDataLocation::Absolute(offset as u32),
+        });
+        let id = self.add(kind, chunk.to_vec());
+        memory.data_segments.insert(id);
+        id
+    }
+
     // Note that this is inaccordance with the upstream bulk memory proposal to
     // WebAssembly and isn't currently part of the WebAssembly standard.
     pub(crate) fn emit_data_count(&self, cx: &mut EmitContext) {
@@ -160,6 +256,96 @@ impl ModuleData {
 }
 
 impl Module {
+    /// Get the end of the highest byte initialized by `memory`'s active data
+    /// segments: the maximum of `offset + len` over all of them.
+    ///
+    /// This is `Some(0)` if there are no active data segments for `memory`, and
+    /// `None` if any of them has an offset relative to a global, since its
+    /// address isn't known until instantiation.
+    ///
+    /// Segments may overlap, in which case this is still the highest end of any
+    /// of them.
+    pub fn max_active_data_offset(&self, memory: MemoryId) -> Option<u64> {
+        let mut max = 0;
+        for data in self.data.iter() {
+            let active = match &data.kind {
+                DataKind::Active(active) if active.memory == memory => active,
+                _ => continue,
+            };
+            let offset = match active.location {
+                ActiveDataLocation::Absolute(offset) => offset,
+                ActiveDataLocation::Relative(_) => return None,
+            };
+            max = cmp::max(max, u64::from(offset) + data.value.len() as u64);
+        }
+        Some(max)
+    }
+
+    /// Move every active data segment `delta` bytes further into its memory,
+    /// for loaders that place a module's memory image at a base address
+    /// chosen at runtime.
+    ///
+    /// Segments placed relative to a global whose value is known, because
+    /// this module defines it with a constant initializer, become absolute
+    /// segments at that value plus `delta`. It is an error if a segment is
+    /// relative to any other global, such as an imported `__memory_base`, or
+    /// if it would be moved below address zero, beyond the 32-bit address
+    /// space, or past the initial size of a memory that this module defines.
+    /// Nothing is changed unless every segment can be moved.
+    pub fn relocate_data(&mut self, delta: i64) -> Result<()> {
+        let mut relocated = Vec::new();
+        for data in self.data.iter() {
+            let active = match &data.kind {
+                DataKind::Active(active) => active,
+                DataKind::Passive => continue,
+            };
+            let offset = match active.location {
+                ActiveDataLocation::Absolute(offset) => i64::from(offset),
+                ActiveDataLocation::Relative(global) => match self.evaluate_global(global) {
+                    Some(ConstValue::I32(offset)) => i64::from(offset as u32),
+                    _ => bail!(
+                        "cannot relocate {:?}: it is placed relative to {:?}, whose value \
+                         isn't known",
+                        data.id(),
+                        global
+                    ),
+                },
+            };
+
+            let new = offset + delta;
+            if new < 0 || new > i64::from(u32::MAX) {
+                bail!(
+                    "cannot relocate {:?} from offset {} to {}, outside of the 32-bit \
+                     address space",
+                    data.id(),
+                    offset,
+                    new
+                );
+            }
+            let memory = self.memories.get(active.memory);
+            let end = new as u64 + data.value.len() as u64;
+            if memory.import.is_none() && end > u64::from(memory.initial) * PAGE_SIZE {
+                bail!(
+                    "cannot relocate {:?} to offset {}: it would extend to byte {}, past the \
+                     initial size of {:?} ({} pages)",
+                    data.id(),
+                    new,
+                    end,
+                    active.memory,
+                    memory.initial
+                );
+            }
+            relocated.push((data.id(), new as u32));
+        }
+
+        for (id, offset) in relocated {
+            if let DataKind::Active(active) = &mut self.data.get_mut(id).kind {
+                active.location = ActiveDataLocation::Absolute(offset);
+            }
+        }
+        Ok(())
+    }
+
     /// Called when we see the data section section to create an id for all data
     /// indices
     ///
@@ -176,6 +362,7 @@ impl Module {
                 // parse the data segments.
                 value: Vec::new(),
                 kind: DataKind::Passive,
+                name: None,
             }));
         }
     }
@@ -200,6 +387,7 @@ impl Module {
                     id,
                     value: Vec::new(),
                     kind: DataKind::Passive,
+                    name: None,
                 })
             };
             let data = self.data.get_mut(id);
@@ -280,3 +468,135 @@ impl Emit for ModuleData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments(module: &Module, ids: &[DataId]) -> Vec<(u32, Vec<u8>)> {
+        ids.iter()
+            .map(|id| {
+                let data = module.data.get(*id);
+                match &data.kind {
+                    DataKind::Active(ActiveData {
+                        location: ActiveDataLocation::Absolute(offset),
+                        ..
+                    }) => (*offset, data.value.clone()),
+                    _ => panic!("expected an absolute active segment"),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn set_flat_image_skips_zero_runs() {
+        let mut module = Module::default();
+        let memory = module.memories.add_local(false, 1, None);
+
+        let image = [0, 0, 1, 2, 0, 3, 0, 0, 0, 0, 4, 0, 0];
+        let memory = module.memories.get_mut(memory);
+        let ids = module.data.set_flat_image(memory, &image, 4);
+        assert_eq!(memory.data_segments.len(), 2);
+        assert_eq!(
+            segments(&module, &ids),
+            vec![(2, vec![1, 2, 0, 3]), (10, vec![4])]
+        );
+    }
+
+    #[test]
+    fn max_active_data_offset() {
+        let mut module = Module::default();
+        let memory = module.memories.add_local(false, 1, None);
+        let other = module.memories.add_local(false, 1, None);
+        assert_eq!(module.max_active_data_offset(memory), Some(0));
+
+        let active = |memory, offset| {
+            DataKind::Active(ActiveData {
+                memory,
+                location: ActiveDataLocation::Absolute(offset),
+            })
+        };
+        // Overlapping segments, where the later one ends first.
+        module.data.add(active(memory, 0), vec![0; 100]);
+        module.data.add(active(memory, 10), vec![0; 20]);
+        module.data.add(active(other, 1000), vec![0; 20]);
+        module.data.add(DataKind::Passive, vec![0; 1000]);
+        assert_eq!(module.max_active_data_offset(memory), Some(100));
+        assert_eq!(module.max_active_data_offset(other), Some(1020));
+
+        let global = module
+            .globals
+            .add_local(ValType::I32, false, InitExpr::Value(Value::I32(0)));
+        module.data.add(
+            DataKind::Active(ActiveData {
+                memory,
+                location: ActiveDataLocation::Relative(global),
+            }),
+            vec![1],
+        );
+        assert_eq!(module.max_active_data_offset(memory), None);
+        assert_eq!(module.max_active_data_offset(other), Some(1020));
+    }
+
+    #[test]
+    fn set_flat_image_replaces_active_segments() {
+        let mut module = Module::default();
+        let memory = module.memories.add_local(false, 1, None);
+        let passive = module.data.add(DataKind::Passive, vec![1]);
+
+        let memory = module.memories.get_mut(memory);
+        let first = module.data.set_flat_image(memory, &[1, 0, 1], 1);
+        assert_eq!(first.len(), 2);
+
+        let second = module.data.set_flat_image(memory, &[0, 0, 0], 1);
+        assert!(second.is_empty());
+        assert!(memory.data_segments.is_empty());
+
+        let remaining: Vec<_> = module.data.iter().map(|d| d.id()).collect();
+        assert_eq!(remaining, [passive]);
+    }
+
+    #[test]
+    fn relocate_data() {
+        let mut module = Module::default();
+        let memory = module.memories.add_local(false, 1, None);
+        let active = |location| DataKind::Active(ActiveData { memory, location });
+        let base = module
+            .globals
+            .add_local(ValType::I32, false, InitExpr::Value(Value::I32(100)));
+        let absolute = module
+            .data
+            .add(active(ActiveDataLocation::Absolute(16)), vec![1, 2]);
+        let relative = module
+            .data
+            .add(active(ActiveDataLocation::Relative(base)), vec![3]);
+        module.data.add(DataKind::Passive, vec![4]);
+
+        module.relocate_data(1024).unwrap();
+        assert_eq!(
+            segments(&module, &[absolute, relative]),
+            vec![(1040, vec![1, 2]), (1124, vec![3])]
+        );
+
+        // Moving below zero or past the end of the memory fails without
+        // moving anything.
+        assert!(module.relocate_data(-1041).is_err());
+        assert!(module.relocate_data(64412).is_err());
+        assert_eq!(
+            segments(&module, &[absolute, relative]),
+            vec![(1040, vec![1, 2]), (1124, vec![3])]
+        );
+        module.relocate_data(64411).unwrap();
+        assert_eq!(
+            segments(&module, &[absolute, relative]),
+            vec![(65451, vec![1, 2]), (65535, vec![3])]
+        );
+
+        // Segments relative to an imported global can't be moved.
+        let (imported, _) = module.add_import_global("env", "__memory_base", ValType::I32, false);
+        module
+            .data
+            .add(active(ActiveDataLocation::Relative(imported)), vec![5]);
+        assert!(module.relocate_data(0).is_err());
+    }
+}
@@ -5,12 +5,13 @@ use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
 use crate::{ir::Value, FunctionId, InitExpr, Module, Result, TableId, ValType};
 use anyhow::{bail, Context};
+use std::collections::BTreeMap;
 
 /// A passive element segment identifier
 pub type ElementId = Id<Element>;
 
 /// A passive segment which contains a list of functions
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Element {
     id: Id<Element>,
 
@@ -22,6 +23,10 @@ pub struct Element {
 
     /// The function members of this passive elements segment.
     pub members: Vec<Option<FunctionId>>,
+
+    /// The name of this element segment, used for debugging purposes in the
+    /// `name` custom section.
+    pub name: Option<String>,
 }
 
 #[allow(missing_docs)]
@@ -47,12 +52,16 @@ impl Tombstone for Element {
 
 /// All element segments of a wasm module, used to initialize `anyfunc` tables,
 /// used as function pointers.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleElements {
     arena: TombstoneArena<Element>,
 }
 
 impl ModuleElements {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Get an element associated with an ID
     pub fn get(&self, id: ElementId) -> &Element {
         &self.arena[id]
@@ -94,13 +103,134 @@ impl ModuleElements {
             kind,
             ty,
             members,
+            name: None,
         });
         debug_assert_eq!(id, id2);
         id
     }
+
+    /// Add a passive element segment, whose `members` can be copied into a
+    /// table with `table.init` and which can be dropped with `elem.drop`.
+    ///
+    /// A `None` member is a null reference.
+    pub fn add_passive(&mut self, ty: ValType, members: Vec<Option<FunctionId>>) -> ElementId {
+        self.add(ElementKind::Passive, ty, members)
+    }
 }
 
 impl Module {
+    /// Get the function at each index of `table` that is initialized by its
+    /// active element segments.
+    ///
+    /// Only the indices that some segment initializes are included, so this
+    /// doesn't allocate anything for the rest of the table, however large it
+    /// is. Indices initialized with a `ref.null` map to `None`. Segments may
+    /// overlap, in which case later segments win, as they do at
+    /// instantiation.
+    ///
+    /// Returns an error if any of the table's active segments has an offset
+    /// relative to a global, since where it ends up isn't known until
+    /// instantiation.
+    pub fn table_entries(&self, table: TableId) -> Result<BTreeMap<u32, Option<FunctionId>>> {
+        let mut entries = BTreeMap::new();
+        for (id, offset) in self.active_table_segments(table)? {
+            for (i, member) in self.elements.get(id).members.iter().enumerate() {
+                // Anything past the largest possible table index can't be in
+                // bounds of any table.
+                match offset.checked_add(i as u32) {
+                    Some(index) => entries.insert(index, *member),
+                    None => break,
+                };
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Set the function at `index` of `table`, as initialized by its active
+    /// element segments; `None` sets it to a `ref.null`.
+    ///
+    /// If a segment already initializes `index`, the last such segment is
+    /// updated, since it is the one that wins. Otherwise the segment ending
+    /// right before `index` is extended, if there is one, or a new segment is
+    /// added. The table's initial size is grown to cover `index` if needed.
+    ///
+    /// Returns an error if the table doesn't hold `funcref`s, if any of its
+    /// active segments has an offset relative to a global, or if `index` is
+    /// beyond the table's maximum size.
+    pub fn set_table_entry(
+        &mut self,
+        table: TableId,
+        index: u32,
+        func: Option<FunctionId>,
+    ) -> Result<()> {
+        let segments = self.active_table_segments(table)?;
+        let t = self.tables.get_mut(table);
+        if t.element_ty != ValType::Funcref {
+            bail!(
+                "can only set entries of a `funcref` table, not a `{}` table",
+                t.element_ty
+            );
+        }
+        if let Some(maximum) = t.maximum {
+            if index >= maximum {
+                bail!(
+                    "table index {} is out of bounds for a table with maximum size {}",
+                    index,
+                    maximum
+                );
+            }
+        }
+        if index >= t.initial {
+            t.initial = index + 1;
+        }
+
+        let covering = segments.iter().rev().find(|(id, offset)| {
+            let len = self.elements.get(*id).members.len() as u64;
+            *offset <= index && u64::from(index) < u64::from(*offset) + len
+        });
+        if let Some((id, offset)) = covering {
+            self.elements.get_mut(*id).members[(index - offset) as usize] = func;
+            return Ok(());
+        }
+
+        let preceding = segments.iter().rev().find(|(id, offset)| {
+            let len = self.elements.get(*id).members.len() as u64;
+            u64::from(*offset) + len == u64::from(index)
+        });
+        match preceding {
+            Some((id, _)) => self.elements.get_mut(*id).members.push(func),
+            None => {
+                let kind = ElementKind::Active {
+                    table,
+                    offset: InitExpr::Value(Value::I32(index as i32)),
+                };
+                let id = self.elements.add(kind, ValType::Funcref, vec![func]);
+                self.tables.get_mut(table).elem_segments.insert(id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the active element segments for `table` in order, along with their
+    /// constant offsets.
+    fn active_table_segments(&self, table: TableId) -> Result<Vec<(ElementId, u32)>> {
+        let mut segments = Vec::new();
+        for element in self.elements.iter() {
+            let offset = match element.kind {
+                ElementKind::Active { table: t, offset } if t == table => offset,
+                _ => continue,
+            };
+            match offset {
+                InitExpr::Value(Value::I32(offset)) => segments.push((element.id(), offset as u32)),
+                _ => bail!(
+                    "element segment {} of the table has a non-constant offset",
+                    element.id().index()
+                ),
+            }
+        }
+        Ok(segments)
+    }
+
     /// Parses a raw was section into a fully-formed `ModuleElements` instance.
     pub(crate) fn parse_elements(
         &mut self,
@@ -154,6 +284,7 @@ impl Module {
                 ty,
                 kind,
                 members,
+                name: None,
             });
             ids.push_element(id);
         }
@@ -226,3 +357,115 @@ impl Emit for ModuleElements {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FunctionBuilder;
+
+    fn add_func(module: &mut Module) -> FunctionId {
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+        builder.func_body();
+        builder.finish(vec![], &mut module.funcs)
+    }
+
+    fn active(table: TableId, offset: i32) -> ElementKind {
+        ElementKind::Active {
+            table,
+            offset: InitExpr::Value(Value::I32(offset)),
+        }
+    }
+
+    #[test]
+    fn table_entries_later_segments_win() {
+        let mut module = Module::default();
+        let table = module.tables.add_local(4, None, ValType::Funcref);
+        let a = add_func(&mut module);
+        let b = add_func(&mut module);
+        module
+            .elements
+            .add(active(table, 0), ValType::Funcref, vec![Some(a), Some(a)]);
+        module.elements.add(
+            active(table, 1),
+            ValType::Funcref,
+            vec![Some(b), None, Some(b), Some(b)],
+        );
+
+        let entries = module.table_entries(table).unwrap();
+        assert_eq!(
+            entries.into_iter().collect::<Vec<_>>(),
+            [
+                (0, Some(a)),
+                (1, Some(b)),
+                (2, None),
+                (3, Some(b)),
+                (4, Some(b))
+            ]
+        );
+    }
+
+    #[test]
+    fn set_table_entry() {
+        let mut module = Module::default();
+        let table = module.tables.add_local(1, None, ValType::Funcref);
+        let a = add_func(&mut module);
+        let b = add_func(&mut module);
+
+        module.set_table_entry(table, 0, Some(a)).unwrap();
+        module.set_table_entry(table, 1, Some(b)).unwrap();
+        module.set_table_entry(table, 0, Some(b)).unwrap();
+        module.set_table_entry(table, 5, Some(a)).unwrap();
+
+        let entries = module.table_entries(table).unwrap();
+        assert_eq!(
+            entries.into_iter().collect::<Vec<_>>(),
+            [(0, Some(b)), (1, Some(b)), (5, Some(a))]
+        );
+        assert_eq!(module.tables.get(table).initial, 6);
+        assert_eq!(module.tables.get(table).elem_segments.len(), 2);
+    }
+
+    #[test]
+    fn table_entries_rejects_global_offsets() {
+        let mut module = Module::default();
+        let table = module.tables.add_local(1, None, ValType::Funcref);
+        let global = module
+            .globals
+            .add_local(ValType::I32, false, InitExpr::Value(Value::I32(0)));
+        module.elements.add(
+            ElementKind::Active {
+                table,
+                offset: InitExpr::Global(global),
+            },
+            ValType::Funcref,
+            vec![None],
+        );
+
+        assert!(module.table_entries(table).is_err());
+        assert!(module.set_table_entry(table, 0, None).is_err());
+    }
+
+    #[test]
+    fn table_entries_are_sparse() {
+        let mut module = Module::default();
+        let table = module.tables.add_local(u32::MAX, None, ValType::Funcref);
+        let a = add_func(&mut module);
+        module
+            .elements
+            .add(active(table, -2), ValType::Funcref, vec![Some(a), None]);
+
+        let entries = module.table_entries(table).unwrap();
+        assert_eq!(
+            entries.into_iter().collect::<Vec<_>>(),
+            [(u32::MAX - 1, Some(a)), (u32::MAX, None)]
+        );
+    }
+
+    #[test]
+    fn set_table_entry_requires_funcref() {
+        let mut module = Module::default();
+        let table = module.tables.add_local(1, None, ValType::Externref);
+        assert!(module.set_table_entry(table, 0, None).is_err());
+        assert!(module.elements.iter().next().is_none());
+    }
+}
@@ -4,6 +4,7 @@ use crate::emit::{Emit, EmitContext, Section};
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
 use crate::{FunctionId, GlobalId, MemoryId, Module, Result, TableId};
+use anyhow::bail;
 
 /// The id of an export.
 pub type ExportId = Id<Export>;
@@ -44,14 +45,32 @@ pub enum ExportItem {
     Global(GlobalId),
 }
 
+/// The number of exports of each kind in a module, as returned by
+/// `ModuleExports::count_by_kind`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExportCounts {
+    /// The number of exported functions.
+    pub functions: usize,
+    /// The number of exported tables.
+    pub tables: usize,
+    /// The number of exported memories.
+    pub memories: usize,
+    /// The number of exported globals.
+    pub globals: usize,
+}
+
 /// The set of exports in a module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleExports {
     /// The arena containing this module's exports.
     arena: TombstoneArena<Export>,
 }
 
 impl ModuleExports {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Gets a reference to an export given its id
     pub fn get(&self, id: ExportId) -> &Export {
         &self.arena[id]
@@ -123,9 +142,105 @@ impl ModuleExports {
             _ => false,
         })
     }
+
+    /// Get the id of the global exported under the given name, if any.
+    pub fn get_global_by_name(&self, name: &str) -> Option<GlobalId> {
+        self.iter().find_map(|e| match e.item {
+            ExportItem::Global(g) if e.name == name => Some(g),
+            _ => None,
+        })
+    }
+
+    /// Count this module's exports of each kind.
+    ///
+    /// This counts exports, not distinct items, so an item that is exported
+    /// under several names is counted once for each of them.
+    pub fn count_by_kind(&self) -> ExportCounts {
+        let mut counts = ExportCounts::default();
+        for export in self.iter() {
+            match export.item {
+                ExportItem::Function(_) => counts.functions += 1,
+                ExportItem::Table(_) => counts.tables += 1,
+                ExportItem::Memory(_) => counts.memories += 1,
+                ExportItem::Global(_) => counts.globals += 1,
+            }
+        }
+        counts
+    }
 }
 
 impl Module {
+    /// Remove this module's start function from the start section and export
+    /// it under `name` instead, returning the new export's id.
+    ///
+    /// This is the usual way to turn a command module into a reactor: the
+    /// function is kept, but it is no longer run automatically on
+    /// instantiation, and the embedder is expected to call it instead, for
+    /// example as `_initialize`.
+    ///
+    /// Returns an error if the module has no start function, if something is
+    /// already exported under `name`, or if the start function doesn't have
+    /// the type `[] -> []`, which a start function must always have.
+    pub fn demote_start_to_export(&mut self, name: &str) -> Result<ExportId> {
+        let start = match self.start {
+            Some(start) => start,
+            None => bail!("module has no start function"),
+        };
+        let (params, results) = self.types.params_results(self.funcs.get(start).ty());
+        if !params.is_empty() || !results.is_empty() {
+            bail!("start function must have type [] -> []");
+        }
+        if self.exports.iter().any(|e| e.name == name) {
+            bail!("there is already an export named `{}`", name);
+        }
+        self.start = None;
+        Ok(self.exports.add(name, start))
+    }
+
+    /// Export `memory` under `name`, returning the id of its export.
+    ///
+    /// If `memory` is already exported, its export is renamed to `name`
+    /// rather than adding a second one; if it is exported more than once, the
+    /// first of those exports is renamed. Otherwise a new export is added.
+    ///
+    /// Returns an error if something else is already exported under `name`.
+    pub fn export_memory(&mut self, memory: MemoryId, name: &str) -> Result<ExportId> {
+        let existing = self.exports.get_exported_memory(memory).map(|e| e.id());
+        let taken = self
+            .exports
+            .iter()
+            .any(|e| e.name == name && Some(e.id()) != existing);
+        if taken {
+            bail!("there is already an export named `{}`", name);
+        }
+        match existing {
+            Some(id) => {
+                self.exports.get_mut(id).name = name.to_string();
+                Ok(id)
+            }
+            None => Ok(self.exports.add(name, memory)),
+        }
+    }
+
+    /// Get this module's main memory.
+    ///
+    /// If the module has exactly one memory, that is its main memory. If it
+    /// has several, which requires the multi-memory proposal, the main memory
+    /// is the one exported as `memory`, as toolchains do for the memory that
+    /// they put the stack and heap in; if none of them is, this returns
+    /// `None`. It also returns `None` if the module has no memories.
+    pub fn main_memory(&self) -> Option<MemoryId> {
+        let mut memories = self.memories.iter();
+        let first = memories.next()?;
+        if memories.next().is_none() {
+            return Some(first.id());
+        }
+        self.exports.iter().find_map(|e| match e.item {
+            ExportItem::Memory(memory) if e.name == "memory" => Some(memory),
+            _ => None,
+        })
+    }
+
     /// Construct the export set for a wasm module.
     pub(crate) fn parse_exports(
         &mut self,
@@ -228,7 +343,8 @@ impl From<TableId> for ExportItem {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{FunctionBuilder, Module};
+    use crate::ir::{ConstValue, Value};
+    use crate::{FunctionBuilder, ImportKind, InitExpr, Module, ValType};
     use id_arena::Arena;
 
     /// this function always returns the same ID
@@ -311,6 +427,51 @@ mod tests {
         assert!(actual.is_none());
     }
 
+    #[test]
+    fn export_memory_renames_existing_export() {
+        let mut module = Module::default();
+        let memory = module.memories.add_local(false, 1, None);
+        let first = module.exports.add("mem", memory);
+        let id = module.export_memory(memory, "memory").unwrap();
+        assert_eq!(id, first);
+        assert_eq!(module.exports.iter().count(), 1);
+        assert_eq!(module.exports.get(id).name, "memory");
+
+        // Re-exporting under the same name is a no-op.
+        assert_eq!(module.export_memory(memory, "memory").unwrap(), first);
+    }
+
+    #[test]
+    fn export_memory_adds_export() {
+        let mut module = Module::default();
+        let memory = module.memories.add_local(false, 1, None);
+        let other = module.memories.add_local(false, 1, None);
+        module.exports.add("taken", other);
+
+        assert!(module.export_memory(memory, "taken").is_err());
+        let id = module.export_memory(memory, "memory").unwrap();
+        assert_eq!(module.exports.iter().count(), 2);
+        match module.exports.get(id).item {
+            ExportItem::Memory(m) => assert_eq!(m, memory),
+            _ => panic!("Expected a Memory variant"),
+        }
+    }
+
+    #[test]
+    fn main_memory() {
+        let mut module = Module::default();
+        assert_eq!(module.main_memory(), None);
+
+        let a = module.memories.add_local(false, 1, None);
+        assert_eq!(module.main_memory(), Some(a));
+
+        let b = module.memories.add_local(false, 1, None);
+        assert_eq!(module.main_memory(), None);
+
+        module.exports.add("memory", b);
+        assert_eq!(module.main_memory(), Some(b));
+    }
+
     #[test]
     fn get_exported_global() {
         let mut module = Module::default();
@@ -380,4 +541,41 @@ mod tests {
             _ => panic!("Expected a Function variant"),
         }
     }
+
+    #[test]
+    fn reexport_imported_func_round_trips() {
+        let mut module = Module::default();
+        let ty = module.types.add(&[], &[]);
+        let (fn_id, _) = module.add_import_func("env", "foo", ty);
+        module.exports.add("bar", fn_id);
+
+        let wasm = module.emit_wasm();
+        let module = Module::from_buffer(&wasm).unwrap();
+
+        assert_eq!(module.imports.iter().count(), 1);
+        assert_eq!(module.exports.iter().count(), 1);
+        let import_id = module.imports.find("env", "foo").unwrap();
+        let fn_id = match module.imports.get(import_id).kind {
+            ImportKind::Function(f) => f,
+            _ => panic!("Expected a Function import"),
+        };
+        let export = module.exports.get_exported_func(fn_id).unwrap();
+        assert_eq!(export.name, "bar");
+    }
+
+    #[test]
+    fn get_global_by_name() {
+        let mut module = Module::default();
+        let init = InitExpr::Value(Value::I32(1024));
+        let id = module.globals.add_local(ValType::I32, false, init);
+        module.exports.add("__heap_base", id);
+
+        let actual = module.exports.get_global_by_name("__heap_base");
+        assert_eq!(actual, Some(id));
+        assert!(module.exports.get_global_by_name("missing").is_none());
+        match module.globals.get(id).const_value() {
+            Some(ConstValue::I32(1024)) => {}
+            other => panic!("Expected Some(I32(1024)) got {:?}", other),
+        }
+    }
 }
@@ -11,19 +11,23 @@ pub(crate) fn run(
     local_indices: &IdHashMap<Local, u32>,
     encoder: &mut Encoder,
     map: Option<&mut Vec<(InstrLocId, usize)>>,
+    locations: Option<&mut Vec<(InstrSeqId, usize, usize)>>,
 ) {
     let v = &mut Emit {
         indices,
         blocks: vec![],
         block_kinds: vec![BlockKind::FunctionEntry],
+        positions: Default::default(),
         encoder,
         local_indices,
         map,
+        locations,
     };
     dfs_in_order(v, func, func.entry_block());
 
     debug_assert!(v.blocks.is_empty());
     debug_assert!(v.block_kinds.is_empty());
+    debug_assert!(v.positions.is_empty());
 }
 
 struct Emit<'a, 'b> {
@@ -42,16 +46,24 @@ struct Emit<'a, 'b> {
     // kind.
     block_kinds: Vec<BlockKind>,
 
+    // Where we are within the blocks in `blocks`.
+    positions: InstrPositions,
+
     // The instruction sequence we are building up to emit.
     encoder: &'a mut Encoder<'b>,
 
     // Encoded ExprId -> offset map.
     map: Option<&'a mut Vec<(InstrLocId, usize)>>,
+
+    // The offset of each instruction, as its sequence and index within it,
+    // and of the `end` or `else` of each sequence, as the sequence's length.
+    locations: Option<&'a mut Vec<(InstrSeqId, usize, usize)>>,
 }
 
 impl<'instr> Visitor<'instr> for Emit<'_, '_> {
     fn start_instr_seq(&mut self, seq: &'instr InstrSeq) {
         self.blocks.push(seq.id());
+        self.positions.start_instr_seq(seq);
         debug_assert_eq!(self.blocks.len(), self.block_kinds.len());
 
         match self.block_kinds.last().unwrap() {
@@ -82,6 +94,11 @@ impl<'instr> Visitor<'instr> for Emit<'_, '_> {
         let popped_kind = self.block_kinds.pop();
         debug_assert!(popped_kind.is_some());
 
+        self.positions.end_instr_seq();
+        if let Some(locations) = self.locations.as_mut() {
+            locations.push((seq.id(), seq.len(), self.encoder.pos()));
+        }
+
         debug_assert_eq!(self.blocks.len(), self.block_kinds.len());
 
         if let BlockKind::If = popped_kind.unwrap() {
@@ -104,6 +121,11 @@ impl<'instr> Visitor<'instr> for Emit<'_, '_> {
             map.push((instr_loc.clone(), pos));
         }
 
+        let (seq, index) = self.positions.next_instr();
+        if let Some(locations) = self.locations.as_mut() {
+            locations.push((seq, index, self.encoder.pos()));
+        }
+
         match instr {
             Block(_) => self.block_kinds.push(BlockKind::Block),
             Loop(_) => self.block_kinds.push(BlockKind::Loop),
@@ -2,6 +2,9 @@
 
 mod context;
 mod emit;
+mod print;
+
+pub(crate) use self::print::instr_mnemonic;
 
 use self::context::ValidationContext;
 use crate::emit::IdsToIndices;
@@ -10,11 +13,12 @@ use crate::ir::*;
 use crate::map::{IdHashMap, IdHashSet};
 use crate::parse::IndicesToIds;
 use crate::{Data, DataId, FunctionBuilder, FunctionId, MemoryId, Module, Result, TypeId, ValType};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
 use wasmparser::{FuncValidator, Operator, ValidatorResources};
 
 /// A function defined locally within the wasm module.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct LocalFunction {
     /// All of this function's instructions, contained in the arena.
     builder: FunctionBuilder,
@@ -51,14 +55,13 @@ impl LocalFunction {
             args,
         };
 
-        let result: Vec<_> = module.types.get(ty).results().iter().cloned().collect();
-        let result = result.into_boxed_slice();
+        let result = module.types.results(ty);
 
         let controls = &mut context::ControlStack::new();
 
         let mut ctx = ValidationContext::new(module, indices, id, &mut func, controls);
 
-        let ty = module.types.find_for_function_entry(&result).expect(
+        let ty = module.types.find_for_function_entry(result).expect(
             "the function entry type should have already been created before parsing the body",
         );
         let entry = ctx.push_control_with_ty(BlockKind::FunctionEntry, ty);
@@ -87,6 +90,30 @@ impl LocalFunction {
         self.builder.ty
     }
 
+    /// Get this function's parameters, in order.
+    ///
+    /// These are the locals that the function's arguments are assigned to,
+    /// the same as the `args` field.
+    pub fn args(&self) -> &[LocalId] {
+        &self.args
+    }
+
+    /// Get the locals that this function declares in addition to its
+    /// parameters, sorted by id.
+    ///
+    /// Locals aren't owned by a function in walrus, so these are the locals
+    /// that the function's body refers to and that aren't parameters: exactly
+    /// the ones that are declared when the function is emitted.
+    pub fn declared_locals(&self) -> Vec<LocalId> {
+        let mut locals = self
+            .used_locals()
+            .into_iter()
+            .filter(|local| !self.args.contains(local))
+            .collect::<Vec<_>>();
+        locals.sort_unstable();
+        locals
+    }
+
     pub(crate) fn add_block(
         &mut self,
         make_block: impl FnOnce(InstrSeqId) -> InstrSeq,
@@ -167,6 +194,45 @@ impl LocalFunction {
         }
     }
 
+    /// Rewrite every reference to a local in this function's body according to
+    /// `map`, leaving locals that aren't in the map untouched.
+    ///
+    /// Note that this only rewrites instructions such as `local.get`; it does
+    /// not touch `args`.
+    pub fn remap_locals<S: BuildHasher>(&mut self, map: &HashMap<LocalId, LocalId, S>) {
+        let entry = self.entry_block();
+        dfs_pre_order_mut(&mut RemapLocals { map }, self, entry);
+
+        struct RemapLocals<'a, S> {
+            map: &'a HashMap<LocalId, LocalId, S>,
+        }
+
+        impl<S: BuildHasher> RemapLocals<'_, S> {
+            fn remap(&self, local: &mut LocalId) {
+                if let Some(new) = self.map.get(local) {
+                    *local = *new;
+                }
+            }
+        }
+
+        // The traversal visits an instruction's `LocalId` both through these
+        // methods and through `visit_local_id_mut`, so the rewrite has to
+        // happen in exactly one of them, or a swap would be undone.
+        impl<S: BuildHasher> VisitorMut for RemapLocals<'_, S> {
+            fn visit_local_get_mut(&mut self, instr: &mut LocalGet) {
+                self.remap(&mut instr.local);
+            }
+
+            fn visit_local_set_mut(&mut self, instr: &mut LocalSet) {
+                self.remap(&mut instr.local);
+            }
+
+            fn visit_local_tee_mut(&mut self, instr: &mut LocalTee) {
+                self.remap(&mut instr.local);
+            }
+        }
+    }
+
     fn used_locals(&self) -> IdHashSet<Local> {
         let mut locals = Used::default();
         dfs_in_order(&mut locals, self, self.entry_block());
@@ -247,8 +313,9 @@ impl LocalFunction {
         local_indices: &IdHashMap<Local, u32>,
         dst: &mut Encoder,
         map: Option<&mut Vec<(InstrLocId, usize)>>,
+        locations: Option<&mut Vec<(InstrSeqId, usize, usize)>>,
     ) {
-        emit::run(self, indices, local_indices, dst, map)
+        emit::run(self, indices, local_indices, dst, map, locations)
     }
 }
 
@@ -290,7 +357,12 @@ fn append_instruction<'context>(
 
     log::trace!("validate instruction: {:?}", inst);
 
-    let const_ = |ctx: &mut ValidationContext, value| {
+    let const_ = |ctx: &mut ValidationContext, value: Value| {
+        let value = if ctx.module.config.canonicalize_nans {
+            value.canonicalize_nan()
+        } else {
+            value
+        };
         ctx.alloc_instr(Const { value }, loc);
     };
 
@@ -1286,9 +1358,23 @@ fn append_instruction<'context>(
             ctx.alloc_instr(ElemDrop { elem }, loc);
         }
 
-        Operator::ReturnCall { .. }
-        | Operator::ReturnCallIndirect { .. }
-        | Operator::Try { ty: _ }
+        // Tail calls are only accepted by the validator when they are to be
+        // lowered to a call followed by a return.
+        Operator::ReturnCall { function_index } => {
+            let func = ctx.indices.get_func(function_index).unwrap();
+            ctx.alloc_instr(Call { func }, loc);
+            ctx.alloc_instr(Return {}, loc);
+            ctx.unreachable();
+        }
+        Operator::ReturnCallIndirect { index, table_index } => {
+            let type_id = ctx.indices.get_type(index).unwrap();
+            let table = ctx.indices.get_table(table_index).unwrap();
+            ctx.alloc_instr(CallIndirect { table, ty: type_id }, loc);
+            ctx.alloc_instr(Return {}, loc);
+            ctx.unreachable();
+        }
+
+        Operator::Try { ty: _ }
         | Operator::Catch { index: _ }
         | Operator::Throw { index: _ }
         | Operator::Rethrow { relative_depth: _ }
@@ -1299,3 +1385,58 @@ fn append_instruction<'context>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::*;
+    use crate::{FunctionBuilder, LocalFunction, Module, ValType};
+    use std::collections::HashMap;
+
+    #[test]
+    fn remap_locals_swaps_locals() {
+        let mut module = Module::default();
+        let a = module.locals.add(ValType::I32);
+        let b = module.locals.add(ValType::I32);
+        let c = module.locals.add(ValType::I32);
+
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+        builder
+            .func_body()
+            .local_get(a)
+            .local_set(b)
+            .block(None, |block| {
+                block.local_get(b).local_tee(a).local_set(c);
+            });
+        let mut func = builder.local_func(vec![]);
+
+        let mut map = HashMap::new();
+        map.insert(a, b);
+        map.insert(b, a);
+        func.remap_locals(&map);
+        assert_eq!(locals_used(&func), [b, a, a, b, c]);
+
+        // Each local is only rewritten once, so chains aren't followed.
+        let mut map = HashMap::new();
+        map.insert(a, b);
+        map.insert(b, c);
+        func.remap_locals(&map);
+        assert_eq!(locals_used(&func), [c, b, b, c, c]);
+    }
+
+    fn locals_used(func: &LocalFunction) -> Vec<LocalId> {
+        let mut locals = Vec::new();
+        let mut seqs = vec![func.entry_block()];
+        while let Some(seq) = seqs.pop() {
+            for (instr, _) in func.block(seq).iter() {
+                match instr {
+                    Instr::LocalGet(LocalGet { local })
+                    | Instr::LocalSet(LocalSet { local })
+                    | Instr::LocalTee(LocalTee { local }) => locals.push(*local),
+                    Instr::Block(Block { seq }) => seqs.push(*seq),
+                    _ => {}
+                }
+            }
+        }
+        locals
+    }
+}
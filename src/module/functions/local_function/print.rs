@@ -0,0 +1,320 @@
+//! Printing a local function's body as WAT-like text, for debugging.
+
+use super::LocalFunction;
+use crate::ir::*;
+use crate::{FunctionId, GlobalId, LocalId, Module, TypeId};
+use std::fmt::Write;
+
+impl LocalFunction {
+    /// Render this function as readable, WAT-like text.
+    ///
+    /// The body is printed as a flat listing of instructions, indented by
+    /// nesting depth. Functions and locals are referred to by name where they
+    /// have one, and by a made-up name based on their id otherwise; blocks,
+    /// loops, and `if`s are labelled after the id of their instruction
+    /// sequence.
+    ///
+    /// This is meant for eyeballing a function while debugging a pass, not for
+    /// feeding to other tools: the output isn't guaranteed to be valid WAT,
+    /// and its exact format may change.
+    pub fn to_wat(&self, module: &Module) -> String {
+        let mut out = String::new();
+        let printer = Printer { module };
+
+        out.push_str("(func");
+        // Local functions don't know their own id, so find it to get at the
+        // function's name.
+        let name = module
+            .funcs
+            .iter_local()
+            .find(|(_, f)| std::ptr::eq(*f, self))
+            .and_then(|(id, _)| module.funcs.get(id).name.as_ref());
+        if let Some(name) = name {
+            write!(out, " ${}", name).unwrap();
+        }
+        for arg in &self.args {
+            let ty = module.locals.get(*arg).ty();
+            write!(out, " (param {} {})", printer.local(*arg), ty).unwrap();
+        }
+        let results = module.types.results(self.ty());
+        if !results.is_empty() {
+            out.push_str(" (result");
+            for ty in results {
+                write!(out, " {}", ty).unwrap();
+            }
+            out.push(')');
+        }
+        out.push('\n');
+
+        // An explicit stack of the work left to do, rather than recursion, so
+        // that deeply nested functions can't blow the call stack.
+        enum Work {
+            Instrs(InstrSeqId, usize),
+            Else,
+            End,
+        }
+
+        let mut depth = 1;
+        let mut stack = vec![Work::Instrs(self.entry_block(), 0)];
+        'work: while let Some(work) = stack.pop() {
+            let (seq_id, start) = match work {
+                Work::Instrs(seq_id, start) => (seq_id, start),
+                Work::Else => {
+                    line(&mut out, depth - 1, "else");
+                    continue;
+                }
+                Work::End => {
+                    depth -= 1;
+                    line(&mut out, depth, "end");
+                    continue;
+                }
+            };
+
+            let seq = self.block(seq_id);
+            for (i, (instr, _)) in seq.instrs.iter().enumerate().skip(start) {
+                let nested = match instr {
+                    Instr::Block(Block { seq }) => Some(("block", *seq, None)),
+                    Instr::Loop(Loop { seq }) => Some(("loop", *seq, None)),
+                    Instr::IfElse(IfElse {
+                        consequent,
+                        alternative,
+                    }) => Some(("if", *consequent, Some(*alternative))),
+                    _ => None,
+                };
+                let (keyword, body, alternative) = match nested {
+                    Some(nested) => nested,
+                    None => {
+                        line(&mut out, depth, &printer.instr(instr));
+                        continue;
+                    }
+                };
+
+                let mut text = format!("{} {}", keyword, label(body));
+                printer.block_type(&mut text, self.block(body).ty);
+                line(&mut out, depth, &text);
+                depth += 1;
+
+                stack.push(Work::Instrs(seq_id, i + 1));
+                stack.push(Work::End);
+                if let Some(alternative) = alternative {
+                    stack.push(Work::Instrs(alternative, 0));
+                    stack.push(Work::Else);
+                }
+                stack.push(Work::Instrs(body, 0));
+                continue 'work;
+            }
+        }
+
+        out.push(')');
+        out
+    }
+}
+
+fn line(out: &mut String, depth: usize, text: &str) {
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+    out.push_str(text);
+    out.push('\n');
+}
+
+fn label(seq: InstrSeqId) -> String {
+    format!("$block{}", seq.index())
+}
+
+struct Printer<'a> {
+    module: &'a Module,
+}
+
+impl Printer<'_> {
+    fn local(&self, id: LocalId) -> String {
+        match &self.module.locals.get(id).name {
+            Some(name) => format!("${}", name),
+            None => format!("$local{}", id.index()),
+        }
+    }
+
+    fn func(&self, id: FunctionId) -> String {
+        match &self.module.funcs.get(id).name {
+            Some(name) => format!("${}", name),
+            None => format!("$func{}", id.index()),
+        }
+    }
+
+    fn global(&self, id: GlobalId) -> String {
+        format!("$global{}", id.index())
+    }
+
+    fn func_type(&self, out: &mut String, ty: TypeId) {
+        let (params, results) = self.module.types.params_results(ty);
+        for param in params {
+            write!(out, " (param {})", param).unwrap();
+        }
+        for result in results {
+            write!(out, " (result {})", result).unwrap();
+        }
+    }
+
+    fn block_type(&self, out: &mut String, ty: InstrSeqType) {
+        match ty {
+            InstrSeqType::Simple(None) => {}
+            InstrSeqType::Simple(Some(ty)) => write!(out, " (result {})", ty).unwrap(),
+            InstrSeqType::MultiValue(ty) => self.func_type(out, ty),
+        }
+    }
+
+    fn instr(&self, instr: &Instr) -> String {
+        match instr {
+            Instr::Call(Call { func }) => format!("call {}", self.func(*func)),
+            Instr::CallIndirect(CallIndirect { ty, .. }) => {
+                let mut text = "call_indirect".to_string();
+                self.func_type(&mut text, *ty);
+                text
+            }
+            Instr::LocalGet(LocalGet { local }) => format!("local.get {}", self.local(*local)),
+            Instr::LocalSet(LocalSet { local }) => format!("local.set {}", self.local(*local)),
+            Instr::LocalTee(LocalTee { local }) => format!("local.tee {}", self.local(*local)),
+            Instr::GlobalGet(GlobalGet { global }) => {
+                format!("global.get {}", self.global(*global))
+            }
+            Instr::GlobalSet(GlobalSet { global }) => {
+                format!("global.set {}", self.global(*global))
+            }
+            Instr::Const(Const { value }) => {
+                let ty = match value {
+                    Value::I32(_) => "i32",
+                    Value::I64(_) => "i64",
+                    Value::F32(_) => "f32",
+                    Value::F64(_) => "f64",
+                    Value::V128(_) => "v128",
+                };
+                format!("{}.const {}", ty, value)
+            }
+            Instr::Binop(Binop { op }) => mnemonic(&format!("{:?}", op)),
+            Instr::Unop(Unop { op }) => mnemonic(&format!("{:?}", op)),
+            Instr::Select(Select { ty: Some(ty) }) => format!("select (result {})", ty),
+            Instr::Br(Br { block }) => format!("br {}", label(*block)),
+            Instr::BrIf(BrIf { block }) => format!("br_if {}", label(*block)),
+            Instr::BrTable(BrTable { blocks, default }) => {
+                let mut text = "br_table".to_string();
+                for block in blocks.iter().chain(Some(default)) {
+                    write!(text, " {}", label(*block)).unwrap();
+                }
+                text
+            }
+            Instr::Load(Load { kind, arg, .. }) => {
+                let (base, extend) = match kind {
+                    LoadKind::I32 { atomic: false } => ("i32.load", None),
+                    LoadKind::I32 { atomic: true } => ("i32.atomic.load", None),
+                    LoadKind::I64 { atomic: false } => ("i64.load", None),
+                    LoadKind::I64 { atomic: true } => ("i64.atomic.load", None),
+                    LoadKind::F32 => ("f32.load", None),
+                    LoadKind::F64 => ("f64.load", None),
+                    LoadKind::V128 => ("v128.load", None),
+                    LoadKind::I32_8 { kind } => ("i32.load8", Some(kind)),
+                    LoadKind::I32_16 { kind } => ("i32.load16", Some(kind)),
+                    LoadKind::I64_8 { kind } => ("i64.load8", Some(kind)),
+                    LoadKind::I64_16 { kind } => ("i64.load16", Some(kind)),
+                    LoadKind::I64_32 { kind } => ("i64.load32", Some(kind)),
+                };
+                let name = match extend {
+                    None => base.to_string(),
+                    Some(ExtendedLoad::SignExtend) => format!("{}_s", base),
+                    Some(ExtendedLoad::ZeroExtend) => format!("{}_u", base),
+                    Some(ExtendedLoad::ZeroExtendAtomic) => {
+                        format!("{}_u", base.replacen('.', ".atomic.", 1))
+                    }
+                };
+                format!("{}{}", name, mem_arg(arg))
+            }
+            Instr::Store(Store { kind, arg, .. }) => {
+                let name = match kind {
+                    StoreKind::I32 { atomic: false } => "i32.store",
+                    StoreKind::I32 { atomic: true } => "i32.atomic.store",
+                    StoreKind::I64 { atomic: false } => "i64.store",
+                    StoreKind::I64 { atomic: true } => "i64.atomic.store",
+                    StoreKind::F32 => "f32.store",
+                    StoreKind::F64 => "f64.store",
+                    StoreKind::V128 => "v128.store",
+                    StoreKind::I32_8 { atomic: false } => "i32.store8",
+                    StoreKind::I32_8 { atomic: true } => "i32.atomic.store8",
+                    StoreKind::I32_16 { atomic: false } => "i32.store16",
+                    StoreKind::I32_16 { atomic: true } => "i32.atomic.store16",
+                    StoreKind::I64_8 { atomic: false } => "i64.store8",
+                    StoreKind::I64_8 { atomic: true } => "i64.atomic.store8",
+                    StoreKind::I64_16 { atomic: false } => "i64.store16",
+                    StoreKind::I64_16 { atomic: true } => "i64.atomic.store16",
+                    StoreKind::I64_32 { atomic: false } => "i64.store32",
+                    StoreKind::I64_32 { atomic: true } => "i64.atomic.store32",
+                };
+                format!("{}{}", name, mem_arg(arg))
+            }
+            Instr::RefNull(RefNull { ty }) => format!("ref.null {}", ty),
+            Instr::RefFunc(RefFunc { func }) => format!("ref.func {}", self.func(*func)),
+            // Everything else is printed by name, without its immediates.
+            other => mnemonic(&format!("{:?}", other)),
+        }
+    }
+}
+
+/// The mnemonic of `instr`, such as `i32.atomic.load`, without any
+/// immediates.
+pub(crate) fn instr_mnemonic(module: &Module, instr: &Instr) -> String {
+    let text = Printer { module }.instr(instr);
+    text.split(' ').next().unwrap_or_default().to_string()
+}
+
+fn mem_arg(arg: &MemArg) -> String {
+    let mut text = String::new();
+    if arg.offset != 0 {
+        write!(text, " offset={}", arg.offset).unwrap();
+    }
+    write!(text, " align={}", arg.align).unwrap();
+    text
+}
+
+/// Turn the `Debug` name of an instruction or operator, such as `MemoryGrow` or
+/// `I32Add`, into a WAT-style mnemonic such as `memory.grow` or `i32.add`.
+///
+/// Any immediates in the `Debug` output are dropped, and the result is only an
+/// approximation of the real mnemonic for some operators, but it is readable.
+fn mnemonic(debug: &str) -> String {
+    let name = debug.split([' ', '(', '{']).next().unwrap();
+    let mut out = String::new();
+    for (i, c) in name.char_indices() {
+        if i > 0 && c.is_ascii_uppercase() {
+            out.push(if out.contains('.') { '_' } else { '.' });
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::BinaryOp;
+    use crate::{FunctionBuilder, Module, ValType};
+
+    #[test]
+    fn to_wat_uses_names() {
+        let mut module = Module::default();
+        let x = module.locals.add(ValType::I32);
+        module.locals.get_mut(x).name = Some("x".to_string());
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+        builder.name("double".to_string());
+        builder.func_body().block(ValType::I32, |block| {
+            block.local_get(x).local_get(x).binop(BinaryOp::I32Add);
+        });
+        let id = builder.finish(vec![x], &mut module.funcs);
+
+        let wat = module.funcs.get(id).kind.unwrap_local().to_wat(&module);
+        let lines: Vec<_> = wat.lines().map(|l| l.trim()).collect();
+        assert_eq!(lines[0], "(func $double (param $x i32) (result i32)");
+        assert!(lines[1].starts_with("block $block"));
+        assert!(lines[1].ends_with("(result i32)"));
+        assert_eq!(
+            &lines[2..],
+            ["local.get $x", "local.get $x", "i32.add", "end", ")"]
+        );
+    }
+}
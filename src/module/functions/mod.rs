@@ -2,22 +2,30 @@
 
 mod local_function;
 
-use crate::emit::{Emit, EmitContext, Section};
+use crate::emit::{Emit, EmitContext, IdsToIndices, Section};
 use crate::encode::Encoder;
-use crate::error::Result;
-use crate::ir::InstrLocId;
+use crate::error::{Limit, ParseError, Result, ValidationError};
+use crate::ir::{
+    dfs_in_order, dfs_pre_order_mut, AtomicNotify, AtomicRmw, AtomicWait, Block, Call, Cmpxchg,
+    IfElse, Instr, InstrLocId, InstrPositions, InstrSeq, InstrSeqId, Load, LoadSimd, LocalId, Loop,
+    MemArg, RefFunc, Store, Visitor, VisitorMut,
+};
 use crate::module::imports::ImportId;
-use crate::module::Module;
-use crate::parse::IndicesToIds;
+use crate::module::{Module, ModuleLocals, ModuleTypes};
+use crate::parse::{capacity_hint, IndicesToIds};
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
 use crate::ty::TypeId;
 use crate::ty::ValType;
+use crate::{FunctionBuilder, GlobalKind, InitExpr};
+use anyhow::bail;
 use std::cmp;
-use wasmparser::{FuncValidator, FunctionBody, ValidatorResources};
+use std::collections::HashMap;
+use wasmparser::{BinaryReader, FuncValidator, FunctionBody, SectionReader, ValidatorResources};
 
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
+use self::local_function::instr_mnemonic;
 pub use self::local_function::LocalFunction;
 
 /// A function identifier.
@@ -26,7 +34,7 @@ pub type FunctionId = Id<Function>;
 /// A wasm function.
 ///
 /// Either defined locally or externally and then imported; see `FunctionKind`.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Function {
     // NB: Not public so that it can't get out of sync with the arena that this
     // function lives within.
@@ -72,7 +80,7 @@ impl Function {
 }
 
 /// The local- or external-specific bits of a function.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum FunctionKind {
     /// An externally defined, imported wasm function.
     Import(ImportedFunction),
@@ -126,7 +134,7 @@ impl FunctionKind {
 }
 
 /// An externally defined, imported function.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ImportedFunction {
     /// The import that brings this function into the module.
     pub import: ImportId,
@@ -135,13 +143,17 @@ pub struct ImportedFunction {
 }
 
 /// The set of functions within a module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleFunctions {
     /// The arena containing this module's functions.
     arena: TombstoneArena<Function>,
 }
 
 impl ModuleFunctions {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Construct a new, empty set of functions for a module.
     pub fn new() -> ModuleFunctions {
         Default::default()
@@ -215,7 +227,10 @@ impl ModuleFunctions {
         self.arena.par_iter().map(|(_, f)| f)
     }
 
-    /// Get an iterator of this module's local functions
+    /// Get an iterator of this module's local functions.
+    ///
+    /// Imported functions are skipped, so this only yields the functions
+    /// defined in this module, along with their ids.
     pub fn iter_local(&self) -> impl Iterator<Item = (FunctionId, &LocalFunction)> {
         self.iter().filter_map(|f| match &f.kind {
             FunctionKind::Local(local) => Some((f.id(), local)),
@@ -223,7 +238,8 @@ impl ModuleFunctions {
         })
     }
 
-    /// Get a parallel iterator of this module's local functions
+    /// Get a parallel iterator of this module's local functions, skipping
+    /// imported functions.
     ///
     /// Requires the `parallel` feature of this crate to be enabled.
     #[cfg(feature = "parallel")]
@@ -247,7 +263,8 @@ impl ModuleFunctions {
         self.arena.par_iter_mut().map(|(_, f)| f)
     }
 
-    /// Get an iterator of this module's local functions
+    /// Get a mutable iterator of this module's local functions, skipping
+    /// imported functions.
     pub fn iter_local_mut(&mut self) -> impl Iterator<Item = (FunctionId, &mut LocalFunction)> {
         self.iter_mut().filter_map(|f| {
             let id = f.id();
@@ -258,7 +275,30 @@ impl ModuleFunctions {
         })
     }
 
-    /// Get a parallel iterator of this module's local functions
+    /// Replace every function that hasn't been parsed with an `unreachable`
+    /// stub.
+    pub(crate) fn stub_uninitialized(
+        &mut self,
+        types: &mut ModuleTypes,
+        locals: &mut ModuleLocals,
+    ) {
+        for (_, func) in self.arena.iter_mut() {
+            let ty = match func.kind {
+                FunctionKind::Uninitialized(ty) => ty,
+                _ => continue,
+            };
+            let args = types
+                .get(ty)
+                .params()
+                .iter()
+                .map(|ty| locals.add(*ty))
+                .collect();
+            func.kind = FunctionKind::Local(unreachable_stub(types, ty, args));
+        }
+    }
+
+    /// Get a parallel iterator of this module's local functions, skipping
+    /// imported functions.
     ///
     /// Requires the `parallel` feature of this crate to be enabled.
     #[cfg(feature = "parallel")]
@@ -296,7 +336,391 @@ impl ModuleFunctions {
     }
 }
 
+/// The encoded sizes of a module's function bodies, as computed by
+/// `Module::code_size_report`.
+#[derive(Clone, Debug, Default)]
+pub struct CodeSizeReport {
+    /// The size of the contents of the code section, in bytes, not including
+    /// the section's own header.
+    pub total: usize,
+    /// The size of each local function's body, in bytes, including its locals
+    /// but not the size prefix in front of it.
+    pub funcs: HashMap<FunctionId, usize>,
+}
+
+impl CodeSizeReport {
+    /// Get the `n` largest functions and their sizes, largest first.
+    pub fn largest(&self, n: usize) -> Vec<(FunctionId, usize)> {
+        let mut funcs: Vec<_> = self.funcs.iter().map(|(id, size)| (*id, *size)).collect();
+        funcs.sort_by_key(|(id, size)| (cmp::Reverse(*size), *id));
+        funcs.truncate(n);
+        funcs
+    }
+}
+
+/// Where an instruction is within a module, as found by
+/// `Module::find_instr` and passed along by `Module::visit_all`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstrPosition {
+    /// The function containing the instruction.
+    pub func: FunctionId,
+    /// The instruction sequence containing the instruction.
+    pub seq: InstrSeqId,
+    /// The index of the instruction within `seq`.
+    pub index: usize,
+}
+
+/// Push the sequences nested in `instr` onto a `visit_all` stack, so that
+/// they're visited next, in order.
+fn push_nested_seqs(stack: &mut Vec<(InstrSeqId, usize)>, instr: &Instr) {
+    match instr {
+        Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => stack.push((*seq, 0)),
+        Instr::IfElse(IfElse {
+            consequent,
+            alternative,
+        }) => {
+            stack.push((*alternative, 0));
+            stack.push((*consequent, 0));
+        }
+        _ => {}
+    }
+}
+
 impl Module {
+    /// Find the instruction with the given `InstrLocId`, such as one that a
+    /// pass logged, and return where it is in this module.
+    ///
+    /// Returns `None` for the default `InstrLocId` of instructions that were
+    /// added rather than parsed, and if no instruction has the location
+    /// anymore. If several instructions share it, for example because a pass
+    /// copied one, any one of them may be returned.
+    pub fn find_instr(&self, loc: InstrLocId) -> Option<InstrPosition> {
+        if loc.is_default() {
+            return None;
+        }
+        self.funcs.iter_local().find_map(|(func, local)| {
+            local.builder().arena.iter().find_map(|(seq, instrs)| {
+                let index = instrs
+                    .iter()
+                    .position(|(_, l)| !l.is_default() && l.data() == loc.data())?;
+                Some(InstrPosition { func, seq, index })
+            })
+        })
+    }
+
+    /// Describe the instruction with the given `InstrLocId` for logs and
+    /// diagnostics, like "func 12, seq 3, instr 7 (offset 0x1a2)".
+    ///
+    /// Functions are named by their name if they have one, and by their id's
+    /// index otherwise. See `find_instr` to get the position as ids instead.
+    pub fn describe_loc(&self, loc: InstrLocId) -> String {
+        if loc.is_default() {
+            return "synthesized instruction (no original offset)".to_string();
+        }
+        let pos = match self.find_instr(loc) {
+            Some(pos) => pos,
+            None => return format!("offset {:#x} (no longer in the module)", loc.data()),
+        };
+        let func = match &self.funcs.get(pos.func).name {
+            Some(name) => format!("func `{}`", name),
+            None => format!("func {}", pos.func.index()),
+        };
+        format!(
+            "{}, seq {}, instr {} (offset {:#x})",
+            func,
+            pos.seq.index(),
+            pos.index,
+            loc.data()
+        )
+    }
+
+    /// Call `visit` on every instruction in this module's local functions,
+    /// along with the instruction's position, so that whole-module analyses
+    /// don't need to keep track of which function and sequence they're in.
+    ///
+    /// Each function is traversed in the same order as `dfs_in_order`: the
+    /// instructions of a nested block, loop or `if` come right after the
+    /// instruction that contains them, and before the rest of its sequence.
+    /// Sequences that aren't reachable from a function's entry block are
+    /// skipped.
+    pub fn visit_all(&self, mut visit: impl FnMut(InstrPosition, &Instr, &InstrLocId)) {
+        struct VisitAll<'a, F> {
+            func: FunctionId,
+            positions: InstrPositions,
+            visit: &'a mut F,
+        }
+
+        impl<'instr, F> Visitor<'instr> for VisitAll<'_, F>
+        where
+            F: FnMut(InstrPosition, &Instr, &InstrLocId),
+        {
+            fn start_instr_seq(&mut self, seq: &'instr InstrSeq) {
+                self.positions.start_instr_seq(seq);
+            }
+
+            fn end_instr_seq(&mut self, _: &'instr InstrSeq) {
+                self.positions.end_instr_seq();
+            }
+
+            fn visit_instr(&mut self, instr: &'instr Instr, loc: &'instr InstrLocId) {
+                let (seq, index) = self.positions.next_instr();
+                (self.visit)(
+                    InstrPosition {
+                        func: self.func,
+                        seq,
+                        index,
+                    },
+                    instr,
+                    loc,
+                );
+            }
+        }
+
+        for (func, local) in self.funcs.iter_local() {
+            let mut visitor = VisitAll {
+                func,
+                positions: InstrPositions::default(),
+                visit: &mut visit,
+            };
+            dfs_in_order(&mut visitor, local, local.entry_block());
+        }
+    }
+
+    /// Like `visit_all`, but with mutable access to each instruction.
+    ///
+    /// If `visit` changes which sequences a block, loop or `if` refers to, the
+    /// traversal continues into the new ones.
+    pub fn visit_all_mut(
+        &mut self,
+        mut visit: impl FnMut(InstrPosition, &mut Instr, &mut InstrLocId),
+    ) {
+        for (func, local) in self.funcs.iter_local_mut() {
+            let mut stack = vec![(local.entry_block(), 0)];
+            while let Some(top) = stack.last_mut() {
+                let (seq, index) = *top;
+                let (instr, loc) = match local.block_mut(seq).instrs.get_mut(index) {
+                    Some((instr, loc)) => (instr, loc),
+                    None => {
+                        stack.pop();
+                        continue;
+                    }
+                };
+                top.1 += 1;
+                visit(InstrPosition { func, seq, index }, instr, loc);
+                push_nested_seqs(&mut stack, instr);
+            }
+        }
+    }
+
+    /// Find the first atomic memory access that isn't naturally aligned, or,
+    /// with `ModuleConfig::strict_atomics`, that accesses a memory which isn't
+    /// shared.
+    ///
+    /// Unlike ordinary loads and stores, whose alignment is only a hint,
+    /// atomic accesses must be aligned to exactly their width.
+    pub(crate) fn invalid_atomic_access(&self) -> Option<ValidationError> {
+        let mut error = None;
+        self.visit_all(|pos, instr, _| {
+            if error.is_some() {
+                return;
+            }
+            let (memory, arg, width) = match instr {
+                Instr::Load(e) if e.kind.atomic() => (e.memory, e.arg, e.kind.width()),
+                Instr::Store(e) if e.kind.atomic() => (e.memory, e.arg, e.kind.width()),
+                Instr::AtomicRmw(e) => (e.memory, e.arg, e.width.bytes()),
+                Instr::Cmpxchg(e) => (e.memory, e.arg, e.width.bytes()),
+                Instr::AtomicNotify(e) => (e.memory, e.arg, 4),
+                Instr::AtomicWait(e) => (e.memory, e.arg, if e.sixty_four { 8 } else { 4 }),
+                _ => return,
+            };
+            let name = instr_mnemonic(self, instr);
+            let message = if arg.align != width {
+                format!(
+                    "`{}` must be aligned to exactly {} bytes, but is aligned to {}",
+                    name, width, arg.align
+                )
+            } else if self.config.strict_atomics && !self.memories.get(memory).shared {
+                format!("`{}` accesses a memory that isn't shared", name)
+            } else {
+                return;
+            };
+            error = Some(ValidationError {
+                func: pos.func,
+                seq: pos.seq,
+                index: pos.index,
+                message,
+            });
+        });
+        error
+    }
+
+    /// Compute the exact encoded size of this module's code section, and of
+    /// each local function's body within it.
+    ///
+    /// This emits the module's standard sections into a scratch buffer, so it
+    /// costs about as much as `emit_wasm`. Unlike `LocalFunction::size`, which is
+    /// only an estimate, this takes the actual encoding of every instruction
+    /// and index into account.
+    pub fn code_size_report(&self) -> CodeSizeReport {
+        let indices = &mut IdsToIndices::default();
+        self.emit_scratch_module(indices);
+
+        let mut report = CodeSizeReport::default();
+        for (id, _) in self.funcs.iter_local() {
+            if let Some(range) = indices.get_func_code_range(id) {
+                report.funcs.insert(id, range.len());
+            }
+        }
+        if report.funcs.is_empty() {
+            return report;
+        }
+
+        // The code section's contents are the number of bodies followed by
+        // each body prefixed with its size.
+        let mut prefixes = Vec::new();
+        let mut prefix_encoder = Encoder::new(&mut prefixes);
+        prefix_encoder.usize(report.funcs.len());
+        for size in report.funcs.values() {
+            prefix_encoder.usize(*size);
+            report.total += size;
+        }
+        report.total += prefixes.len();
+        report
+    }
+
+    /// Redirect every direct `call` of `from` in this module's local functions
+    /// to `to`, returning the number of call sites that were rewritten.
+    ///
+    /// If `update_elements` is set, other references to `from` are redirected
+    /// to `to` as well, so that indirect calls through tables are redirected
+    /// too: members of element segments, `ref.func` instructions, and global
+    /// initializers. Such references aren't included in the returned count.
+    ///
+    /// Returns an error, without changing anything, if `from` and `to` have
+    /// different signatures.
+    pub fn replace_all_calls(
+        &mut self,
+        from: FunctionId,
+        to: FunctionId,
+        update_elements: bool,
+    ) -> Result<usize> {
+        let from_ty = self.types.get(self.funcs.get(from).ty());
+        let to_ty = self.types.get(self.funcs.get(to).ty());
+        if from_ty != to_ty {
+            bail!(
+                "cannot replace calls to a function of type {:?} -> {:?} with \
+                 calls to a function of type {:?} -> {:?}",
+                from_ty.params(),
+                from_ty.results(),
+                to_ty.params(),
+                to_ty.results(),
+            );
+        }
+
+        let mut replace = ReplaceCalls {
+            from,
+            to,
+            update_refs: update_elements,
+            count: 0,
+        };
+        for (_, func) in self.funcs.iter_local_mut() {
+            let entry = func.entry_block();
+            dfs_pre_order_mut(&mut replace, func, entry);
+        }
+
+        if update_elements {
+            for elem in self.elements.iter_mut() {
+                for member in elem.members.iter_mut() {
+                    if *member == Some(from) {
+                        *member = Some(to);
+                    }
+                }
+            }
+            let globals: Vec<_> = self.globals.iter().map(|g| g.id()).collect();
+            for id in globals {
+                if let GlobalKind::Local(InitExpr::RefFunc(func)) =
+                    &mut self.globals.get_mut(id).kind
+                {
+                    if *func == from {
+                        *func = to;
+                    }
+                }
+            }
+        }
+
+        return Ok(replace.count);
+
+        struct ReplaceCalls {
+            from: FunctionId,
+            to: FunctionId,
+            update_refs: bool,
+            count: usize,
+        }
+
+        impl VisitorMut for ReplaceCalls {
+            fn visit_call_mut(&mut self, call: &mut Call) {
+                if call.func == self.from {
+                    call.func = self.to;
+                    self.count += 1;
+                }
+            }
+
+            fn visit_ref_func_mut(&mut self, instr: &mut RefFunc) {
+                if self.update_refs && instr.func == self.from {
+                    instr.func = self.to;
+                }
+            }
+        }
+    }
+
+    /// Call `f` with the `MemArg` of every memory access in this module's local
+    /// functions: loads and stores, atomic operations, and SIMD loads.
+    ///
+    /// This makes it easy to rewrite alignment hints, for example to force
+    /// every access to its natural alignment. Alignment is only a hint, so
+    /// changing it doesn't change what the module does, but note that atomic
+    /// accesses must always be naturally aligned to validate. Like everywhere
+    /// else in walrus, `MemArg::align` is in bytes rather than a power of two.
+    pub fn each_memarg_mut(&mut self, f: impl FnMut(&mut MemArg)) {
+        let mut visitor = EachMemArg(f);
+        for (_, func) in self.funcs.iter_local_mut() {
+            let entry = func.entry_block();
+            dfs_pre_order_mut(&mut visitor, func, entry);
+        }
+
+        struct EachMemArg<F>(F);
+
+        impl<F: FnMut(&mut MemArg)> VisitorMut for EachMemArg<F> {
+            fn visit_load_mut(&mut self, instr: &mut Load) {
+                (self.0)(&mut instr.arg);
+            }
+
+            fn visit_store_mut(&mut self, instr: &mut Store) {
+                (self.0)(&mut instr.arg);
+            }
+
+            fn visit_atomic_rmw_mut(&mut self, instr: &mut AtomicRmw) {
+                (self.0)(&mut instr.arg);
+            }
+
+            fn visit_cmpxchg_mut(&mut self, instr: &mut Cmpxchg) {
+                (self.0)(&mut instr.arg);
+            }
+
+            fn visit_atomic_notify_mut(&mut self, instr: &mut AtomicNotify) {
+                (self.0)(&mut instr.arg);
+            }
+
+            fn visit_atomic_wait_mut(&mut self, instr: &mut AtomicWait) {
+                (self.0)(&mut instr.arg);
+            }
+
+            fn visit_load_simd_mut(&mut self, instr: &mut LoadSimd) {
+                (self.0)(&mut instr.arg);
+            }
+        }
+    }
+
     /// Declare local functions after seeing the `function` section of a wasm
     /// executable.
     pub(crate) fn declare_local_functions(
@@ -305,6 +729,11 @@ impl Module {
         ids: &mut IndicesToIds,
     ) -> Result<()> {
         log::debug!("parse function section");
+        let total = self.funcs.arena.len() as u64 + u64::from(section.get_count());
+        self.config.check_limit(Limit::Functions, total)?;
+        let capacity = capacity_hint(section.get_count(), section.range(), 1);
+        self.funcs.arena.reserve(capacity);
+        ids.reserve_funcs(capacity);
         for func in section {
             let ty = ids.get_type(func?)?;
             let id = self
@@ -321,14 +750,29 @@ impl Module {
     }
 
     /// Add the locally defined functions in the wasm module to this instance.
+    ///
+    /// If `errors` is `Some`, functions which fail to parse are replaced with
+    /// an `unreachable` stub and their errors recorded there, rather than
+    /// returned.
     pub(crate) fn parse_local_functions(
         &mut self,
         functions: Vec<(FunctionBody<'_>, FuncValidator<ValidatorResources>)>,
         indices: &mut IndicesToIds,
         on_instr_pos: Option<&(dyn Fn(&usize) -> InstrLocId + Sync + Send + 'static)>,
+        mut errors: Option<&mut Vec<ParseError>>,
     ) -> Result<()> {
         log::debug!("parse code section");
-        let num_imports = self.funcs.arena.len() - functions.len();
+        // Note that this isn't necessarily `arena.len() - functions.len()`,
+        // since we may be recovering from the code section being cut short.
+        let num_imports = self
+            .funcs
+            .arena
+            .iter()
+            .filter(|(_, f)| match f.kind {
+                FunctionKind::Import(_) => true,
+                _ => false,
+            })
+            .count();
 
         // First up serially create corresponding `LocalId` instances for all
         // functions as well as extract the operators parser for each function.
@@ -366,19 +810,19 @@ impl Module {
 
             // Next up comes all the locals of the function.
             let mut reader = body.get_binary_reader();
-            for _ in 0..reader.read_var_u32()? {
-                let pos = reader.original_position();
-                let count = reader.read_var_u32()?;
-                let ty = reader.read_type()?;
-                validator.define_locals(pos, count, ty)?;
-                let ty = ValType::parse(&ty)?;
-                for _ in 0..count {
-                    let local_id = self.locals.add(ty);
-                    let idx = indices.push_local(id, local_id);
-                    if self.config.generate_synthetic_names_for_anonymous_items {
-                        let name = format!("l{}", idx);
-                        self.locals.get_mut(local_id).name = Some(name);
+            indices.set_func_code_offset(id, reader.original_position());
+            if let Err(error) = self.parse_locals(id, &mut reader, &mut validator, indices) {
+                match errors.as_mut() {
+                    Some(errors) => {
+                        errors.push(ParseError {
+                            func: Some(id),
+                            error,
+                        });
+                        let stub = unreachable_stub(&mut self.types, ty, args);
+                        self.funcs.arena[id].kind = FunctionKind::Local(stub);
+                        continue;
                     }
+                    None => return Err(error),
                 }
             }
 
@@ -391,6 +835,8 @@ impl Module {
             .map(|(id, body, args, ty, validator)| {
                 (
                     id,
+                    ty,
+                    args.clone(),
                     LocalFunction::parse(
                         self,
                         indices,
@@ -407,13 +853,62 @@ impl Module {
 
         // After all the function bodies are collected and finished push them
         // into our function arena.
-        for (id, func) in results {
-            let func = func?;
+        for (id, ty, args, func) in results {
+            let func = match (func, errors.as_mut()) {
+                (Ok(func), _) => func,
+                (Err(error), Some(errors)) => {
+                    errors.push(ParseError {
+                        func: Some(id),
+                        error,
+                    });
+                    unreachable_stub(&mut self.types, ty, args)
+                }
+                (Err(error), None) => return Err(error),
+            };
             self.funcs.arena[id].kind = FunctionKind::Local(func);
         }
 
         Ok(())
     }
+
+    fn parse_locals(
+        &mut self,
+        id: FunctionId,
+        reader: &mut BinaryReader<'_>,
+        validator: &mut FuncValidator<ValidatorResources>,
+        indices: &mut IndicesToIds,
+    ) -> Result<()> {
+        let mut total = 0;
+        for _ in 0..reader.read_var_u32()? {
+            let pos = reader.original_position();
+            let count = reader.read_var_u32()?;
+            total += u64::from(count);
+            self.config.check_limit(Limit::LocalsPerFunction, total)?;
+            let ty = reader.read_type()?;
+            validator.define_locals(pos, count, ty)?;
+            let ty = ValType::parse(&ty)?;
+            for _ in 0..count {
+                let local_id = self.locals.add(ty);
+                let idx = indices.push_local(id, local_id);
+                if self.config.generate_synthetic_names_for_anonymous_items {
+                    let name = format!("l{}", idx);
+                    self.locals.get_mut(local_id).name = Some(name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Create a function of type `ty` whose body is just `unreachable`, to stand in
+/// for one which failed to parse.
+fn unreachable_stub(types: &mut ModuleTypes, ty: TypeId, args: Vec<LocalId>) -> LocalFunction {
+    let ty = types.get(ty);
+    let params = ty.params().to_vec();
+    let results = ty.results().to_vec();
+    let mut builder = FunctionBuilder::new(types, &params, &results);
+    builder.func_body().unreachable();
+    builder.local_func(args)
 }
 
 fn used_local_functions<'a>(cx: &mut EmitContext<'a>) -> Vec<(FunctionId, &'a LocalFunction, u64)> {
@@ -435,7 +930,15 @@ fn used_local_functions<'a>(cx: &mut EmitContext<'a>) -> Vec<(FunctionId, &'a Lo
     // the function as their level of granularity for parallelism. We want
     // larger functions compiled before smaller ones because they will take
     // longer to compile.
-    functions.sort_by_key(|(id, _, size)| (cmp::Reverse(*size), *id));
+    //
+    // Unless we were asked to keep the original order, that is. Ids are
+    // allocated in the order functions are parsed and then added, so sorting
+    // by id gives us exactly that.
+    if cx.module.config.preserve_function_order {
+        functions.sort_by_key(|(id, _, _)| *id);
+    } else {
+        functions.sort_by_key(|(id, _, size)| (cmp::Reverse(*size), *id));
+    }
 
     functions
 }
@@ -477,7 +980,13 @@ impl Emit for ModuleFunctions {
                 let mut map = if generate_map { Some(Vec::new()) } else { None };
 
                 let (used_locals, local_indices) = func.emit_locals(cx.module, &mut encoder);
-                func.emit_instructions(cx.indices, &local_indices, &mut encoder, map.as_mut());
+                func.emit_instructions(
+                    cx.indices,
+                    &local_indices,
+                    &mut encoder,
+                    map.as_mut(),
+                    None,
+                );
                 (wasm, id, used_locals, local_indices, map)
             })
             .collect::<Vec<_>>();
@@ -487,6 +996,8 @@ impl Emit for ModuleFunctions {
             cx.encoder.usize(wasm.len());
             let code_offset = cx.encoder.pos();
             cx.encoder.raw(&wasm);
+            cx.indices
+                .set_func_code_range(id, code_offset..code_offset + wasm.len());
             if let Some(map) = map {
                 collect_non_default_code_offsets(&mut cx.code_transform, code_offset, map);
             }
@@ -1,14 +1,17 @@
 //! Globals within a wasm module.
 use crate::emit::{Emit, EmitContext, Section};
+use crate::ir::{dfs_in_order, ConstValue, GlobalSet, Visitor};
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
-use crate::{ImportId, InitExpr, Module, Result, ValType};
+use crate::{ExportItem, ImportId, InitExpr, Module, Result, ValType};
+use anyhow::bail;
+use std::collections::{HashMap, HashSet};
 
 /// The id of a global.
 pub type GlobalId = Id<Global>;
 
 /// A wasm global.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Global {
     // NB: Not public so that it can't get out of sync with the arena this is
     // contained within.
@@ -27,7 +30,7 @@ pub struct Global {
 impl Tombstone for Global {}
 
 /// The different kinds of globals a wasm module can have
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum GlobalKind {
     /// An imported global without a known initializer
     Import(ImportId),
@@ -40,6 +43,40 @@ impl Global {
     pub fn id(&self) -> GlobalId {
         self.id
     }
+
+    /// Is this global imported?
+    pub fn is_imported(&self) -> bool {
+        self.import().is_some()
+    }
+
+    /// Get the import this global comes from, if it is imported.
+    pub fn import(&self) -> Option<ImportId> {
+        match self.kind {
+            GlobalKind::Import(import) => Some(import),
+            GlobalKind::Local(_) => None,
+        }
+    }
+
+    /// Get this global's initializer, if it is defined locally rather than
+    /// imported.
+    pub fn init_expr(&self) -> Option<&InitExpr> {
+        match &self.kind {
+            GlobalKind::Local(init) => Some(init),
+            GlobalKind::Import(_) => None,
+        }
+    }
+
+    /// Get the value of this global's initializer, if it is a constant.
+    ///
+    /// Returns `None` for imported globals and for globals initialized from
+    /// another global or to a reference. Note that a mutable global may start
+    /// out with this value but be changed at runtime.
+    pub fn const_value(&self) -> Option<ConstValue> {
+        match self.kind {
+            GlobalKind::Local(InitExpr::Value(value)) => Some(value.into()),
+            _ => None,
+        }
+    }
 }
 
 impl Emit for Global {
@@ -50,13 +87,17 @@ impl Emit for Global {
 }
 
 /// The set of globals in each function in this module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleGlobals {
     /// The arena where the globals are stored.
     arena: TombstoneArena<Global>,
 }
 
 impl ModuleGlobals {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Adds a new imported global to this list.
     pub fn add_import(&mut self, ty: ValType, mutable: bool, import_id: ImportId) -> GlobalId {
         self.arena.alloc_with_id(|id| Global {
@@ -100,9 +141,144 @@ impl ModuleGlobals {
     pub fn iter(&self) -> impl Iterator<Item = &Global> {
         self.arena.iter().map(|(_, f)| f)
     }
+
+    /// Get an iterator of the globals defined in this module, skipping
+    /// imported globals.
+    pub fn iter_local(&self) -> impl Iterator<Item = &Global> {
+        self.iter().filter(|g| !g.is_imported())
+    }
+
+    /// Get an iterator of this module's imported globals.
+    pub fn iter_imported(&self) -> impl Iterator<Item = &Global> {
+        self.iter().filter(|g| g.is_imported())
+    }
 }
 
 impl Module {
+    /// Evaluate the initializers of this module's globals, returning the
+    /// value that each global starts out with.
+    ///
+    /// A `global.get` in an initializer is resolved to the value of the global
+    /// it refers to, as long as that can be worked out in turn. Globals whose
+    /// value depends on an imported global are left out of the map, as are
+    /// globals initialized to a reference. Mutable globals are included, with
+    /// the value they have at instantiation.
+    ///
+    /// This is handy for finding things like the heap base that toolchains
+    /// put in globals, without running the module.
+    pub fn evaluate_globals(&self) -> HashMap<GlobalId, ConstValue> {
+        self.globals
+            .iter_local()
+            .filter_map(|global| Some((global.id(), self.evaluate_global(global.id())?)))
+            .collect()
+    }
+
+    pub(crate) fn evaluate_global(&self, mut id: GlobalId) -> Option<ConstValue> {
+        // Valid modules can't have cycles between global initializers, but
+        // modules built up with walrus might, so give up after following as
+        // many `global.get`s as there are globals.
+        for _ in self.globals.iter() {
+            match self.globals.get(id).kind {
+                GlobalKind::Local(InitExpr::Value(value)) => return Some(value.into()),
+                GlobalKind::Local(InitExpr::Global(next)) => id = next,
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Find the globals that the module's own code ever sets, with
+    /// `global.set`.
+    ///
+    /// A global that isn't in this set never changes from the value it's
+    /// initialized with, as far as the module itself goes, even if it's
+    /// declared mutable. The host can still change exported and imported
+    /// mutable globals, though; use `externally_mutable_globals` to find those
+    /// before treating a global as a constant.
+    pub fn mutated_globals(&self) -> HashSet<GlobalId> {
+        struct GlobalSets(HashSet<GlobalId>);
+
+        impl<'instr> Visitor<'instr> for GlobalSets {
+            fn visit_global_set(&mut self, set: &GlobalSet) {
+                self.0.insert(set.global);
+            }
+        }
+
+        let mut sets = GlobalSets(HashSet::new());
+        for (_, func) in self.funcs.iter_local() {
+            dfs_in_order(&mut sets, func, func.entry_block());
+        }
+        sets.0
+    }
+
+    /// Find the mutable globals that the host can change, because they are
+    /// either imported or exported.
+    ///
+    /// These globals may change at any time that control is outside of the
+    /// module, whether or not the module sets them itself.
+    pub fn externally_mutable_globals(&self) -> HashSet<GlobalId> {
+        let exported = self.exports.iter().filter_map(|e| match e.item {
+            ExportItem::Global(id) => Some(id),
+            _ => None,
+        });
+        let imported = self.globals.iter_imported().map(|g| g.id());
+        exported
+            .chain(imported)
+            .filter(|id| self.globals.get(*id).mutable)
+            .collect()
+    }
+
+    /// Change the module and name that an imported global is imported from,
+    /// such as when a toolchain renames `__memory_base`.
+    ///
+    /// Returns an error if the global isn't imported, or if something else is
+    /// already imported under the new name.
+    pub fn rename_global_import(
+        &mut self,
+        global: GlobalId,
+        module: &str,
+        name: &str,
+    ) -> Result<()> {
+        let import = match self.globals.get(global).import() {
+            Some(import) => import,
+            None => bail!("cannot rename {:?} because it is not imported", global),
+        };
+        if let Some(other) = self.imports.find(module, name) {
+            if other != import {
+                bail!(
+                    "cannot rename {:?}: `{}.{}` is already imported",
+                    global,
+                    module,
+                    name
+                );
+            }
+        }
+
+        let import = self.imports.get_mut(import);
+        import.module = module.to_string();
+        import.name = name.to_string();
+        Ok(())
+    }
+
+    /// Turn an imported global into one defined by this module, initialized
+    /// with `init`, and remove its import.
+    ///
+    /// This is useful for self-contained builds, where things like
+    /// `__stack_pointer` would otherwise be provided by the host. The
+    /// `GlobalId` stays the same, so all `global.get`s and `global.set`s of
+    /// it keep working.
+    ///
+    /// Returns an error if the global isn't imported.
+    pub fn internalize_global_import(&mut self, global: GlobalId, init: InitExpr) -> Result<()> {
+        let import = match self.globals.get(global).import() {
+            Some(import) => import,
+            None => bail!("cannot internalize {:?} because it is not imported", global),
+        };
+        self.imports.delete(import);
+        self.globals.get_mut(global).kind = GlobalKind::Local(init);
+        Ok(())
+    }
+
     /// Construct a new, empty set of globals for a module.
     pub(crate) fn parse_globals(
         &mut self,
@@ -112,11 +288,15 @@ impl Module {
         log::debug!("parse global section");
         for g in section {
             let g = g?;
-            let id = self.globals.add_local(
-                ValType::parse(&g.ty.content_type)?,
-                g.ty.mutable,
-                InitExpr::eval(&g.init_expr, ids)?,
-            );
+            let init = match InitExpr::eval(&g.init_expr, ids)? {
+                InitExpr::Value(value) if self.config.canonicalize_nans => {
+                    InitExpr::Value(value.canonicalize_nan())
+                }
+                init => init,
+            };
+            let id =
+                self.globals
+                    .add_local(ValType::parse(&g.ty.content_type)?, g.ty.mutable, init);
             ids.push_global(id);
         }
         Ok(())
@@ -1,9 +1,11 @@
 //! A wasm module's imports.
 
 use crate::emit::{Emit, EmitContext, Section};
+use crate::ir::{dfs_in_order, Visitor};
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
-use crate::{FunctionId, GlobalId, MemoryId, Result, TableId};
+use crate::{ActiveDataLocation, DataKind, ElementKind, ExportItem, GlobalKind, InitExpr};
+use crate::{FunctionId, GlobalId, Limit, MemoryId, Result, TableId};
 use crate::{Module, TypeId, ValType};
 use anyhow::bail;
 
@@ -49,13 +51,31 @@ pub enum ImportKind {
     Global(GlobalId),
 }
 
+/// The number of imports of each kind in a module, as returned by
+/// `ModuleImports::count_by_kind`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportCounts {
+    /// The number of imported functions.
+    pub functions: usize,
+    /// The number of imported tables.
+    pub tables: usize,
+    /// The number of imported memories.
+    pub memories: usize,
+    /// The number of imported globals.
+    pub globals: usize,
+}
+
 /// The set of imports in a module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleImports {
     arena: TombstoneArena<Import>,
 }
 
 impl ModuleImports {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Gets a reference to an import given its id
     pub fn get(&self, id: ImportId) -> &Import {
         &self.arena[id]
@@ -69,7 +89,9 @@ impl ModuleImports {
     /// Removes an import from this module.
     ///
     /// It is up to you to ensure that any potential references to the deleted
-    /// import are also removed, eg `get_global` expressions.
+    /// import are also removed, eg `get_global` expressions. See
+    /// `Module::delete_import` for a checked version of this method that also
+    /// removes the imported item.
     pub fn delete(&mut self, id: ImportId) {
         self.arena.delete(id);
     }
@@ -103,6 +125,38 @@ impl ModuleImports {
 
         Some(import?.0)
     }
+
+    /// Iterate over the imports from the given `module`, such as
+    /// `wasi_snapshot_preview1`, in the order they appear in the module.
+    pub fn by_module<'a>(&'a self, module: &'a str) -> impl Iterator<Item = &'a Import> + 'a {
+        self.iter().filter(move |import| import.module == module)
+    }
+
+    /// Iterate over the distinct module names that items are imported from,
+    /// in the order of their first import.
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        let mut modules: Vec<&str> = Vec::new();
+        for import in self.iter() {
+            if !modules.contains(&&import.module[..]) {
+                modules.push(&import.module);
+            }
+        }
+        modules.into_iter()
+    }
+
+    /// Count this module's imports of each kind.
+    pub fn count_by_kind(&self) -> ImportCounts {
+        let mut counts = ImportCounts::default();
+        for import in self.iter() {
+            match import.kind {
+                ImportKind::Function(_) => counts.functions += 1,
+                ImportKind::Table(_) => counts.tables += 1,
+                ImportKind::Memory(_) => counts.memories += 1,
+                ImportKind::Global(_) => counts.globals += 1,
+            }
+        }
+        counts
+    }
 }
 
 impl Module {
@@ -123,9 +177,13 @@ impl Module {
                         entry.field.expect("module linking not supported"),
                         ty,
                     );
-                    ids.push_func(id.0);
+                    let idx = ids.push_func(id.0);
+                    self.config
+                        .check_limit(Limit::Functions, u64::from(idx) + 1)?;
                 }
                 wasmparser::ImportSectionEntryType::Table(t) => {
+                    self.config
+                        .check_limit(Limit::TableSize, t.limits.initial.into())?;
                     let ty = ValType::parse(&t.element_type)?;
                     let id = self.add_import_table(
                         entry.module,
@@ -143,6 +201,8 @@ impl Module {
                             bail!("64-bit memories not supported")
                         }
                     };
+                    self.config
+                        .check_limit(Limit::MemoryPages, limits.initial.into())?;
                     let id = self.add_import_memory(
                         entry.module,
                         entry.field.expect("module linking not supported"),
@@ -174,6 +234,111 @@ impl Module {
         Ok(())
     }
 
+    /// Removes an import from this module, along with the function, table,
+    /// memory, or global that it brings into the module.
+    ///
+    /// Unlike `ModuleImports::delete`, this will not leave dangling references
+    /// behind: if the imported item is still referenced anywhere in the module
+    /// (by an instruction, an export, the start function, a global
+    /// initializer, or a data/element segment) then nothing is removed and an
+    /// error is returned instead.
+    pub fn delete_import(&mut self, id: ImportId) -> Result<()> {
+        let import = self.imports.get(id);
+        if let Some(user) = self.import_user(&import.kind) {
+            bail!(
+                "cannot delete import `{}.{}` because it is still used by {}",
+                import.module,
+                import.name,
+                user,
+            );
+        }
+
+        let kind = import.kind.clone();
+        self.imports.delete(id);
+        match kind {
+            ImportKind::Function(f) => self.funcs.delete(f),
+            ImportKind::Table(t) => self.tables.delete(t),
+            ImportKind::Memory(m) => self.memories.delete(m),
+            ImportKind::Global(g) => self.globals.delete(g),
+        }
+        Ok(())
+    }
+
+    /// Find something in this module that references the given imported item,
+    /// returning a description of it suitable for error messages.
+    fn import_user(&self, kind: &ImportKind) -> Option<String> {
+        for (id, func) in self.funcs.iter_local() {
+            let mut visitor = ImportUser { kind, used: false };
+            dfs_in_order(&mut visitor, func, func.entry_block());
+            if visitor.used {
+                return Some(match &self.funcs.get(id).name {
+                    Some(name) => format!("function `{}`", name),
+                    None => format!("function {:?}", id),
+                });
+            }
+        }
+
+        for export in self.exports.iter() {
+            let used = match (export.item, kind) {
+                (ExportItem::Function(a), ImportKind::Function(b)) => a == *b,
+                (ExportItem::Table(a), ImportKind::Table(b)) => a == *b,
+                (ExportItem::Memory(a), ImportKind::Memory(b)) => a == *b,
+                (ExportItem::Global(a), ImportKind::Global(b)) => a == *b,
+                _ => false,
+            };
+            if used {
+                return Some(format!("export `{}`", export.name));
+            }
+        }
+
+        if let (Some(start), ImportKind::Function(f)) = (self.start, kind) {
+            if start == *f {
+                return Some("the start function".to_string());
+            }
+        }
+
+        for global in self.globals.iter() {
+            let used = match (&global.kind, kind) {
+                (GlobalKind::Local(InitExpr::Global(a)), ImportKind::Global(b)) => a == b,
+                (GlobalKind::Local(InitExpr::RefFunc(a)), ImportKind::Function(b)) => a == b,
+                _ => false,
+            };
+            if used {
+                return Some(format!("the initializer of global {:?}", global.id()));
+            }
+        }
+
+        for data in self.data.iter() {
+            let used = match (&data.kind, kind) {
+                (DataKind::Active(a), ImportKind::Memory(m)) => a.memory == *m,
+                (DataKind::Active(a), ImportKind::Global(g)) => {
+                    a.location == ActiveDataLocation::Relative(*g)
+                }
+                _ => false,
+            };
+            if used {
+                return Some(format!("data segment {:?}", data.id()));
+            }
+        }
+
+        for elem in self.elements.iter() {
+            let used = match (&elem.kind, kind) {
+                (_, ImportKind::Function(f)) => elem.members.contains(&Some(*f)),
+                (ElementKind::Active { table, .. }, ImportKind::Table(t)) => table == t,
+                (ElementKind::Active { offset, .. }, ImportKind::Global(g)) => match offset {
+                    InitExpr::Global(g2) => g == g2,
+                    _ => false,
+                },
+                _ => false,
+            };
+            if used {
+                return Some(format!("element segment {:?}", elem.id()));
+            }
+        }
+
+        None
+    }
+
     /// Add an imported function to this module
     pub fn add_import_func(
         &mut self,
@@ -187,6 +352,51 @@ impl Module {
         (func, import)
     }
 
+    /// Add an imported function with the given signature to this module,
+    /// returning the function, the import, and the function's type.
+    ///
+    /// Unlike `add_import_func`, this takes the function's parameters and
+    /// results directly, reusing an existing type with that signature or
+    /// adding one if there isn't one yet.
+    ///
+    /// If `module.name` is already imported as a function with the same
+    /// signature, that import is returned rather than adding a duplicate.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `module.name` is already imported as something
+    /// other than a function, or as a function with a different signature.
+    pub fn add_import_func_with_type(
+        &mut self,
+        module: &str,
+        name: &str,
+        params: &[ValType],
+        results: &[ValType],
+    ) -> Result<(FunctionId, ImportId, TypeId)> {
+        if let Some(import) = self.imports.find(module, name) {
+            let existing = match self.imports.get(import).kind {
+                ImportKind::Function(func) => func,
+                _ => bail!(
+                    "`{}.{}` is already imported as a non-function",
+                    module,
+                    name
+                ),
+            };
+            let ty = self.funcs.get(existing).ty();
+            if self.types.params_results(ty) != (params, results) {
+                bail!(
+                    "`{}.{}` is already imported as a function with a different type",
+                    module,
+                    name
+                );
+            }
+            return Ok((existing, import, ty));
+        }
+        let ty = self.types.add(params, results);
+        let (func, import) = self.add_import_func(module, name, ty);
+        Ok((func, import, ty))
+    }
+
     /// Add an imported memory to this module
     pub fn add_import_memory(
         &mut self,
@@ -274,6 +484,29 @@ impl Emit for ModuleImports {
     }
 }
 
+struct ImportUser<'a> {
+    kind: &'a ImportKind,
+    used: bool,
+}
+
+impl<'instr> Visitor<'instr> for ImportUser<'_> {
+    fn visit_function_id(&mut self, &id: &FunctionId) {
+        self.used |= *self.kind == ImportKind::Function(id);
+    }
+
+    fn visit_table_id(&mut self, &id: &TableId) {
+        self.used |= *self.kind == ImportKind::Table(id);
+    }
+
+    fn visit_memory_id(&mut self, &id: &MemoryId) {
+        self.used |= *self.kind == ImportKind::Memory(id);
+    }
+
+    fn visit_global_id(&mut self, &id: &GlobalId) {
+        self.used |= *self.kind == ImportKind::Global(id);
+    }
+}
+
 impl From<MemoryId> for ImportKind {
     fn from(id: MemoryId) -> ImportKind {
         ImportKind::Memory(id)
@@ -297,3 +530,134 @@ impl From<TableId> for ImportKind {
         ImportKind::Table(id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FunctionBuilder;
+
+    #[test]
+    fn delete_import_removes_unused_function() {
+        let mut module = Module::default();
+        let ty = module.types.add(&[], &[]);
+        let (func, import) = module.add_import_func("env", "f", ty);
+
+        module.delete_import(import).unwrap();
+
+        assert!(module.imports.find("env", "f").is_none());
+        assert!(module.funcs.iter().all(|f| f.id() != func));
+    }
+
+    #[test]
+    fn delete_import_errors_for_called_function() {
+        let mut module = Module::default();
+        let ty = module.types.add(&[], &[]);
+        let (func, import) = module.add_import_func("env", "f", ty);
+
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+        builder.func_body().call(func);
+        builder.finish(vec![], &mut module.funcs);
+
+        assert!(module.delete_import(import).is_err());
+        assert_eq!(module.imports.find("env", "f"), Some(import));
+        assert!(module.funcs.iter().any(|f| f.id() == func));
+    }
+
+    #[test]
+    fn add_import_func_with_type() {
+        let mut module = Module::default();
+        let (func, import, ty) = module
+            .add_import_func_with_type("env", "f", &[ValType::I32], &[])
+            .unwrap();
+        assert_eq!(module.funcs.get(func).ty(), ty);
+        assert_eq!(module.types.find(&[ValType::I32], &[]), Some(ty));
+
+        let again = module
+            .add_import_func_with_type("env", "f", &[ValType::I32], &[])
+            .unwrap();
+        assert_eq!(again, (func, import, ty));
+        assert_eq!(module.imports.iter().count(), 1);
+
+        assert!(module
+            .add_import_func_with_type("env", "f", &[], &[])
+            .is_err());
+        module.add_import_memory("env", "memory", false, 1, None);
+        assert!(module
+            .add_import_func_with_type("env", "memory", &[], &[])
+            .is_err());
+    }
+
+    #[test]
+    fn by_module() {
+        let mut module = Module::default();
+        let ty = module.types.add(&[], &[]);
+        module.add_import_func("wasi_snapshot_preview1", "fd_write", ty);
+        module.add_import_memory("env", "memory", false, 1, None);
+        module.add_import_func("wasi_snapshot_preview1", "proc_exit", ty);
+
+        let names: Vec<_> = module
+            .imports
+            .by_module("wasi_snapshot_preview1")
+            .map(|i| &i.name[..])
+            .collect();
+        assert_eq!(names, ["fd_write", "proc_exit"]);
+        assert_eq!(module.imports.by_module("wasi_unstable").count(), 0);
+
+        let modules: Vec<_> = module.imports.modules().collect();
+        assert_eq!(modules, ["wasi_snapshot_preview1", "env"]);
+    }
+
+    #[test]
+    fn count_by_kind() {
+        let mut module = Module::default();
+        let ty = module.types.add(&[], &[]);
+        module.add_import_func("env", "f", ty);
+        module.add_import_func("env", "g", ty);
+        let (memory, _) = module.add_import_memory("env", "memory", false, 1, None);
+        module.add_import_global("env", "global", ValType::I32, false);
+        module.exports.add("memory", memory);
+
+        let imports = module.imports.count_by_kind();
+        assert_eq!(
+            imports,
+            ImportCounts {
+                functions: 2,
+                tables: 0,
+                memories: 1,
+                globals: 1,
+            }
+        );
+        let exports = module.exports.count_by_kind();
+        assert_eq!(exports.memories, 1);
+        assert_eq!(exports.functions + exports.tables + exports.globals, 0);
+    }
+
+    #[test]
+    fn typed_table_constructors_round_trip() {
+        let mut module = Module::default();
+        module.tables.add_funcref(1, Some(2));
+        module.tables.add_externref(3, None);
+        let import = module.imports.arena.next_id();
+        let table = module.tables.add_import_funcref(4, Some(5), import);
+        module.imports.add("env", "funcs", table);
+        let import = module.imports.arena.next_id();
+        let table = module.tables.add_import_externref(6, None, import);
+        module.imports.add("env", "externs", table);
+
+        let module = Module::from_buffer(&module.emit_wasm()).unwrap();
+        let tables: Vec<_> = module
+            .tables
+            .iter()
+            .map(|t| (t.element_ty, t.initial, t.maximum, t.is_imported()))
+            .collect();
+        assert_eq!(
+            tables,
+            [
+                (ValType::Funcref, 4, Some(5), true),
+                (ValType::Externref, 6, None, true),
+                (ValType::Funcref, 1, Some(2), false),
+                (ValType::Externref, 3, None, false),
+            ]
+        );
+    }
+}
@@ -5,7 +5,7 @@ use crate::ty::ValType;
 use id_arena::Arena;
 
 /// The set of locals in each function in this module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleLocals {
     arena: Arena<Local>,
 }
@@ -34,4 +34,9 @@ impl ModuleLocals {
     pub fn iter(&self) -> impl Iterator<Item = &Local> {
         self.arena.iter().map(|(_, f)| f)
     }
+
+    /// Get a mutable reference to this module's locals.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Local> {
+        self.arena.iter_mut().map(|(_, f)| f)
+    }
 }
@@ -4,14 +4,17 @@ use crate::emit::{Emit, EmitContext, Section};
 use crate::map::IdHashSet;
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
-use crate::{Data, ImportId, Module, Result};
+use crate::{Data, ImportId, Limit, Module, Result};
 use anyhow::bail;
 
 /// The id of a memory.
 pub type MemoryId = Id<Memory>;
 
+/// The size of a page of memory, in bytes.
+pub(crate) const PAGE_SIZE: u64 = 65536;
+
 /// A memory in the wasm.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Memory {
     id: MemoryId,
     /// Is this memory shared?
@@ -37,6 +40,45 @@ impl Memory {
     pub fn id(&self) -> MemoryId {
         self.id
     }
+
+    /// Is this memory imported?
+    pub fn is_imported(&self) -> bool {
+        self.import.is_some()
+    }
+
+    /// Widen this memory's limits so that they also satisfy a memory with the
+    /// given `initial` and `maximum` sizes, for example when merging two
+    /// memories into one.
+    ///
+    /// Limits are only ever grown, never shrunk: the initial size becomes the
+    /// larger of the two initial sizes, and the maximum becomes the larger of
+    /// the two maximums. If either memory has no maximum, then the widened
+    /// memory conservatively has no maximum either.
+    ///
+    /// Returns an error if the given limits are invalid, or if this memory is
+    /// shared and widening would drop its maximum, since shared memories must
+    /// have one.
+    pub fn widen_to(&mut self, initial: u32, maximum: Option<u32>) -> Result<()> {
+        if let Some(maximum) = maximum {
+            if initial > maximum {
+                bail!(
+                    "initial memory size {} is larger than its maximum {}",
+                    initial,
+                    maximum
+                );
+            }
+        }
+        let maximum = match (self.maximum, maximum) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            _ => None,
+        };
+        if self.shared && maximum.is_none() {
+            bail!("cannot remove the maximum size of a shared memory");
+        }
+        self.initial = self.initial.max(initial);
+        self.maximum = maximum;
+        Ok(())
+    }
 }
 
 impl Emit for Memory {
@@ -53,12 +95,16 @@ impl Emit for Memory {
 }
 
 /// The set of memories in this module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleMemories {
     arena: TombstoneArena<Memory>,
 }
 
 impl ModuleMemories {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Add an imported memory
     pub fn add_import(
         &mut self,
@@ -119,6 +165,17 @@ impl ModuleMemories {
         self.arena.iter().map(|(_, f)| f)
     }
 
+    /// Get an iterator of the memories defined in this module, skipping
+    /// imported memories.
+    pub fn iter_local(&self) -> impl Iterator<Item = &Memory> {
+        self.iter().filter(|m| !m.is_imported())
+    }
+
+    /// Get an iterator of this module's imported memories.
+    pub fn iter_imported(&self) -> impl Iterator<Item = &Memory> {
+        self.iter().filter(|m| m.is_imported())
+    }
+
     /// Get a mutable reference to this module's memories.
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Memory> {
         self.arena.iter_mut().map(|(_, f)| f)
@@ -138,6 +195,8 @@ impl Module {
                 wasmparser::MemoryType::M32 { shared, limits } => (shared, limits),
                 wasmparser::MemoryType::M64 { .. } => bail!("64-bit memories not supported"),
             };
+            self.config
+                .check_limit(Limit::MemoryPages, limits.initial.into())?;
             let id = self
                 .memories
                 .add_local(shared, limits.initial, limits.maximum);
@@ -147,6 +206,25 @@ impl Module {
     }
 }
 
+impl Module {
+    /// Find the local memories whose active data segments extend past their
+    /// initial size, along with the end of the highest byte those segments
+    /// initialize.
+    pub(crate) fn data_beyond_initial_memory(&self) -> Vec<(MemoryId, u64)> {
+        self.memories
+            .iter_local()
+            .filter_map(|memory| {
+                let end = self.max_active_data_offset(memory.id())?;
+                if end > u64::from(memory.initial) * PAGE_SIZE {
+                    Some((memory.id(), end))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
 impl Emit for ModuleMemories {
     fn emit(&self, cx: &mut EmitContext) {
         log::debug!("emit memory section");
@@ -164,3 +242,34 @@ impl Emit for ModuleMemories {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Module;
+
+    #[test]
+    fn widen_to_only_grows() {
+        let mut module = Module::default();
+        let id = module.memories.add_local(false, 2, Some(10));
+        let memory = module.memories.get_mut(id);
+
+        memory.widen_to(1, Some(20)).unwrap();
+        assert_eq!(memory.initial, 2);
+        assert_eq!(memory.maximum, Some(20));
+
+        memory.widen_to(5, None).unwrap();
+        assert_eq!(memory.initial, 5);
+        assert_eq!(memory.maximum, None);
+
+        assert!(memory.widen_to(3, Some(1)).is_err());
+    }
+
+    #[test]
+    fn widen_to_keeps_shared_maximum() {
+        let mut module = Module::default();
+        let id = module.memories.add_local(true, 1, Some(10));
+        let memory = module.memories.get_mut(id);
+        assert!(memory.widen_to(1, None).is_err());
+        assert_eq!(memory.maximum, Some(10));
+    }
+}
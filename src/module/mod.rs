@@ -16,37 +16,53 @@ mod types;
 
 use crate::emit::{Emit, EmitContext, IdsToIndices, Section};
 use crate::encode::Encoder;
-use crate::error::Result;
+use crate::error::{self, ErrorKind, Limit, ParseError, Result, ValidationError};
 pub use crate::ir::InstrLocId;
+use crate::ir::InstrSeqId;
+pub use crate::module::custom::{
+    BranchHint, BranchHintSection, CodeMetadata, CodeMetadataEntry, CodeMetadataSection,
+};
 pub use crate::module::custom::{
     CustomSection, CustomSectionId, ModuleCustomSections, RawCustomSection, TypedCustomSectionId,
     UntypedCustomSectionId,
 };
+pub use crate::module::custom::{
+    DataSymbolDefinition, InitFunc, LinkingSection, SegmentInfo, SymbolInfo, SymbolKind,
+};
+pub use crate::module::custom::{FeaturePrefix, TargetFeature, TargetFeaturesSection};
 pub use crate::module::data::{ActiveData, ActiveDataLocation, Data, DataId, DataKind, ModuleData};
 pub use crate::module::elements::ElementKind;
 pub use crate::module::elements::{Element, ElementId, ModuleElements};
-pub use crate::module::exports::{Export, ExportId, ExportItem, ModuleExports};
-pub use crate::module::functions::{Function, FunctionId, ModuleFunctions};
-pub use crate::module::functions::{FunctionKind, ImportedFunction, LocalFunction};
+pub use crate::module::exports::{Export, ExportCounts, ExportId, ExportItem, ModuleExports};
+pub use crate::module::functions::{CodeSizeReport, Function, FunctionId, ModuleFunctions};
+pub use crate::module::functions::{FunctionKind, ImportedFunction, InstrPosition, LocalFunction};
 pub use crate::module::globals::{Global, GlobalId, GlobalKind, ModuleGlobals};
-pub use crate::module::imports::{Import, ImportId, ImportKind, ModuleImports};
+pub use crate::module::imports::{Import, ImportCounts, ImportId, ImportKind, ModuleImports};
 pub use crate::module::locals::ModuleLocals;
 pub use crate::module::memories::{Memory, MemoryId, ModuleMemories};
 pub use crate::module::producers::ModuleProducers;
 pub use crate::module::tables::{ModuleTables, Table, TableId};
-pub use crate::module::types::ModuleTypes;
+pub use crate::module::types::{ModuleTypes, TypeUse};
 use crate::parse::IndicesToIds;
+use crate::{LocalId, TypeId, ValType};
 use anyhow::{bail, Context};
 use log::warn;
 use std::fs;
 use std::mem;
 use std::path::Path;
-use wasmparser::{Parser, Payload, Validator, WasmFeatures};
+use wasmparser::{
+    FuncValidator, FunctionBody, Parser, Payload, Validator, ValidatorResources, WasmFeatures,
+};
 
 pub use self::config::ModuleConfig;
 
 /// A wasm module.
-#[derive(Debug, Default)]
+///
+/// Cloning a module makes an independent deep copy, in which all of the
+/// original's ids are valid too. Custom sections that don't implement
+/// `CustomSection::clone_section` are left out of the copy, and, as with
+/// cloning a `ModuleConfig`, the copy's configuration has no callbacks.
+#[derive(Clone, Debug, Default)]
 #[allow(missing_docs)]
 pub struct Module {
     pub imports: ModuleImports,
@@ -107,28 +123,186 @@ impl Module {
         config.parse(&fs::read(path)?)
     }
 
+    /// Construct a new module from the given path with the default
+    /// configuration, memory-mapping the file rather than reading it into a
+    /// buffer first.
+    ///
+    /// See `Module::from_file_mmap_with_config` for details.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is being parsed,
+    /// as described in `Module::from_file_mmap_with_config`.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_file_mmap<P>(path: P) -> Result<Module>
+    where
+        P: AsRef<Path>,
+    {
+        Module::from_file_mmap_with_config(path, &ModuleConfig::new())
+    }
+
+    /// Construct a new module from the given path and configuration,
+    /// memory-mapping the file rather than reading it into a buffer first.
+    ///
+    /// This avoids holding a second, owned copy of very large modules in
+    /// memory while they are parsed. walrus copies everything it keeps out of
+    /// the input, so the mapping is dropped again before this returns.
+    ///
+    /// This requires the `mmap` feature.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, or truncated, by this or any other
+    /// process while it is being parsed. Doing so is undefined behavior: the
+    /// parser may see the contents change underneath it, and truncation can
+    /// crash the process with `SIGBUS`. Use `Module::from_file_with_config` for
+    /// files that might change concurrently.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_file_mmap_with_config<P>(path: P, config: &ModuleConfig) -> Result<Module>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file =
+            fs::File::open(path).with_context(|| format!("failed to open `{}`", path.display()))?;
+        // Safety: upheld by our caller, as described above.
+        let wasm = memmap2::Mmap::map(&file)
+            .with_context(|| format!("failed to map `{}`", path.display()))?;
+        config.parse(&wasm)
+    }
+
     /// Construct a new module from the in-memory wasm buffer with the default
     /// configuration.
     pub fn from_buffer(wasm: &[u8]) -> Result<Module> {
         ModuleConfig::new().parse(wasm)
     }
 
+    /// Construct a new module from the in-memory wasm buffer with the default
+    /// configuration, recovering from as many errors as possible.
+    ///
+    /// Rather than stopping at the first error, every local function whose body
+    /// fails to decode or validate is replaced with a stub whose body is just
+    /// `unreachable`, and parsing continues with the next function. If an error
+    /// is encountered outside of a function body, parsing stops there and the
+    /// module parsed so far is returned, with any functions whose bodies were
+    /// not reached stubbed out in the same way.
+    ///
+    /// All the errors encountered are returned alongside the module, which is
+    /// useful to find out which functions of a large module are problematic.
+    pub fn from_buffer_lenient(wasm: &[u8]) -> (Module, Vec<ParseError>) {
+        ModuleConfig::new().parse_lenient(wasm)
+    }
+
     fn parse(wasm: &[u8], config: &ModuleConfig) -> Result<Module> {
+        Module::parse_with_errors(wasm, config, None).map_err(|e| error::classify_leb128(e, wasm))
+    }
+
+    fn parse_lenient(wasm: &[u8], config: &ModuleConfig) -> (Module, Vec<ParseError>) {
+        let mut errors = Vec::new();
+        let module = match Module::parse_with_errors(wasm, config, Some(&mut errors)) {
+            Ok(module) => module,
+            Err(error) => {
+                errors.push(ParseError { func: None, error });
+                Module::with_config(config.clone())
+            }
+        };
+        let errors = errors
+            .into_iter()
+            .map(|e| ParseError {
+                func: e.func,
+                error: error::classify_leb128(e.error, wasm),
+            })
+            .collect();
+        (module, errors)
+    }
+
+    /// Parse a module, recording recoverable errors in `errors` if it is
+    /// `Some` rather than bailing out on them.
+    fn parse_with_errors(
+        wasm: &[u8],
+        config: &ModuleConfig,
+        mut errors: Option<&mut Vec<ParseError>>,
+    ) -> Result<Module> {
         let mut ret = Module::default();
         ret.config = config.clone();
         let mut indices = IndicesToIds::default();
+        let mut local_functions = Vec::new();
+        let mut name_section = None;
+
+        if let Err(error) = ret.parse_payloads(
+            wasm,
+            config,
+            &mut indices,
+            &mut local_functions,
+            &mut name_section,
+        ) {
+            match errors.as_mut() {
+                Some(errors) => errors.push(ParseError { func: None, error }),
+                None => return Err(error),
+            }
+        }
+
+        if let Err(error) = ret
+            .parse_local_functions(
+                local_functions,
+                &mut indices,
+                config.on_instr_loc.as_ref().map(|f| f.as_ref()),
+                errors.as_mut().map(|e| &mut **e),
+            )
+            .context("failed to parse code section")
+        {
+            match errors.as_mut() {
+                Some(errors) => errors.push(ParseError { func: None, error }),
+                None => return Err(error),
+            }
+        }
+
+        // The name section is parsed last, since local names can only be
+        // resolved once the locals declared by function bodies exist.
+        if let Some(names) = name_section {
+            if let Err(e) = ret.parse_name_section(names, &indices) {
+                log::warn!("failed to parse `name` custom section {}", e);
+            }
+        }
+
+        if errors.is_some() {
+            ret.funcs
+                .stub_uninitialized(&mut ret.types, &mut ret.locals);
+        }
+
+        ret.producers
+            .add_processed_by("walrus", env!("CARGO_PKG_VERSION"));
+
+        if let Some(on_parse) = &config.on_parse {
+            if let Err(error) = on_parse(&mut ret, &indices) {
+                match errors {
+                    Some(errors) => errors.push(ParseError { func: None, error }),
+                    None => return Err(error),
+                }
+            }
+        }
+
+        log::debug!("parse complete");
+        Ok(ret)
+    }
+
+    fn parse_payloads<'a>(
+        &mut self,
+        wasm: &'a [u8],
+        config: &ModuleConfig,
+        indices: &mut IndicesToIds,
+        local_functions: &mut Vec<(FunctionBody<'a>, FuncValidator<ValidatorResources>)>,
+        name_section: &mut Option<wasmparser::NameSectionReader<'a>>,
+    ) -> Result<()> {
+        config.check_limit(Limit::ModuleSize, wasm.len() as u64)?;
+
         let mut validator = Validator::new();
-        validator.wasm_features(WasmFeatures {
-            reference_types: !config.only_stable_features,
-            multi_value: true,
-            bulk_memory: !config.only_stable_features,
-            simd: !config.only_stable_features,
-            threads: !config.only_stable_features,
-            multi_memory: !config.only_stable_features,
-            ..WasmFeatures::default()
-        });
+        validator.wasm_features(wasm_features(config));
 
-        let mut local_functions = Vec::new();
+        // The number of functions declared by the function section, and
+        // whether we've seen a code section to give them bodies.
+        let mut declared_funcs = 0;
+        let mut saw_code_section = false;
 
         for payload in Parser::new(0).parse_all(wasm) {
             match payload? {
@@ -139,65 +313,68 @@ impl Module {
                     validator
                         .data_section(&s)
                         .context("failed to parse data section")?;
-                    ret.parse_data(s, &mut indices)?;
+                    self.parse_data(s, indices)?;
                 }
                 Payload::TypeSection(s) => {
                     validator
                         .type_section(&s)
                         .context("failed to parse type section")?;
-                    ret.parse_types(s, &mut indices)?;
+                    self.parse_types(s, indices)?;
                 }
                 Payload::ImportSection(s) => {
                     validator
                         .import_section(&s)
                         .context("failed to parse import section")?;
-                    ret.parse_imports(s, &mut indices)?;
+                    self.parse_imports(s, indices)?;
                 }
                 Payload::TableSection(s) => {
                     validator
                         .table_section(&s)
                         .context("failed to parse table section")?;
-                    ret.parse_tables(s, &mut indices)?;
+                    self.parse_tables(s, indices)?;
                 }
                 Payload::MemorySection(s) => {
                     validator
                         .memory_section(&s)
                         .context("failed to parse memory section")?;
-                    ret.parse_memories(s, &mut indices)?;
+                    self.parse_memories(s, indices)?;
                 }
                 Payload::GlobalSection(s) => {
                     validator
                         .global_section(&s)
                         .context("failed to parse global section")?;
-                    ret.parse_globals(s, &mut indices)?;
+                    self.parse_globals(s, indices)?;
                 }
                 Payload::ExportSection(s) => {
                     validator
                         .export_section(&s)
                         .context("failed to parse export section")?;
-                    ret.parse_exports(s, &mut indices)?;
+                    self.parse_exports(s, indices)?;
                 }
                 Payload::ElementSection(s) => {
                     validator
                         .element_section(&s)
                         .context("failed to parse element section")?;
-                    ret.parse_elements(s, &mut indices)?;
+                    self.parse_elements(s, indices)?;
                 }
                 Payload::StartSection { func, range, .. } => {
                     validator.start_section(func, &range)?;
-                    ret.start = Some(indices.get_func(func)?);
+                    self.start = Some(indices.get_func(func)?);
                 }
                 Payload::FunctionSection(s) => {
                     validator
                         .function_section(&s)
                         .context("failed to parse function section")?;
-                    ret.declare_local_functions(s, &mut indices)?;
+                    declared_funcs = s.get_count();
+                    self.declare_local_functions(s, indices)?;
                 }
                 Payload::DataCountSection { count, range } => {
                     validator.data_count_section(count, &range)?;
-                    ret.reserve_data(count, &mut indices);
+                    self.reserve_data(count, indices);
                 }
                 Payload::CodeSectionStart { count, range, .. } => {
+                    saw_code_section = true;
+                    check_function_count(declared_funcs, count)?;
                     validator.code_section_start(count, &range)?;
                 }
                 Payload::CodeSectionEntry(body) => {
@@ -213,13 +390,13 @@ impl Module {
                     let result = match name {
                         "producers" => wasmparser::ProducersSectionReader::new(data, data_offset)
                             .map_err(anyhow::Error::from)
-                            .and_then(|s| ret.parse_producers_section(s)),
+                            .and_then(|s| self.parse_producers_section(s)),
                         "name" => wasmparser::NameSectionReader::new(data, data_offset)
-                            .map_err(anyhow::Error::from)
-                            .and_then(|r| ret.parse_name_section(r, &indices)),
+                            .map(|r| *name_section = Some(r))
+                            .map_err(anyhow::Error::from),
                         _ => {
                             log::debug!("parsing custom section `{}`", name);
-                            ret.customs.add(RawCustomSection {
+                            self.customs.add(RawCustomSection {
                                 name: name.to_string(),
                                 data: data.to_vec(),
                             });
@@ -230,12 +407,26 @@ impl Module {
                         log::warn!("failed to parse `{}` custom section {}", name, e);
                     }
                 }
-                Payload::UnknownSection { id, range, .. } => {
+                Payload::UnknownSection {
+                    id,
+                    contents,
+                    range,
+                } => {
+                    if let Some(on_raw_section) = &config.on_raw_section {
+                        log::debug!("passing unknown section {} to `on_raw_section`", id);
+                        on_raw_section(self, id, contents)?;
+                        continue;
+                    }
                     validator.unknown_section(id, &range)?;
                     unreachable!()
                 }
 
-                Payload::End => validator.end()?,
+                Payload::End => {
+                    if !saw_code_section {
+                        check_function_count(declared_funcs, 0)?;
+                    }
+                    validator.end()?;
+                }
 
                 // the module linking proposal is not implemented yet.
                 Payload::AliasSection(s) => {
@@ -270,22 +461,7 @@ impl Module {
             }
         }
 
-        ret.parse_local_functions(
-            local_functions,
-            &mut indices,
-            config.on_instr_loc.as_ref().map(|f| f.as_ref()),
-        )
-        .context("failed to parse code section")?;
-
-        ret.producers
-            .add_processed_by("walrus", env!("CARGO_PKG_VERSION"));
-
-        if let Some(on_parse) = &config.on_parse {
-            on_parse(&mut ret, &indices)?;
-        }
-
-        log::debug!("parse complete");
-        Ok(ret)
+        Ok(())
     }
 
     /// Emit this module into a `.wasm` file at the given path.
@@ -299,14 +475,32 @@ impl Module {
     }
 
     /// Emit this module into an in-memory wasm buffer.
+    ///
+    /// Every section's size is always encoded as a maximal-width, 5-byte
+    /// LEB128, rather than in as few bytes as possible. This means tools that
+    /// patch the emitted wasm in place can grow or shrink a section without
+    /// moving the bytes before it.
     pub fn emit_wasm(&mut self) -> Vec<u8> {
         log::debug!("start emit");
 
+        for (memory, end) in self.data_beyond_initial_memory() {
+            log::warn!(
+                "active data segments extend to byte {}, past the initial size of {:?}",
+                end,
+                memory
+            );
+        }
+
         let indices = &mut IdsToIndices::default();
         let mut wasm = Vec::new();
         wasm.extend(&[0x00, 0x61, 0x73, 0x6d]); // magic
         wasm.extend(&[0x01, 0x00, 0x00, 0x00]); // version
 
+        if self.config.stamp_provenance {
+            self.producers
+                .add_processed_by("walrus", env!("CARGO_PKG_VERSION"));
+        }
+
         let mut customs = mem::replace(&mut self.customs, ModuleCustomSections::default());
 
         let mut cx = EmitContext {
@@ -316,37 +510,47 @@ impl Module {
             locals: Default::default(),
             code_transform: Vec::new(),
         };
-        self.types.emit(&mut cx);
-        self.imports.emit(&mut cx);
-        self.funcs.emit_func_section(&mut cx);
-        self.tables.emit(&mut cx);
-        self.memories.emit(&mut cx);
-        self.globals.emit(&mut cx);
-        self.exports.emit(&mut cx);
-        if let Some(start) = self.start {
-            let idx = cx.indices.get_func_index(start);
-            cx.start_section(Section::Start).encoder.u32(idx);
-        }
-        self.elements.emit(&mut cx);
-        self.data.emit_data_count(&mut cx);
-        self.funcs.emit(&mut cx);
-        self.data.emit(&mut cx);
+        self.emit_standard_sections(&mut cx);
 
         if !self.config.skip_name_section {
             emit_name_section(&mut cx);
         }
         if !self.config.skip_producers_section {
             self.producers.emit(&mut cx);
+        } else if self.config.stamp_provenance {
+            let mut stamp = ModuleProducers::default();
+            stamp.add_processed_by("walrus", env!("CARGO_PKG_VERSION"));
+            stamp.emit(&mut cx);
         }
 
         let indices = mem::replace(cx.indices, Default::default());
 
+        // There may only be one `name` and one `producers` section. The ones
+        // generated above supersede any custom sections with the same name,
+        // and otherwise only the first such custom section is kept.
+        let mut emitted_name = !self.config.skip_name_section;
+        let mut emitted_producers =
+            !self.config.skip_producers_section || self.config.stamp_provenance;
+
         for (_id, section) in customs.iter_mut() {
             if !self.config.generate_dwarf && section.name().starts_with(".debug") {
                 log::debug!("skipping DWARF custom section {}", section.name());
                 continue;
             }
 
+            let emitted = match section.name() {
+                "name" => Some(&mut emitted_name),
+                "producers" => Some(&mut emitted_producers),
+                _ => None,
+            };
+            if let Some(emitted) = emitted {
+                if *emitted {
+                    log::debug!("skipping duplicate {} section", section.name());
+                    continue;
+                }
+                *emitted = true;
+            }
+
             log::debug!("emitting custom section {}", section.name());
 
             if self.config.preserve_code_transform {
@@ -358,15 +562,362 @@ impl Module {
                 .raw(&section.data(&indices));
         }
 
+        if self.config.align_output > 1 {
+            pad_to_alignment(&mut wasm, self.config.align_output);
+        }
+
         log::debug!("emission finished");
         wasm
     }
 
+    /// Emit the module's standard (non-custom) sections, in order.
+    ///
+    /// This is the one place that knows the order of the sections, and it
+    /// also assigns the indices that later sections refer to.
+    pub(crate) fn emit_standard_sections(&self, cx: &mut EmitContext) {
+        self.types.emit(cx);
+        self.imports.emit(cx);
+        self.funcs.emit_func_section(cx);
+        self.tables.emit(cx);
+        self.memories.emit(cx);
+        self.globals.emit(cx);
+        self.exports.emit(cx);
+        if let Some(start) = self.start {
+            let idx = cx.indices.get_func_index(start);
+            cx.start_section(Section::Start).encoder.u32(idx);
+        }
+        self.elements.emit(cx);
+        self.data.emit_data_count(cx);
+        self.funcs.emit(cx);
+        self.data.emit(cx);
+    }
+
+    /// Check that this module is valid wasm.
+    ///
+    /// Instructions added with a `FunctionBuilder` aren't type checked as they
+    /// are added, so mistakes like calling a function with the wrong number of
+    /// arguments otherwise only show up once the emitted wasm is loaded. This
+    /// emits the module into a scratch buffer and validates that instead. If
+    /// a function body is invalid, the error says which function it is, and
+    /// for an invalid `call` also the type of the function being called.
+    /// A `ValidationError` attached to the error pinpoints the invalid
+    /// instruction.
+    ///
+    /// This also checks that no function body is larger than
+    /// `ModuleConfig::max_function_body_size`, failing with an
+    /// `ErrorKind::FunctionTooLarge` error otherwise.
+    ///
+    /// With `ModuleConfig::strict_data_bounds` turned on, this also fails if
+    /// an active data segment extends past the initial size of its memory.
+    ///
+    /// Atomic memory accesses must be aligned to exactly their width, and with
+    /// `ModuleConfig::strict_atomics` turned on, must also access a shared
+    /// memory; otherwise this fails with a `ValidationError` naming the
+    /// offending instruction.
+    ///
+    /// The module is validated with the same wasm features that parsing
+    /// allows. In particular, with `ModuleConfig::only_stable_features`
+    /// turned on, using reference types, bulk memory, SIMD, threads, or
+    /// multiple memories anywhere in the module is an error, which names the
+    /// first instruction or type that needs the feature. Code generators that
+    /// must target a baseline without those features can call this before
+    /// emitting to catch them creeping in.
+    pub fn validate(&self) -> Result<()> {
+        let indices = &mut IdsToIndices::default();
+        let wasm = self.emit_scratch_module(indices);
+
+        let describe = |id: FunctionId| match &self.funcs.get(id).name {
+            Some(name) => format!("function `{}`", name),
+            None => format!("function {}", indices.get_func_index(id)),
+        };
+
+        let limit = self.config.function_body_size_limit();
+        let too_large = self
+            .funcs
+            .iter_local()
+            .filter_map(|(id, _)| Some((id, indices.get_func_code_range(id)?.len())))
+            .filter(|(_, size)| *size > limit)
+            .min_by_key(|(id, _)| indices.get_func_index(*id));
+        if let Some((func, size)) = too_large {
+            return Err(anyhow::Error::from(ErrorKind::FunctionTooLarge {
+                func,
+                size: size as u64,
+                limit: limit as u64,
+            })
+            .context(format!("{} is too large", describe(func))));
+        }
+
+        if self.config.strict_data_bounds {
+            if let Some((memory, end)) = self.data_beyond_initial_memory().first() {
+                bail!(
+                    "active data segments extend to byte {}, past the initial size of \
+                     memory {} ({} pages)",
+                    end,
+                    indices.get_memory_index(*memory),
+                    self.memories.get(*memory).initial
+                );
+            }
+        }
+
+        if let Some(error) = self.invalid_atomic_access() {
+            let context = format!("{} is invalid", describe(error.func));
+            return Err(anyhow::Error::from(error).context(context));
+        }
+
+        let mut validator = Validator::new();
+        validator.wasm_features(wasm_features(&self.config));
+        let error = match validator.validate_all(&wasm) {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        let offset = error.offset();
+        let func = self.funcs.iter_local().find_map(|(id, _)| {
+            let range = indices.get_func_code_range(id)?;
+            if range.contains(&offset) {
+                Some(id)
+            } else {
+                None
+            }
+        });
+        let func = match func {
+            Some(func) => func,
+            None => return Err(error.into()),
+        };
+        let location = self.locate_invalid_instr(indices, func, offset);
+
+        let mut context = format!("{} is invalid", describe(func));
+        let mut reader = wasmparser::BinaryReader::new_with_offset(&wasm[offset..], offset);
+        if let Ok(wasmparser::Operator::Call { function_index }) = reader.read_operator() {
+            let callee = self
+                .funcs
+                .iter()
+                .find(|f| indices.get_func_index(f.id()) == function_index);
+            if let Some(callee) = callee {
+                let (params, results) = self.types.params_results(callee.ty());
+                let list = |tys: &[ValType]| {
+                    tys.iter()
+                        .map(|ty| ty.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                context.push_str(&format!(
+                    ": it calls {}, which expects [{}] -> [{}]",
+                    describe(callee.id()),
+                    list(params),
+                    list(results)
+                ));
+            }
+        }
+        let message = error.message().to_string();
+        let mut error = anyhow::Error::from(error);
+        if let Some((seq, index)) = location {
+            error = error.context(ValidationError {
+                func,
+                seq,
+                index,
+                message,
+            });
+        }
+        Err(error.context(context))
+    }
+
+    /// Find the instruction of `func` that was emitted at `offset` by
+    /// `emit_scratch_module`, as its instruction sequence and its index
+    /// within it.
+    fn locate_invalid_instr(
+        &self,
+        indices: &IdsToIndices,
+        func: FunctionId,
+        offset: usize,
+    ) -> Option<(InstrSeqId, usize)> {
+        let start = indices.get_func_code_range(func)?.start;
+        let local = self.funcs.get(func).kind.unwrap_local();
+
+        // Emit the function again, this time recording where everything ends
+        // up. Offsets are relative to the start of its body either way.
+        let mut body = Vec::new();
+        let mut encoder = Encoder::new(&mut body);
+        let (_, local_indices) = local.emit_locals(self, &mut encoder);
+        let mut locations = Vec::new();
+        local.emit_instructions(
+            indices,
+            &local_indices,
+            &mut encoder,
+            None,
+            Some(&mut locations),
+        );
+
+        let at = offset.checked_sub(start)?;
+        locations
+            .into_iter()
+            .take_while(|(_, _, pos)| *pos <= at)
+            .last()
+            .map(|(seq, index, _)| (seq, index))
+    }
+
+    /// Work out the index that each item in this module would be given if
+    /// the module were emitted now.
+    ///
+    /// The returned map covers every kind of index space: types, functions,
+    /// tables, memories, globals, and element and data segments. Imported
+    /// items come first in their index spaces, just like in the emitted wasm.
+    /// This is handy for cross-referencing a module against external tools
+    /// that refer to items by index, without having to emit it first.
+    ///
+    /// Items that wouldn't be emitted, because they have been deleted, don't
+    /// have an index. The indices only stay accurate as long as the module
+    /// isn't changed; in particular, running `passes::gc` may remove unused
+    /// items and shift the indices of the ones after them.
+    ///
+    /// This does most of the work of emitting the module, so it's no cheaper
+    /// than `emit_wasm`.
+    pub fn id_index_map(&self) -> IdsToIndices {
+        let mut indices = IdsToIndices::default();
+        self.emit_scratch_module(&mut indices);
+        indices
+    }
+
+    /// Emit a module with just the standard (non-custom) sections into a new
+    /// buffer, recording the index of each item in `indices`.
+    pub(crate) fn emit_scratch_module(&self, indices: &mut IdsToIndices) -> Vec<u8> {
+        let mut wasm = Vec::new();
+        wasm.extend(&[0x00, 0x61, 0x73, 0x6d]); // magic
+        wasm.extend(&[0x01, 0x00, 0x00, 0x00]); // version
+
+        let mut cx = EmitContext {
+            module: self,
+            indices,
+            encoder: Encoder::new(&mut wasm),
+            locals: Default::default(),
+            code_transform: Vec::new(),
+        };
+        self.emit_standard_sections(&mut cx);
+        wasm
+    }
+
+    /// Release memory that this module's items hold on to but no longer
+    /// need.
+    ///
+    /// After adding lots of instructions or data and then removing it again,
+    /// function bodies and data and element segments may have much more
+    /// capacity than they use; this shrinks them to fit.
+    ///
+    /// The arenas holding the module's items are shrunk too, but only their
+    /// spare capacity is released: deleted slots are not reclaimed. Ids are
+    /// never changed by this, so any ids into the module stay valid. Deleted
+    /// items already release their contents when they are deleted, but each
+    /// still takes up a small, fixed-size slot in its arena, since ids are
+    /// indices into those arenas. Reclaiming those slots would mean
+    /// renumbering ids, so if that matters, emit the module and parse it
+    /// again to get a fresh module without them.
+    pub fn shrink_to_fit(&mut self) {
+        for (_, func) in self.funcs.iter_local_mut() {
+            let builder = func.builder_mut();
+            for (_, seq) in builder.arena.iter_mut() {
+                seq.instrs.shrink_to_fit();
+            }
+            builder.arena.shrink_to_fit();
+        }
+        for data in self.data.iter_mut() {
+            data.value.shrink_to_fit();
+        }
+        for elem in self.elements.iter_mut() {
+            elem.members.shrink_to_fit();
+        }
+
+        self.funcs.shrink_to_fit();
+        self.imports.shrink_to_fit();
+        self.tables.shrink_to_fit();
+        self.memories.shrink_to_fit();
+        self.globals.shrink_to_fit();
+        self.exports.shrink_to_fit();
+        self.elements.shrink_to_fit();
+        self.data.shrink_to_fit();
+        self.customs.shrink_to_fit();
+    }
+
     /// Returns an iterator over all functions in this module
     pub fn functions(&self) -> impl Iterator<Item = &Function> {
         self.funcs.iter()
     }
 
+    /// Add a custom section called `name` containing the raw bytes `data`.
+    ///
+    /// This is shorthand for adding a `RawCustomSection` to `self.customs`,
+    /// and is the simplest way to embed arbitrary metadata in a module. The
+    /// returned id can be used to get at the section later, for example with
+    /// `self.customs.get_mut(id)` to update it or `self.customs.delete(id)` to
+    /// remove it.
+    ///
+    /// Custom sections are emitted after all of the standard sections, in the
+    /// order they were added, so this section ends up at the end of the
+    /// module unless more custom sections are added after it. Adding several
+    /// custom sections with the same name is allowed, and they are all
+    /// emitted, except that only one "name" and one "producers" section are
+    /// ever emitted.
+    pub fn add_custom_section(
+        &mut self,
+        name: &str,
+        data: Vec<u8>,
+    ) -> TypedCustomSectionId<RawCustomSection> {
+        self.customs.add(RawCustomSection {
+            name: name.to_string(),
+            data,
+        })
+    }
+
+    /// Get the name of this module itself, from the module-name subsection of
+    /// the "name" section. Some debuggers use it to label the module.
+    ///
+    /// This is the same as `self.name`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Set or clear the name of this module itself.
+    ///
+    /// The name is emitted in the module-name subsection of the "name"
+    /// section, as long as `ModuleConfig::generate_name_section` is on.
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    /// Rewrite the names of this module's items with `f`.
+    ///
+    /// `f` is called with each item that has a name, and that name; returning
+    /// `Some` replaces the name, while returning `None` keeps it as it is.
+    /// Items without names are skipped. This covers the module's own name and
+    /// the names of functions, locals, types, and data and element segments.
+    /// Globals don't have names in walrus, and type names are never emitted;
+    /// the other names end up in the emitted "name" section as long as
+    /// `ModuleConfig::generate_name_section` is on.
+    pub fn rename_names(&mut self, mut f: impl FnMut(NameKind, &str) -> Option<String>) {
+        let mut rename = |kind: NameKind, name: &mut Option<String>| {
+            if let Some(new) = name.as_ref().and_then(|old| f(kind, old)) {
+                *name = Some(new);
+            }
+        };
+
+        rename(NameKind::Module, &mut self.name);
+        for func in self.funcs.iter_mut() {
+            rename(NameKind::Function(func.id()), &mut func.name);
+        }
+        for local in self.locals.iter_mut() {
+            rename(NameKind::Local(local.id()), &mut local.name);
+        }
+        let types: Vec<_> = self.types.iter().map(|ty| ty.id()).collect();
+        for ty in types {
+            rename(NameKind::Type(ty), &mut self.types.get_mut(ty).name);
+        }
+        for data in self.data.iter_mut() {
+            rename(NameKind::Data(data.id()), &mut data.name);
+        }
+        for element in self.elements.iter_mut() {
+            rename(NameKind::Element(element.id()), &mut element.name);
+        }
+    }
+
     fn parse_name_section(
         &mut self,
         names: wasmparser::NameSectionReader,
@@ -420,6 +971,32 @@ impl Module {
                         }
                     }
                 }
+                // The extended name section's element (8) and data (9) segment
+                // subsections aren't known to wasmparser yet, but they're
+                // plain name maps, so read them by hand.
+                wasmparser::Name::Unknown {
+                    ty: ty @ 8..=9,
+                    data,
+                    range,
+                } => {
+                    let mut reader = wasmparser::BinaryReader::new_with_offset(data, range.start);
+                    for _ in 0..reader.read_var_u32()? {
+                        let index = reader.read_var_u32()?;
+                        let name = reader.read_string()?.to_string();
+                        let result = if ty == 8 {
+                            indices
+                                .get_element(index)
+                                .map(|id| self.elements.get_mut(id).name = Some(name))
+                        } else {
+                            indices
+                                .get_data(index)
+                                .map(|id| self.data.get_mut(id).name = Some(name))
+                        };
+                        if let Err(e) = result {
+                            warn!("in name section: {}", e);
+                        }
+                    }
+                }
                 wasmparser::Name::Unknown { ty, .. } => warn!("unknown name subsection {}", ty),
             }
         }
@@ -427,6 +1004,91 @@ impl Module {
     }
 }
 
+/// Check that the code section has a body for each function declared in the
+/// function section.
+fn check_function_count(declared: u32, code: u32) -> Result<()> {
+    if declared != code {
+        return Err(ErrorKind::FunctionCountMismatch { declared, code }.into());
+    }
+    Ok(())
+}
+
+/// The wasm features that walrus supports with the given configuration.
+fn wasm_features(config: &ModuleConfig) -> WasmFeatures {
+    WasmFeatures {
+        reference_types: !config.only_stable_features,
+        multi_value: true,
+        bulk_memory: !config.only_stable_features,
+        simd: !config.only_stable_features,
+        threads: !config.only_stable_features,
+        multi_memory: !config.only_stable_features,
+        tail_call: config.lower_tail_calls,
+        ..WasmFeatures::default()
+    }
+}
+
+/// The kinds of names that `Module::rename_names` rewrites, along with the
+/// item that each name belongs to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NameKind {
+    /// The name of the module itself.
+    Module,
+    /// The name of a function.
+    Function(FunctionId),
+    /// The name of a local.
+    Local(LocalId),
+    /// The name of a type.
+    Type(TypeId),
+    /// The name of a data segment.
+    Data(DataId),
+    /// The name of an element segment.
+    Element(ElementId),
+}
+
+/// Append a "padding" custom section full of zeros to `wasm`, so that its
+/// length becomes a multiple of `align`.
+fn pad_to_alignment(wasm: &mut Vec<u8>, align: usize) {
+    const NAME: &str = "padding";
+
+    fn leb128_len(mut n: usize) -> usize {
+        let mut len = 1;
+        while n >= 0x80 {
+            n >>= 7;
+            len += 1;
+        }
+        len
+    }
+
+    if wasm.len().is_multiple_of(align) {
+        return;
+    }
+
+    // Growing the payload may also grow the encoded section size, so just
+    // search for the smallest payload that works.
+    let name_size = leb128_len(NAME.len()) + NAME.len();
+    let mut payload = 0;
+    loop {
+        let size = name_size + payload;
+        if (wasm.len() + 1 + leb128_len(size) + size).is_multiple_of(align) {
+            break;
+        }
+        payload += 1;
+    }
+
+    log::debug!("padding module with {} bytes", payload);
+    let mut encoder = Encoder::new(wasm);
+    encoder.byte(0);
+    encoder.usize(name_size + payload);
+    encoder.str(NAME);
+    encoder.reserve(payload);
+}
+
+/// Emit the "name" section.
+///
+/// The spec requires the entries of every name map, and of the indirect name
+/// map of locals, to be sorted by index, and strict consumers reject sections
+/// that aren't. Items are stored in arena order rather than index order, so
+/// every map is sorted before it is emitted.
 fn emit_name_section(cx: &mut EmitContext) {
     log::debug!("emit name section");
     let mut funcs = cx
@@ -461,7 +1123,30 @@ fn emit_name_section(cx: &mut EmitContext) {
         .collect::<Vec<_>>();
     locals.sort_by_key(|p| p.0); // sort by index
 
-    if cx.module.name.is_none() && funcs.len() == 0 && locals.len() == 0 {
+    let mut elements = cx
+        .module
+        .elements
+        .iter()
+        .filter_map(|elem| elem.name.as_ref().map(|name| (elem, name)))
+        .map(|(elem, name)| (cx.indices.get_element_index(elem.id()), name))
+        .collect::<Vec<_>>();
+    elements.sort_by_key(|p| p.0); // sort by index
+
+    let mut data = cx
+        .module
+        .data
+        .iter()
+        .filter_map(|data| data.name.as_ref().map(|name| (data, name)))
+        .map(|(data, name)| (cx.indices.get_data_index(data.id()), name))
+        .collect::<Vec<_>>();
+    data.sort_by_key(|p| p.0); // sort by index
+
+    if cx.module.name.is_none()
+        && funcs.is_empty()
+        && locals.is_empty()
+        && elements.is_empty()
+        && data.is_empty()
+    {
         return;
     }
 
@@ -492,4 +1177,37 @@ fn emit_name_section(cx: &mut EmitContext) {
             }
         }
     }
+
+    // Subsections 3 through 7 (labels, types, tables, memories, and globals)
+    // of the extended name section aren't emitted.
+    for (subsection, names) in [(8, elements), (9, data)].iter() {
+        if !names.is_empty() {
+            let mut cx = cx.subsection(*subsection);
+            cx.encoder.usize(names.len());
+            for (index, name) in names {
+                cx.encoder.u32(*index);
+                cx.encoder.str(name);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_file_mmap() {
+        let mut module = Module::default();
+        module.name = Some("mapped".to_string());
+        let path = std::env::temp_dir().join(format!("walrus-mmap-{}.wasm", std::process::id()));
+        module.emit_wasm_file(&path).unwrap();
+
+        // Safety: nothing else knows about this file, so it won't change.
+        let result = unsafe { Module::from_file_mmap(&path) };
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap().name.as_deref(), Some("mapped"));
+
+        assert!(unsafe { Module::from_file_mmap(&path) }.is_err());
+    }
 }
@@ -1,17 +1,18 @@
 //! Tables within a wasm module.
 
 use crate::emit::{Emit, EmitContext, Section};
+use crate::ir::*;
 use crate::map::IdHashSet;
 use crate::parse::IndicesToIds;
 use crate::tombstone_arena::{Id, Tombstone, TombstoneArena};
-use crate::{Element, ImportId, Module, Result, ValType};
+use crate::{Element, ElementKind, ImportId, Limit, Module, ModuleElements, Result, ValType};
 use anyhow::bail;
 
 /// The id of a table.
 pub type TableId = Id<Table>;
 
 /// A table in the wasm.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Table {
     id: TableId,
     /// The initial size of this table
@@ -26,13 +27,50 @@ pub struct Table {
     pub elem_segments: IdHashSet<Element>,
 }
 
-impl Tombstone for Table {}
+impl Tombstone for Table {
+    fn on_delete(&mut self) {
+        self.elem_segments = Default::default();
+    }
+}
 
 impl Table {
     /// Get this table's id.
     pub fn id(&self) -> TableId {
         self.id
     }
+
+    /// Is this table imported?
+    pub fn is_imported(&self) -> bool {
+        self.import.is_some()
+    }
+
+    /// Widen this table's limits so that they also satisfy a table with the
+    /// given `initial` and `maximum` sizes, for example when merging two
+    /// tables into one.
+    ///
+    /// Limits are only ever grown, never shrunk: the initial size becomes the
+    /// larger of the two initial sizes, and the maximum becomes the larger of
+    /// the two maximums. If either table has no maximum, then the widened table
+    /// conservatively has no maximum either.
+    ///
+    /// Returns an error if the given limits are invalid.
+    pub fn widen_to(&mut self, initial: u32, maximum: Option<u32>) -> Result<()> {
+        if let Some(maximum) = maximum {
+            if initial > maximum {
+                bail!(
+                    "initial table size {} is larger than its maximum {}",
+                    initial,
+                    maximum
+                );
+            }
+        }
+        self.initial = self.initial.max(initial);
+        self.maximum = match (self.maximum, maximum) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            _ => None,
+        };
+        Ok(())
+    }
 }
 
 impl Emit for Table {
@@ -46,14 +84,29 @@ impl Emit for Table {
     }
 }
 
+fn check_limits(initial: u32, max: Option<u32>) {
+    if let Some(max) = max {
+        assert!(
+            initial <= max,
+            "initial table size {} is larger than its maximum {}",
+            initial,
+            max
+        );
+    }
+}
+
 /// The set of tables in this module.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleTables {
     /// The arena containing this module's tables.
     arena: TombstoneArena<Table>,
 }
 
 impl ModuleTables {
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
     /// Adds a new imported table to this list of tables
     pub fn add_import(
         &mut self,
@@ -89,6 +142,58 @@ impl ModuleTables {
         id
     }
 
+    /// Construct a new `funcref` table, that does not originate from any of
+    /// the input wasm tables.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial` is greater than `max`.
+    pub fn add_funcref(&mut self, initial: u32, max: Option<u32>) -> TableId {
+        check_limits(initial, max);
+        self.add_local(initial, max, ValType::Funcref)
+    }
+
+    /// Construct a new `externref` table, that does not originate from any of
+    /// the input wasm tables.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial` is greater than `max`.
+    pub fn add_externref(&mut self, initial: u32, max: Option<u32>) -> TableId {
+        check_limits(initial, max);
+        self.add_local(initial, max, ValType::Externref)
+    }
+
+    /// Adds a new imported `funcref` table to this list of tables.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial` is greater than `max`.
+    pub fn add_import_funcref(
+        &mut self,
+        initial: u32,
+        max: Option<u32>,
+        import: ImportId,
+    ) -> TableId {
+        check_limits(initial, max);
+        self.add_import(initial, max, ValType::Funcref, import)
+    }
+
+    /// Adds a new imported `externref` table to this list of tables.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial` is greater than `max`.
+    pub fn add_import_externref(
+        &mut self,
+        initial: u32,
+        max: Option<u32>,
+        import: ImportId,
+    ) -> TableId {
+        check_limits(initial, max);
+        self.add_import(initial, max, ValType::Externref, import)
+    }
+
     /// Returns the actual table associated with an ID
     pub fn get(&self, table: TableId) -> &Table {
         &self.arena[table]
@@ -112,6 +217,17 @@ impl ModuleTables {
         self.arena.iter().map(|p| p.1)
     }
 
+    /// Iterates over the tables defined in this module, skipping imported
+    /// tables.
+    pub fn iter_local(&self) -> impl Iterator<Item = &Table> {
+        self.iter().filter(|t| !t.is_imported())
+    }
+
+    /// Iterates over this module's imported tables.
+    pub fn iter_imported(&self) -> impl Iterator<Item = &Table> {
+        self.iter().filter(|t| t.is_imported())
+    }
+
     /// Finds a unique function table in a module.
     ///
     /// Modules produced by compilers like LLVM typically have one function
@@ -141,6 +257,61 @@ impl ModuleTables {
 }
 
 impl Module {
+    /// Change the type of the elements in `table` to `ty`.
+    ///
+    /// Unlike assigning to `Table::element_ty` directly, this checks that the
+    /// module stays valid with the new type, and returns an error without
+    /// changing anything if it wouldn't. That is the case when:
+    ///
+    /// * `ty` isn't a reference type,
+    /// * the table is used by `call_indirect` and `ty` isn't `funcref`,
+    /// * an active element segment for the table, or a segment that
+    ///   `table.init` copies into it, holds elements of a different type,
+    /// * `table.copy` copies between the table and a table of a different
+    ///   type, or
+    /// * `table.get`, `table.set`, `table.grow`, or `table.fill` move the
+    ///   table's elements to or from the operand stack, where the surrounding
+    ///   code expects the old type.
+    ///
+    /// Setting a table's element type to the type it already has always
+    /// succeeds.
+    pub fn set_table_element_type(&mut self, table: TableId, ty: ValType) -> Result<()> {
+        if self.tables.get(table).element_ty == ty {
+            return Ok(());
+        }
+        match ty {
+            ValType::Funcref | ValType::Externref => {}
+            _ => bail!("{} is not a valid table element type", ty),
+        }
+
+        for elem in self.elements.iter() {
+            match elem.kind {
+                ElementKind::Active { table: t, .. } if t == table && elem.ty != ty => bail!(
+                    "table is initialized by an active element segment of type {}",
+                    elem.ty
+                ),
+                _ => {}
+            }
+        }
+
+        let mut uses = TableUses {
+            tables: &self.tables,
+            elements: &self.elements,
+            table,
+            ty,
+            error: None,
+        };
+        for (_, func) in self.funcs.iter_local() {
+            dfs_in_order(&mut uses, func, func.entry_block());
+            if let Some(error) = &uses.error {
+                bail!("table is used by {}", error);
+            }
+        }
+
+        self.tables.get_mut(table).element_ty = ty;
+        Ok(())
+    }
+
     /// Construct a new, empty set of tables for a module.
     pub(crate) fn parse_tables(
         &mut self,
@@ -150,6 +321,8 @@ impl Module {
         log::debug!("parse table section");
         for t in section {
             let t = t?;
+            self.config
+                .check_limit(Limit::TableSize, t.limits.initial.into())?;
             let id = self.tables.add_local(
                 t.limits.initial,
                 t.limits.maximum,
@@ -161,6 +334,66 @@ impl Module {
     }
 }
 
+/// Finds the first instruction that would be invalid if `table`'s element
+/// type became `ty`.
+struct TableUses<'a> {
+    tables: &'a ModuleTables,
+    elements: &'a ModuleElements,
+    table: TableId,
+    ty: ValType,
+    error: Option<String>,
+}
+
+impl<'instr> Visitor<'instr> for TableUses<'_> {
+    fn visit_instr(&mut self, instr: &'instr Instr, _: &'instr InstrLocId) {
+        if self.error.is_some() {
+            return;
+        }
+        self.error = match instr {
+            Instr::CallIndirect(CallIndirect { table, .. })
+                if *table == self.table && self.ty != ValType::Funcref =>
+            {
+                Some("`call_indirect`, which requires a funcref table".to_string())
+            }
+            Instr::TableInit(TableInit { table, elem }) if *table == self.table => {
+                let elem_ty = self.elements.get(*elem).ty;
+                if elem_ty != self.ty {
+                    Some(format!(
+                        "`table.init` of an element segment of type {}",
+                        elem_ty
+                    ))
+                } else {
+                    None
+                }
+            }
+            Instr::TableCopy(TableCopy { src, dst })
+                if *src == self.table || *dst == self.table =>
+            {
+                let other = if *src == self.table { *dst } else { *src };
+                let other_ty = self.tables.get(other).element_ty;
+                if other != self.table && other_ty != self.ty {
+                    Some(format!("`table.copy` with a table of type {}", other_ty))
+                } else {
+                    None
+                }
+            }
+            Instr::TableGet(TableGet { table }) if *table == self.table => {
+                Some("`table.get`".to_string())
+            }
+            Instr::TableSet(TableSet { table }) if *table == self.table => {
+                Some("`table.set`".to_string())
+            }
+            Instr::TableGrow(TableGrow { table }) if *table == self.table => {
+                Some("`table.grow`".to_string())
+            }
+            Instr::TableFill(TableFill { table }) if *table == self.table => {
+                Some("`table.fill`".to_string())
+            }
+            _ => None,
+        };
+    }
+}
+
 impl Emit for ModuleTables {
     fn emit(&self, cx: &mut EmitContext) {
         log::debug!("emit table section");
@@ -178,3 +411,29 @@ impl Emit for ModuleTables {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Module;
+
+    #[test]
+    fn widen_to_only_grows() {
+        let mut module = Module::default();
+        let id = module.tables.add_funcref(2, Some(10));
+        let table = module.tables.get_mut(id);
+
+        table.widen_to(1, Some(20)).unwrap();
+        assert_eq!(table.initial, 2);
+        assert_eq!(table.maximum, Some(20));
+
+        table.widen_to(5, Some(8)).unwrap();
+        assert_eq!(table.initial, 5);
+        assert_eq!(table.maximum, Some(20));
+
+        table.widen_to(5, None).unwrap();
+        assert_eq!(table.maximum, None);
+
+        assert!(table.widen_to(3, Some(1)).is_err());
+        assert_eq!(table.initial, 5);
+    }
+}
@@ -2,13 +2,24 @@
 
 use crate::arena_set::ArenaSet;
 use crate::emit::{Emit, EmitContext, Section};
-use crate::error::Result;
+use crate::error::{Limit, Result};
+use crate::ir::{dfs_in_order, CallIndirect, InstrSeq, InstrSeqId, InstrSeqType, Visitor};
 use crate::module::Module;
-use crate::parse::IndicesToIds;
+use crate::parse::{capacity_hint, IndicesToIds};
 use crate::ty::{Type, TypeId, ValType};
+use crate::{FunctionId, FunctionKind};
+use std::collections::HashMap;
+use wasmparser::SectionReader;
 
 /// The set of de-duplicated types within a module.
-#[derive(Debug, Default)]
+///
+/// Structurally identical function types always share one `TypeId`, whether
+/// they are added with `add` or parsed. A type section that repeats a type,
+/// as some tools emit, is merged as it's parsed, so every function, import,
+/// `call_indirect` and block type refers to the same id. Type indices are
+/// assigned when the module is emitted, so the emitted type section only
+/// contains each type once.
+#[derive(Clone, Debug, Default)]
 pub struct ModuleTypes {
     arena: ArenaSet<Type>,
 }
@@ -25,6 +36,9 @@ impl ModuleTypes {
     }
 
     /// Get the parameters and results for the given type.
+    ///
+    /// This is a shorthand for getting the type with `get` and then calling
+    /// `Type::params` and `Type::results` on it.
     pub fn params_results(&self, id: TypeId) -> (&[ValType], &[ValType]) {
         let ty = self.get(id);
         (ty.params(), ty.results())
@@ -110,7 +124,71 @@ impl ModuleTypes {
     }
 }
 
+/// A place where a type is used, as reported by `Module::used_types`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TypeUse {
+    /// The type is the signature of this function, which may be local or
+    /// imported.
+    FunctionSignature(FunctionId),
+    /// A `call_indirect` instruction in this function's body calls through the
+    /// type.
+    CallIndirect(FunctionId),
+    /// A block, loop, or `if` in this function's body has the type as its
+    /// multi-value block type.
+    BlockType(FunctionId, InstrSeqId),
+}
+
 impl Module {
+    /// Find every type that is used in this module, along with the places that
+    /// use it.
+    ///
+    /// Types that aren't used at all are left out of the map. The uses of each
+    /// type are listed in a deterministic order: function by function, in the
+    /// order the functions were added to the module, with a function's
+    /// signature first and then the uses in its body in the order they appear.
+    pub fn used_types(&self) -> HashMap<TypeId, Vec<TypeUse>> {
+        let mut uses = TypeUses::default();
+        for func in self.funcs.iter() {
+            uses.push(func.ty(), TypeUse::FunctionSignature(func.id()));
+            if let FunctionKind::Local(local) = &func.kind {
+                uses.func = Some((func.id(), local.entry_block()));
+                dfs_in_order(&mut uses, local, local.entry_block());
+            }
+        }
+        return uses.map;
+
+        #[derive(Default)]
+        struct TypeUses {
+            map: HashMap<TypeId, Vec<TypeUse>>,
+            func: Option<(FunctionId, InstrSeqId)>,
+        }
+
+        impl TypeUses {
+            fn push(&mut self, ty: TypeId, use_: TypeUse) {
+                self.map.entry(ty).or_insert_with(Vec::new).push(use_);
+            }
+        }
+
+        impl<'instr> Visitor<'instr> for TypeUses {
+            fn start_instr_seq(&mut self, seq: &'instr InstrSeq) {
+                let (func, entry) = self.func.unwrap();
+                // A function's entry sequence is given a type for its results,
+                // but it isn't a block, so that type doesn't appear anywhere
+                // in the emitted wasm.
+                if let InstrSeqType::MultiValue(ty) = seq.ty {
+                    if seq.id() != entry {
+                        self.push(ty, TypeUse::BlockType(func, seq.id()));
+                    }
+                }
+            }
+
+            fn visit_call_indirect(&mut self, call: &CallIndirect) {
+                let (func, _) = self.func.unwrap();
+                self.push(call.ty, TypeUse::CallIndirect(func));
+            }
+        }
+    }
+
     /// Construct the set of types within a module.
     pub(crate) fn parse_types(
         &mut self,
@@ -118,6 +196,13 @@ impl Module {
         ids: &mut IndicesToIds,
     ) -> Result<()> {
         log::debug!("parsing type section");
+        self.config
+            .check_limit(Limit::Types, section.get_count().into())?;
+        // Each function type takes at least 3 bytes: 0x60 and two empty
+        // vectors.
+        let capacity = capacity_hint(section.get_count(), section.range(), 3);
+        self.types.arena.reserve(capacity);
+        ids.reserve_types(capacity);
         for ty in section {
             let fun_ty = match ty? {
                 wasmparser::TypeDef::Func(ty) => ty,
@@ -2,6 +2,7 @@ use crate::map::IdHashMap;
 use crate::{DataId, ElementId, Function, FunctionId, GlobalId, Result};
 use crate::{LocalId, MemoryId, TableId, TypeId};
 use anyhow::bail;
+use std::cmp;
 
 /// Maps from old indices in the original Wasm binary to `walrus` IDs.
 ///
@@ -26,6 +27,7 @@ pub struct IndicesToIds {
     elements: Vec<ElementId>,
     data: Vec<DataId>,
     locals: IdHashMap<Function, Vec<LocalId>>,
+    code_offsets: IdHashMap<Function, usize>,
 }
 
 macro_rules! define_push_get {
@@ -63,7 +65,29 @@ define_push_get!(push_memory, get_memory, MemoryId, memories);
 define_push_get!(push_element, get_element, ElementId, elements);
 define_push_get!(push_data, get_data, DataId, data);
 
+/// How many items to preallocate space for when parsing a section that
+/// declares `count` items.
+///
+/// The count comes straight from the binary, so it is capped by how many items
+/// could actually fit in the section, assuming each takes at least `min_size`
+/// bytes. Otherwise a tiny module could make us allocate lots of memory up
+/// front.
+pub(crate) fn capacity_hint(count: u32, range: wasmparser::Range, min_size: usize) -> usize {
+    let max = (range.end - range.start) / min_size;
+    cmp::min(count as usize, max)
+}
+
 impl IndicesToIds {
+    /// Preallocate space for `additional` more type IDs.
+    pub(crate) fn reserve_types(&mut self, additional: usize) {
+        self.types.reserve(additional);
+    }
+
+    /// Preallocate space for `additional` more function IDs.
+    pub(crate) fn reserve_funcs(&mut self, additional: usize) {
+        self.funcs.reserve(additional);
+    }
+
     /// Pushes a new local ID to map it to the next index internally
     pub(crate) fn push_local(&mut self, function: FunctionId, id: LocalId) -> u32 {
         let list = self.locals.entry(function).or_insert(Vec::new());
@@ -90,3 +114,26 @@ impl IndicesToIds {
         }
     }
 }
+
+impl IndicesToIds {
+    /// Records the offset of a local function's body in the original Wasm
+    pub(crate) fn set_func_code_offset(&mut self, function: FunctionId, offset: usize) {
+        self.code_offsets.insert(function, offset);
+    }
+
+    /// Gets the offset, within the original Wasm binary, of a local function's
+    /// body.
+    ///
+    /// This is the offset of the function's locals declarations, just after
+    /// the body's size. Offsets of instructions relative to their function,
+    /// as found in some custom sections, are relative to this position.
+    pub fn get_func_code_offset(&self, function: FunctionId) -> Result<usize> {
+        match self.code_offsets.get(&function) {
+            Some(x) => Ok(*x),
+            None => bail!(
+                "function index `{}` does not have a code offset",
+                function.index()
+            ),
+        }
+    }
+}
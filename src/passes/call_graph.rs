@@ -0,0 +1,193 @@
+//! The call graph of a module's functions.
+
+use crate::ir::*;
+use crate::map::{IdHashMap, IdHashSet};
+use crate::{ElementKind, Function, FunctionId, Module, TableId, TypeId};
+
+/// How a `CallGraph` treats `call_indirect` instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndirectCalls {
+    /// Ignore `call_indirect` instructions, so that the graph only contains
+    /// direct calls.
+    Ignore,
+    /// Conservatively treat a `call_indirect` as a call to every function with
+    /// the same type that is in an element segment which could be used to
+    /// initialize its table: active segments for that table, and all passive
+    /// segments.
+    ///
+    /// Functions that end up in the table some other way, such as through
+    /// `ref.func` and `table.set`, are not taken into account.
+    TypeCompatible,
+}
+
+/// The call graph of a module's functions.
+///
+/// Each function has an edge to each function it calls. Imported functions
+/// are part of the graph, but never have any outgoing edges.
+#[derive(Debug)]
+pub struct CallGraph {
+    funcs: Vec<FunctionId>,
+    callees: IdHashMap<Function, Vec<FunctionId>>,
+}
+
+impl CallGraph {
+    /// Build the call graph of the given module's functions.
+    pub fn new(module: &Module, indirect: IndirectCalls) -> CallGraph {
+        let mut graph = CallGraph {
+            funcs: module.funcs.iter().map(|f| f.id()).collect(),
+            callees: Default::default(),
+        };
+
+        for (id, func) in module.funcs.iter_local() {
+            let mut calls = Calls::default();
+            dfs_in_order(&mut calls, func, func.entry_block());
+
+            let mut seen = IdHashSet::default();
+            let mut callees = Vec::new();
+            let mut add = |callee| {
+                if seen.insert(callee) {
+                    callees.push(callee);
+                }
+            };
+            calls.direct.into_iter().for_each(&mut add);
+            if indirect == IndirectCalls::TypeCompatible {
+                for (ty, table) in calls.indirect {
+                    indirect_targets(module, ty, table).for_each(&mut add);
+                }
+            }
+            graph.callees.insert(id, callees);
+        }
+
+        graph
+    }
+
+    /// The functions that `func` calls, without duplicates, in the order they
+    /// are first called in its body.
+    pub fn callees(&self, func: FunctionId) -> &[FunctionId] {
+        self.callees
+            .get(&func)
+            .map_or(&[][..], |callees| &callees[..])
+    }
+
+    /// Compute the strongly connected components of this call graph using
+    /// Tarjan's algorithm.
+    ///
+    /// Each component is a group of functions that are all (possibly
+    /// indirectly) recursive with each other. A function that isn't part of
+    /// any cycle is in a component of its own; use `callees` to tell whether
+    /// such a function calls itself.
+    ///
+    /// Components are returned in reverse topological order: every function
+    /// called from a component is either in the same component or in one that
+    /// comes before it.
+    pub fn sccs(&self) -> Vec<Vec<FunctionId>> {
+        struct Node {
+            index: usize,
+            lowlink: usize,
+            on_stack: bool,
+        }
+
+        let mut nodes: IdHashMap<Function, Node> = Default::default();
+        let mut stack = Vec::new();
+        let mut sccs = Vec::new();
+
+        for &root in &self.funcs {
+            if nodes.contains_key(&root) {
+                continue;
+            }
+
+            // An explicit stack of the functions being visited, along with the
+            // index of the next callee to visit, to avoid overflowing the
+            // native stack on deep call graphs.
+            let mut work = vec![(root, 0)];
+            while let Some((func, i)) = work.pop() {
+                if i == 0 {
+                    let index = nodes.len();
+                    nodes.insert(
+                        func,
+                        Node {
+                            index,
+                            lowlink: index,
+                            on_stack: true,
+                        },
+                    );
+                    stack.push(func);
+                }
+
+                if let Some(&callee) = self.callees(func).get(i) {
+                    work.push((func, i + 1));
+                    match nodes.get(&callee) {
+                        None => work.push((callee, 0)),
+                        Some(node) if node.on_stack => {
+                            let index = node.index;
+                            let node = nodes.get_mut(&func).unwrap();
+                            node.lowlink = node.lowlink.min(index);
+                        }
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+
+                // We've visited all of this function's callees, so propagate
+                // its lowlink to its caller, and pop its component off the
+                // stack if it is the component's root.
+                let (index, lowlink) = {
+                    let node = &nodes[&func];
+                    (node.index, node.lowlink)
+                };
+                if let Some(&(caller, _)) = work.last() {
+                    let caller = nodes.get_mut(&caller).unwrap();
+                    caller.lowlink = caller.lowlink.min(lowlink);
+                }
+                if index == lowlink {
+                    let mut scc = Vec::new();
+                    loop {
+                        let member = stack.pop().unwrap();
+                        nodes.get_mut(&member).unwrap().on_stack = false;
+                        scc.push(member);
+                        if member == func {
+                            break;
+                        }
+                    }
+                    sccs.push(scc);
+                }
+            }
+        }
+
+        sccs
+    }
+}
+
+fn indirect_targets<'a>(
+    module: &'a Module,
+    ty: TypeId,
+    table: TableId,
+) -> impl Iterator<Item = FunctionId> + 'a {
+    let ty = module.types.get(ty);
+    module
+        .elements
+        .iter()
+        .filter(move |elem| match elem.kind {
+            ElementKind::Active { table: t, .. } => t == table,
+            ElementKind::Passive => true,
+            ElementKind::Declared => false,
+        })
+        .flat_map(|elem| elem.members.iter().filter_map(|member| *member))
+        .filter(move |&func| module.types.get(module.funcs.get(func).ty()) == ty)
+}
+
+#[derive(Default)]
+struct Calls {
+    direct: Vec<FunctionId>,
+    indirect: Vec<(TypeId, TableId)>,
+}
+
+impl<'instr> Visitor<'instr> for Calls {
+    fn visit_call(&mut self, call: &Call) {
+        self.direct.push(call.func);
+    }
+
+    fn visit_call_indirect(&mut self, call: &CallIndirect) {
+        self.indirect.push((call.ty, call.table));
+    }
+}
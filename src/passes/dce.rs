@@ -0,0 +1,95 @@
+//! Removes dead instructions that follow unconditional control transfers.
+
+use crate::ir::*;
+use crate::Module;
+
+/// Remove unreachable instructions from all of the module's local functions.
+///
+/// Any instructions in an instruction sequence that follow an unconditional
+/// branch (`br`, `br_table`, `return`, or `unreachable`) can never execute, so
+/// they are removed, up to the end of the sequence.
+///
+/// Only the instructions are removed: every sequence keeps its declared type.
+/// That is still valid, because the operand stack is polymorphic after an
+/// unconditional branch, so the sequence is well-typed no matter what results
+/// its type says it leaves on the stack.
+pub fn dce(module: &mut Module) {
+    for (_, func) in module.funcs.iter_local_mut() {
+        for seq in instr_seq_ids(func) {
+            let instrs = &mut func.block_mut(seq).instrs;
+            if let Some(end) = instrs
+                .iter()
+                .position(|(instr, _)| instr.following_instructions_are_unreachable())
+            {
+                instrs.truncate(end + 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::passes::test_util::entry_instrs;
+    use crate::{FunctionBuilder, ValType};
+
+    #[test]
+    fn removes_code_after_return() {
+        let mut module = Module::default();
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+        builder
+            .func_body()
+            .i32_const(1)
+            .return_()
+            .i32_const(2)
+            .drop()
+            .unreachable();
+        builder.finish(vec![], &mut module.funcs);
+
+        dce(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 2);
+        assert!(instrs[0].is_const());
+        assert!(instrs[1].is_return());
+        module.emit_wasm();
+    }
+
+    #[test]
+    fn removes_code_after_br_in_nested_block() {
+        let mut module = Module::default();
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+        builder.func_body().block(ValType::I32, |block| {
+            let id = block.id();
+            block.i32_const(1).br(id).i32_const(2).drop();
+        });
+        builder.finish(vec![], &mut module.funcs);
+
+        dce(&mut module);
+
+        let (_, func) = module.funcs.iter_local().next().unwrap();
+        let block = match &func.block(func.entry_block())[0].0 {
+            Instr::Block(Block { seq }) => func.block(*seq),
+            other => panic!("expected a block, found {:?}", other),
+        };
+        assert_eq!(block.ty, InstrSeqType::Simple(Some(ValType::I32)));
+        assert_eq!(block.len(), 2);
+        assert!(block[1].0.is_br());
+    }
+
+    #[test]
+    fn keeps_reachable_code() {
+        let mut module = Module::default();
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+        builder
+            .func_body()
+            .i32_const(1)
+            .i32_const(2)
+            .binop(BinaryOp::I32Add);
+        builder.finish(vec![], &mut module.funcs);
+
+        dce(&mut module);
+
+        assert_eq!(entry_instrs(&module).len(), 3);
+    }
+}
@@ -12,6 +12,16 @@ use id_arena::Id;
 pub fn run(m: &mut Module) {
     let used = Used::new(m);
 
+    let unrooted_exports: Vec<_> = m
+        .exports
+        .iter()
+        .filter(|e| !m.config.export_is_root(e))
+        .map(|e| e.id())
+        .collect();
+    for id in unrooted_exports {
+        m.exports.delete(id);
+    }
+
     let mut unused_imports = Vec::new();
     for import in m.imports.iter() {
         let used = match &import.kind {
@@ -0,0 +1,161 @@
+//! Hoists constants that are used many times within a function into locals.
+
+use crate::ir::*;
+use crate::{LocalFunction, Module, ModuleLocals};
+use std::collections::HashMap;
+
+/// The size, in bytes, that a `local.get` is assumed to take up when encoded:
+/// an opcode and a single-byte LEB128 local index.
+const LOCAL_GET_SIZE: usize = 2;
+
+/// Hoist constants that are used more than `threshold` times within a function
+/// into a local in all of the module's local functions.
+///
+/// Each such constant is computed once, at the start of the function, and
+/// stored into a fresh local with `local.set`; every use of the constant is
+/// then replaced with a `local.get` of that local. The local is set before
+/// anything else in the function runs, so every `local.get` sees the
+/// constant's value, and the function behaves exactly as before.
+///
+/// Constants that encode to no more than a `local.get` does, such as small
+/// `i32.const`s, are cheaper to rematerialize than to load and are never
+/// hoisted. This pays off for large `i64`, `f64`, and `v128` constants that
+/// are repeated throughout generated code.
+pub fn hoist_constants(module: &mut Module, threshold: usize) {
+    for (_, func) in module.funcs.iter_local_mut() {
+        hoist_func(func, &mut module.locals, threshold);
+    }
+}
+
+fn hoist_func(func: &mut LocalFunction, locals: &mut ModuleLocals, threshold: usize) {
+    let mut counts = Counts::default();
+    dfs_in_order(&mut counts, func, func.entry_block());
+
+    let mut hoisted = HashMap::new();
+    let mut prologue = Vec::new();
+    for value in counts.order {
+        if counts.uses[&value] <= threshold || encoded_size(value) <= LOCAL_GET_SIZE {
+            continue;
+        }
+        let local = locals.add(value.ty());
+        hoisted.insert(value, local);
+        prologue.push((Instr::const_(value), InstrLocId::default()));
+        prologue.push((LocalSet { local }.into(), InstrLocId::default()));
+    }
+    if hoisted.is_empty() {
+        return;
+    }
+
+    let entry = func.entry_block();
+    dfs_pre_order_mut(&mut Replace { hoisted }, func, entry);
+    func.block_mut(entry).instrs.splice(0..0, prologue);
+}
+
+/// Counts the uses of each constant, remembering the order in which they are
+/// first seen so that the hoisted locals are deterministic.
+#[derive(Default)]
+struct Counts {
+    uses: HashMap<ConstValue, usize>,
+    order: Vec<ConstValue>,
+}
+
+impl<'instr> Visitor<'instr> for Counts {
+    fn visit_const(&mut self, c: &Const) {
+        let value = ConstValue::from(c.value);
+        let uses = self.uses.entry(value).or_insert(0);
+        if *uses == 0 {
+            self.order.push(value);
+        }
+        *uses += 1;
+    }
+}
+
+struct Replace {
+    hoisted: HashMap<ConstValue, LocalId>,
+}
+
+impl VisitorMut for Replace {
+    fn visit_instr_mut(&mut self, instr: &mut Instr, _: &mut InstrLocId) {
+        if let Some(local) = instr.as_const().and_then(|v| self.hoisted.get(&v)) {
+            *instr = LocalGet { local: *local }.into();
+        }
+    }
+}
+
+/// The size of a `*.const` instruction with the given value, in bytes.
+fn encoded_size(value: ConstValue) -> usize {
+    match value {
+        ConstValue::I32(n) => 1 + sleb128_size(n.into()),
+        ConstValue::I64(n) => 1 + sleb128_size(n),
+        ConstValue::F32(_) => 1 + 4,
+        ConstValue::F64(_) => 1 + 8,
+        ConstValue::V128(_) => 2 + 16,
+    }
+}
+
+fn sleb128_size(mut n: i64) -> usize {
+    let mut size = 1;
+    loop {
+        let byte = n & 0x7f;
+        n >>= 7;
+        if (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0) {
+            return size;
+        }
+        size += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::passes::test_util::entry_instrs;
+    use crate::FunctionBuilder;
+
+    #[test]
+    fn hoists_repeated_large_constants() {
+        let mut module = Module::default();
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+        let mut body = builder.func_body();
+        for _ in 0..3 {
+            body.i64_const(0x1234_5678_9abc).drop();
+            body.block(None, |block| {
+                block.i64_const(0x1234_5678_9abc).drop();
+            });
+        }
+        builder.finish(vec![], &mut module.funcs);
+
+        hoist_constants(&mut module, 2);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(
+            instrs[0].as_const(),
+            Some(ConstValue::I64(0x1234_5678_9abc))
+        );
+        let local = instrs[1].unwrap_local_set().local;
+        assert_eq!(instrs[2].unwrap_local_get().local, local);
+        assert!(instrs[2..].iter().all(|i| !i.is_const()));
+
+        let (_, func) = module.funcs.iter_local().next().unwrap();
+        let block = instrs[4].unwrap_block().seq;
+        assert_eq!(func.block(block)[0].0.unwrap_local_get().local, local);
+        module.emit_wasm();
+    }
+
+    #[test]
+    fn leaves_small_and_rare_constants() {
+        let mut module = Module::default();
+        let mut builder = FunctionBuilder::new(&mut module.types, &[], &[]);
+        let mut body = builder.func_body();
+        for _ in 0..10 {
+            body.i32_const(1).drop();
+        }
+        body.f64_const(1.5).drop().f64_const(1.5).drop();
+        builder.finish(vec![], &mut module.funcs);
+
+        hoist_constants(&mut module, 2);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 24);
+        assert!(instrs.iter().all(|i| !i.is_local_get()));
+    }
+}
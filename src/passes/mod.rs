@@ -1,5 +1,17 @@
 //! Passes over whole modules or individual functions.
 
+mod call_graph;
+mod dce;
 pub mod gc;
+mod hoist_constants;
+mod simplify_if;
+mod strip_debug;
+#[cfg(test)]
+mod test_util;
 mod used;
+pub use self::call_graph::{CallGraph, IndirectCalls};
+pub use self::dce::dce;
+pub use self::hoist_constants::hoist_constants;
+pub use self::simplify_if::simplify_if;
+pub use self::strip_debug::strip_debug;
 pub use self::used::Roots;
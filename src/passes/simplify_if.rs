@@ -0,0 +1,351 @@
+//! Simplifies `if`/`else` instructions whose arms are trivial.
+
+use crate::ir::*;
+use crate::{LocalFunction, Module, ValType};
+use std::mem;
+
+/// Simplify trivial `if`/`else` instructions in all of the module's local
+/// functions.
+///
+/// Two patterns are rewritten:
+///
+/// * An `if` without results whose arms are both pure (see `Instr::is_pure`)
+///   is replaced with a `drop` of its condition. This includes an `if` whose
+///   arms are both empty. An `if` with an empty `else` arm is still
+///   conditional logic, so it is only removed if its `then` arm is pure too.
+///
+/// * An `if` with a single result whose arms are both pure, and whose
+///   condition is produced by the instruction right before it, if that is a
+///   pure instruction without any operands such as a `*.const`, `local.get` or
+///   `global.get`, is replaced with a `select`:
+///
+///   ```wat
+///   local.get $cond
+///   if (result i32)
+///     i32.const 1
+///   else
+///     i32.const 2
+///   end
+///   ```
+///
+///   becomes
+///
+///   ```wat
+///   i32.const 1
+///   i32.const 2
+///   local.get $cond
+///   select
+///   ```
+///
+/// Both arms and the condition are free of side effects in these cases, so
+/// evaluating both arms and reordering them before the condition is not
+/// observable. Any other `if` is left alone.
+pub fn simplify_if(module: &mut Module) {
+    for (_, func) in module.funcs.iter_local_mut() {
+        simplify_func(func);
+    }
+}
+
+fn simplify_func(func: &mut LocalFunction) {
+    // Collect the sequences up front, in pre-order, so that we always rewrite
+    // an `if` before the arms it reads from get taken out below.
+    for seq in instr_seq_ids(func) {
+        let instrs = mem::take(&mut func.block_mut(seq).instrs);
+        let mut simplified = Vec::with_capacity(instrs.len());
+        for (instr, loc) in instrs {
+            if let Instr::IfElse(IfElse {
+                consequent,
+                alternative,
+            }) = instr
+            {
+                if simplify_if_else(func, consequent, alternative, loc, &mut simplified) {
+                    continue;
+                }
+            }
+            simplified.push((instr, loc));
+        }
+        func.block_mut(seq).instrs = simplified;
+    }
+}
+
+/// Try to simplify the given `if`, appending its replacement to `seq` if we
+/// can. Returns whether the `if` was simplified.
+fn simplify_if_else(
+    func: &LocalFunction,
+    consequent: InstrSeqId,
+    alternative: InstrSeqId,
+    loc: InstrLocId,
+    seq: &mut Vec<(Instr, InstrLocId)>,
+) -> bool {
+    let consequent = func.block(consequent);
+    let alternative = func.block(alternative);
+    if !is_pure_seq(consequent) || !is_pure_seq(alternative) {
+        return false;
+    }
+    match consequent.ty {
+        InstrSeqType::Simple(None) => {
+            seq.push((Drop {}.into(), loc));
+            true
+        }
+        InstrSeqType::Simple(Some(ty)) => {
+            // Both arms must produce their result; an empty arm here is only
+            // possible in unreachable code.
+            if consequent.is_empty() || alternative.is_empty() {
+                return false;
+            }
+            let movable = match seq.last() {
+                Some((instr, _)) => is_movable_condition(instr),
+                None => false,
+            };
+            if !movable {
+                return false;
+            }
+            let condition = seq.pop().unwrap();
+            seq.extend(consequent.iter().cloned());
+            seq.extend(alternative.iter().cloned());
+            seq.push(condition);
+            // Only numeric and vector types can use the untyped `select`.
+            let ty = match ty {
+                ValType::Externref | ValType::Funcref => Some(ty),
+                _ => None,
+            };
+            seq.push((Select { ty }.into(), loc));
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_pure_seq(seq: &InstrSeq) -> bool {
+    seq.iter().all(|(instr, _)| instr.is_pure())
+}
+
+/// Can this instruction, which produces an `if`'s condition, be moved after
+/// the `if`'s arms? It must be pure, and not take any operands, since those
+/// would be left behind.
+fn is_movable_condition(instr: &Instr) -> bool {
+    matches!(
+        instr,
+        Instr::Const(..)
+            | Instr::LocalGet(..)
+            | Instr::GlobalGet(..)
+            | Instr::MemorySize(..)
+            | Instr::TableSize(..)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::passes::test_util::entry_instrs;
+    use crate::{FunctionBuilder, InitExpr, ValType};
+
+    #[test]
+    fn empty_arms_become_drop() {
+        let mut module = Module::default();
+        let cond = module.locals.add(ValType::I32);
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
+        builder
+            .func_body()
+            .local_get(cond)
+            .if_else(None, |_| {}, |_| {});
+        builder.finish(vec![cond], &mut module.funcs);
+
+        simplify_if(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 2);
+        assert!(instrs[0].is_local_get());
+        assert!(instrs[1].is_drop());
+    }
+
+    #[test]
+    fn constant_arms_become_select() {
+        let mut module = Module::default();
+        let cond = module.locals.add(ValType::I32);
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+        builder.func_body().local_get(cond).if_else(
+            ValType::I32,
+            |then| {
+                then.i32_const(1);
+            },
+            |else_| {
+                else_.i32_const(2);
+            },
+        );
+        builder.finish(vec![cond], &mut module.funcs);
+
+        simplify_if(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 4);
+        match (&instrs[0], &instrs[1]) {
+            (
+                Instr::Const(Const {
+                    value: Value::I32(1),
+                }),
+                Instr::Const(Const {
+                    value: Value::I32(2),
+                }),
+            ) => {}
+            other => panic!("unexpected arms: {:?}", other),
+        }
+        assert!(instrs[2].is_local_get());
+        assert!(instrs[3].is_select());
+    }
+
+    #[test]
+    fn side_effecting_arms_are_kept() {
+        let mut module = Module::default();
+        let cond = module.locals.add(ValType::I32);
+        let local = module.locals.add(ValType::I32);
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+        builder.func_body().local_get(cond).if_else(
+            ValType::I32,
+            |then| {
+                then.i32_const(1).local_tee(local);
+            },
+            |else_| {
+                else_.i32_const(2);
+            },
+        );
+        builder.finish(vec![cond], &mut module.funcs);
+
+        simplify_if(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 2);
+        assert!(instrs[1].is_if_else());
+    }
+
+    #[test]
+    fn empty_else_with_pure_then_becomes_drop() {
+        let mut module = Module::default();
+        let cond = module.locals.add(ValType::I32);
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
+        builder.func_body().local_get(cond).if_else(
+            None,
+            |then| {
+                then.i32_const(1).drop();
+            },
+            |_| {},
+        );
+        builder.finish(vec![cond], &mut module.funcs);
+
+        simplify_if(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 2);
+        assert!(instrs[1].is_drop());
+    }
+
+    #[test]
+    fn empty_else_with_side_effecting_then_is_kept() {
+        let mut module = Module::default();
+        let cond = module.locals.add(ValType::I32);
+        let global = module
+            .globals
+            .add_local(ValType::I32, true, InitExpr::Value(Value::I32(0)));
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
+        builder.func_body().local_get(cond).if_else(
+            None,
+            |then| {
+                then.i32_const(1).global_set(global);
+            },
+            |_| {},
+        );
+        builder.finish(vec![cond], &mut module.funcs);
+
+        simplify_if(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 2);
+        assert!(instrs[1].is_if_else());
+    }
+
+    #[test]
+    fn pure_arms_become_select() {
+        let mut module = Module::default();
+        let cond = module.locals.add(ValType::I32);
+        let x = module.locals.add(ValType::I32);
+        let mut builder = FunctionBuilder::new(
+            &mut module.types,
+            &[ValType::I32, ValType::I32],
+            &[ValType::I32],
+        );
+        builder.func_body().local_get(cond).if_else(
+            ValType::I32,
+            |then| {
+                then.local_get(x).i32_const(1).binop(BinaryOp::I32Add);
+            },
+            |else_| {
+                else_.local_get(x);
+            },
+        );
+        builder.finish(vec![cond, x], &mut module.funcs);
+
+        simplify_if(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 6);
+        assert!(instrs[3].is_local_get());
+        assert!(instrs[4].is_local_get());
+        assert!(instrs[5].is_select());
+    }
+
+    #[test]
+    fn trapping_arms_are_kept() {
+        let mut module = Module::default();
+        let cond = module.locals.add(ValType::I32);
+        let x = module.locals.add(ValType::I32);
+        let mut builder = FunctionBuilder::new(
+            &mut module.types,
+            &[ValType::I32, ValType::I32],
+            &[ValType::I32],
+        );
+        builder.func_body().local_get(cond).if_else(
+            ValType::I32,
+            |then| {
+                then.i32_const(1).local_get(x).binop(BinaryOp::I32DivU);
+            },
+            |else_| {
+                else_.i32_const(0);
+            },
+        );
+        builder.finish(vec![cond, x], &mut module.funcs);
+
+        simplify_if(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 2);
+        assert!(instrs[1].is_if_else());
+    }
+
+    #[test]
+    fn side_effecting_condition_is_kept() {
+        let mut module = Module::default();
+        let cond = module.locals.add(ValType::I32);
+        let local = module.locals.add(ValType::I32);
+        let mut builder = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+        builder
+            .func_body()
+            .local_get(cond)
+            .local_tee(local)
+            .if_else(
+                ValType::I32,
+                |then| {
+                    then.local_get(local);
+                },
+                |else_| {
+                    else_.i32_const(2);
+                },
+            );
+        builder.finish(vec![cond], &mut module.funcs);
+
+        simplify_if(&mut module);
+
+        let instrs = entry_instrs(&module);
+        assert_eq!(instrs.len(), 3);
+        assert!(instrs[2].is_if_else());
+    }
+}
@@ -0,0 +1,47 @@
+//! Strips all debugging information from a module.
+
+use crate::Module;
+
+/// Remove all debugging information from the module specified.
+///
+/// This removes:
+///
+/// * every custom section whose name starts with `.debug_`, which is where
+///   DWARF debug info lives,
+/// * the `name` section, by clearing the names of the module, its functions
+///   and its locals,
+///
+/// and disables `ModuleConfig::generate_dwarf` and
+/// `ModuleConfig::generate_name_section` so that neither is emitted by
+/// `Module::emit_wasm`. All other custom sections are left alone.
+///
+/// The `producers` section is kept as well, unless `strip_producers` is `true`,
+/// in which case it is cleared and `ModuleConfig::generate_producers_section`
+/// is disabled too.
+pub fn strip_debug(module: &mut Module, strip_producers: bool) {
+    let debug_sections = module
+        .customs
+        .iter()
+        .filter(|(_, section)| section.name().starts_with(".debug_"))
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    for id in debug_sections {
+        module.customs.delete(id);
+    }
+
+    module.name = None;
+    for func in module.funcs.iter_mut() {
+        func.name = None;
+    }
+    for local in module.locals.iter_mut() {
+        local.name = None;
+    }
+
+    module.config.generate_dwarf(false);
+    module.config.generate_name_section(false);
+
+    if strip_producers {
+        module.producers.clear();
+        module.config.generate_producers_section(false);
+    }
+}
@@ -0,0 +1,14 @@
+//! Helpers shared by the passes' unit tests.
+
+use crate::ir::Instr;
+use crate::Module;
+
+/// Get the instructions in the entry block of the module's first local
+/// function.
+pub(crate) fn entry_instrs(module: &Module) -> Vec<Instr> {
+    let (_, func) = module.funcs.iter_local().next().unwrap();
+    func.block(func.entry_block())
+        .iter()
+        .map(|(instr, _)| instr.clone())
+        .collect()
+}
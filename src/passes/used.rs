@@ -105,8 +105,11 @@ impl Used {
         log::debug!("starting to calculate used set");
         let mut stack = Roots::default();
 
-        // All exports are roots
+        // All exports are roots, unless the module is configured otherwise.
         for export in module.exports.iter() {
+            if !module.config.export_is_root(export) {
+                continue;
+            }
             match export.item {
                 ExportItem::Function(f) => stack.push_func(f),
                 ExportItem::Table(t) => stack.push_table(t),
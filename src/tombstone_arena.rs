@@ -1,6 +1,8 @@
 use crate::map::IdHashSet;
-use id_arena::Arena as InnerArena;
+use id_arena::{ArenaBehavior, DefaultArenaBehavior};
+use std::iter::Enumerate;
 use std::ops::{Index, IndexMut};
+use std::slice;
 
 #[cfg(feature = "parallel")]
 use rayon::iter::plumbing::UnindexedConsumer;
@@ -9,19 +11,26 @@ use rayon::prelude::*;
 
 pub use id_arena::Id;
 
-/// A wrapper around an `id_arena::Arena` that adds a tombstone set for deleting
-/// items.
-#[derive(Debug)]
+/// An arena of items indexed by `id_arena::Id`s, plus a tombstone set for
+/// deleting items.
+///
+/// This works like an `id_arena::Arena`, but keeps hold of its own storage so
+/// that it can be grown and shrunk after items have been allocated.
+#[derive(Clone, Debug)]
 pub struct TombstoneArena<T> {
-    inner: InnerArena<T>,
+    arena_id: u32,
+    items: Vec<T>,
     dead: IdHashSet<T>,
 }
 
+type Behavior<T> = DefaultArenaBehavior<T>;
+
 // Note: can't derive because that would require `T: Default`.
 impl<T> Default for TombstoneArena<T> {
     fn default() -> TombstoneArena<T> {
         TombstoneArena {
-            inner: Default::default(),
+            arena_id: Behavior::<T>::new_arena_id(),
+            items: Vec::new(),
             dead: Default::default(),
         }
     }
@@ -50,13 +59,45 @@ where
     pub fn delete(&mut self, id: Id<T>) {
         assert!(self.contains(id));
         self.dead.insert(id);
-        self.inner[id].on_delete();
+        self.items[id.index()].on_delete();
     }
 }
 
 impl<T> TombstoneArena<T> {
+    /// Preallocate space for `additional` more items.
+    pub fn reserve(&mut self, additional: usize) {
+        self.items.reserve(additional);
+    }
+
+    /// Release any spare capacity in the arena's storage.
+    ///
+    /// Note that deleted items keep their slots, since ids index directly into
+    /// the arena; only the unused capacity past the last item is freed.
+    pub fn shrink_to_fit(&mut self) {
+        self.items.shrink_to_fit();
+        self.dead.shrink_to_fit();
+    }
+
+    /// Copy this arena, cloning each item with `f`, such that ids into this
+    /// arena are valid in the copy too.
+    pub fn clone_with(&self, mut f: impl FnMut(Id<T>, &T) -> T) -> TombstoneArena<T> {
+        let arena_id = self.arena_id;
+        TombstoneArena {
+            arena_id,
+            items: self
+                .items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| f(Behavior::new_id(arena_id, i), item))
+                .collect(),
+            dead: self.dead.clone(),
+        }
+    }
+
     pub fn alloc(&mut self, val: T) -> Id<T> {
-        self.inner.alloc(val)
+        let id = self.next_id();
+        self.items.push(val);
+        id
     }
 
     pub fn alloc_with_id<F>(&mut self, f: F) -> Id<T>
@@ -68,43 +109,47 @@ impl<T> TombstoneArena<T> {
     }
 
     pub fn get(&self, id: Id<T>) -> Option<&T> {
-        if self.dead.contains(&id) {
+        if self.dead.contains(&id) || Behavior::arena_id(id) != self.arena_id {
             None
         } else {
-            self.inner.get(id)
+            self.items.get(id.index())
         }
     }
 
     pub fn get_mut(&mut self, id: Id<T>) -> Option<&mut T> {
-        if self.dead.contains(&id) {
+        if self.dead.contains(&id) || Behavior::arena_id(id) != self.arena_id {
             None
         } else {
-            self.inner.get_mut(id)
+            self.items.get_mut(id.index())
         }
     }
 
     pub fn next_id(&self) -> Id<T> {
-        self.inner.next_id()
+        Behavior::new_id(self.arena_id, self.items.len())
     }
 
     pub fn len(&self) -> usize {
-        self.inner.len() - self.dead.len()
+        self.items.len() - self.dead.len()
     }
 
     pub fn contains(&self, id: Id<T>) -> bool {
-        self.inner.get(id).is_some() && !self.dead.contains(&id)
+        self.get(id).is_some()
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (Id<T>, &T)> {
-        self.inner
+        let arena_id = self.arena_id;
+        self.items
             .iter()
+            .enumerate()
+            .map(move |(i, item)| (Behavior::new_id(arena_id, i), item))
             .filter(move |&(id, _)| !self.dead.contains(&id))
     }
 
     pub fn iter_mut(&mut self) -> IterMut<T> {
         IterMut {
+            arena_id: self.arena_id,
             dead: &self.dead,
-            inner: self.inner.iter_mut(),
+            inner: self.items.iter_mut().enumerate(),
         }
     }
 
@@ -113,8 +158,11 @@ impl<T> TombstoneArena<T> {
     where
         T: Sync,
     {
-        self.inner
+        let arena_id = self.arena_id;
+        self.items
             .par_iter()
+            .enumerate()
+            .map(move |(i, item)| (Behavior::new_id(arena_id, i), item))
             .filter(move |&(id, _)| !self.dead.contains(&id))
     }
 
@@ -124,8 +172,9 @@ impl<T> TombstoneArena<T> {
         T: Send + Sync,
     {
         ParIterMut {
+            arena_id: self.arena_id,
             dead: &self.dead,
-            inner: self.inner.par_iter_mut(),
+            inner: self.items.par_iter_mut().enumerate(),
         }
     }
 }
@@ -135,21 +184,24 @@ impl<T> Index<Id<T>> for TombstoneArena<T> {
 
     fn index(&self, id: Id<T>) -> &T {
         assert!(!self.dead.contains(&id));
-        &self.inner[id]
+        assert_eq!(self.arena_id, Behavior::arena_id(id));
+        &self.items[id.index()]
     }
 }
 
 impl<T> IndexMut<Id<T>> for TombstoneArena<T> {
     fn index_mut(&mut self, id: Id<T>) -> &mut T {
         assert!(!self.dead.contains(&id));
-        &mut self.inner[id]
+        assert_eq!(self.arena_id, Behavior::arena_id(id));
+        &mut self.items[id.index()]
     }
 }
 
 #[derive(Debug)]
 pub struct IterMut<'a, T: 'a> {
+    arena_id: u32,
     dead: &'a IdHashSet<T>,
-    inner: id_arena::IterMut<'a, T, id_arena::DefaultArenaBehavior<T>>,
+    inner: Enumerate<slice::IterMut<'a, T>>,
 }
 
 impl<'a, T: 'a> Iterator for IterMut<'a, T> {
@@ -157,9 +209,10 @@ impl<'a, T: 'a> Iterator for IterMut<'a, T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            match self.inner.next() {
-                Some((id, _)) if self.dead.contains(&id) => continue,
-                x => return x,
+            let (i, item) = self.inner.next()?;
+            let id = Behavior::new_id(self.arena_id, i);
+            if !self.dead.contains(&id) {
+                return Some((id, item));
             }
         }
     }
@@ -168,8 +221,9 @@ impl<'a, T: 'a> Iterator for IterMut<'a, T> {
 #[derive(Debug)]
 #[cfg(feature = "parallel")]
 pub struct ParIterMut<'a, T: 'a + Send + Sync> {
+    arena_id: u32,
     dead: &'a IdHashSet<T>,
-    inner: id_arena::ParIterMut<'a, T, id_arena::DefaultArenaBehavior<T>>,
+    inner: rayon::iter::Enumerate<rayon::slice::IterMut<'a, T>>,
 }
 
 #[cfg(feature = "parallel")]
@@ -183,8 +237,10 @@ where
     where
         C: UnindexedConsumer<Self::Item>,
     {
+        let arena_id = self.arena_id;
         let dead = self.dead;
         self.inner
+            .map(move |(i, item)| (Behavior::new_id(arena_id, i), item))
             .filter(move |&(id, _)| !dead.contains(&id))
             .drive_unindexed(consumer)
     }
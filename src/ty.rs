@@ -4,6 +4,7 @@ use crate::emit::{Emit, EmitContext};
 use crate::encode::Encoder;
 use crate::error::Result;
 use crate::tombstone_arena::Tombstone;
+use crate::Module;
 use anyhow::bail;
 use id_arena::Id;
 use std::cmp::Ordering;
@@ -153,6 +154,40 @@ pub enum ValType {
 }
 
 impl ValType {
+    /// The size of a value of this type, in bytes, as it would be laid out in
+    /// linear memory.
+    ///
+    /// Reference types are opaque and their real size depends on the engine
+    /// running the module, so for `externref` and `funcref` this returns an
+    /// abstract size of 4 bytes: the size of a pointer on wasm32.
+    pub fn size_in_bytes(&self) -> u32 {
+        match self {
+            ValType::I32 | ValType::F32 => 4,
+            ValType::I64 | ValType::F64 => 8,
+            ValType::V128 => 16,
+            ValType::Externref | ValType::Funcref => 4,
+        }
+    }
+
+    /// The natural alignment of a value of this type, in bytes.
+    ///
+    /// This is always equal to `size_in_bytes`.
+    pub fn natural_alignment(&self) -> u32 {
+        self.size_in_bytes()
+    }
+
+    /// Is this type a subtype of `other`, so that a value of this type can be
+    /// used wherever a value of type `other` is expected?
+    ///
+    /// Of the reference types currently supported, `funcref` and `externref`
+    /// are unrelated, so this is the same as checking for equality. The module
+    /// that the types belong to is taken so that this can be extended to typed
+    /// function references, whose subtyping depends on the module's types.
+    pub fn is_subtype_of(&self, other: &ValType, module: &Module) -> bool {
+        let _ = module;
+        self == other
+    }
+
     pub(crate) fn from_wasmparser_type(ty: wasmparser::Type) -> Result<Box<[ValType]>> {
         let v = match ty {
             wasmparser::Type::EmptyBlockType => Vec::new(),
@@ -210,3 +245,32 @@ impl Emit for ValType {
         self.emit(&mut cx.encoder);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ValType;
+    use crate::Module;
+
+    #[test]
+    fn is_subtype_of() {
+        let module = Module::default();
+        let all = [
+            ValType::I32,
+            ValType::I64,
+            ValType::F32,
+            ValType::F64,
+            ValType::V128,
+            ValType::Externref,
+            ValType::Funcref,
+        ];
+        for a in all.iter() {
+            for b in all.iter() {
+                assert_eq!(a.is_subtype_of(b, &module), a == b, "{} <: {}", a, b);
+            }
+        }
+        assert!(ValType::Funcref.is_subtype_of(&ValType::Funcref, &module));
+        assert!(!ValType::Funcref.is_subtype_of(&ValType::Externref, &module));
+        assert!(!ValType::Externref.is_subtype_of(&ValType::Funcref, &module));
+        assert!(!ValType::I32.is_subtype_of(&ValType::I64, &module));
+    }
+}